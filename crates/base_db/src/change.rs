@@ -13,23 +13,119 @@
 
 use std::fmt;
 use std::sync::Arc;
+use std::sync::Mutex;
 
+use fxhash::FxHashMap;
+use fxhash::FxHashSet;
 use vfs::AbsPathBuf;
 use vfs::FileId;
+use vfs::VfsPath;
 
+use crate::LineEndings;
 use crate::RootQueryDb;
 use crate::SourceRoot;
 use crate::SourceRootId;
 use crate::input::AppStructure;
 
+/// How many files are assigned to their source roots between two
+/// progress reports, see `Change::apply_with_progress`.
+pub const PROGRESS_INTERVAL: usize = 1000;
+
 /// Encapsulate a bunch of raw `.set` calls on the database.
-#[derive(Clone, Default)]
+#[derive(Clone, Default, PartialEq, Eq)]
 pub struct Change {
     pub roots: Option<Vec<SourceRoot>>,
     pub files_changed: Vec<(FileId, Option<Arc<str>>)>,
     pub app_structure: Option<AppStructure>,
+    pub root_edits: Vec<SourceRootEdit>,
+    /// Replace `\r\n` with `\n` in the file texts set, see
+    /// `AppliedFiles::dos_line_endings`. Off by default, so that the
+    /// texts are set byte for byte.
+    pub normalize_line_endings: bool,
+}
+
+/// The files touched by applying a `Change`, see `Change::apply_detailed`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AppliedFiles {
+    /// Files whose text was set.
+    pub changed: Vec<FileId>,
+    /// Files that were removed, whose text was reset.
+    pub removed: Vec<FileId>,
+    /// Changed files whose text had `\r\n` line endings, normalized
+    /// to `\n`. Use `LineEndings::Dos` to restore them on save.
+    pub dos_line_endings: Vec<FileId>,
+}
+
+impl AppliedFiles {
+    /// The changed files followed by the removed ones.
+    pub fn into_files(self) -> Vec<FileId> {
+        let mut files = self.changed;
+        files.extend(self.removed);
+        files
+    }
+}
+
+/// An incremental edit of a single `SourceRoot`, applied on top of
+/// whatever roots are current when the `Change` is applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SourceRootEdit {
+    AddFile {
+        root: SourceRootId,
+        file_id: FileId,
+        path: VfsPath,
+    },
+    RemoveFile {
+        root: SourceRootId,
+        file_id: FileId,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SourceRootEditError {
+    RootMissing(SourceRootId),
+    FileAlreadyPresent {
+        root: SourceRootId,
+        file_id: FileId,
+    },
+    FileMissing {
+        root: SourceRootId,
+        file_id: FileId,
+    },
+    /// The app structure refers to a source root that neither the
+    /// change nor the database has.
+    AppStructureRootMissing {
+        root: SourceRootId,
+        referenced_by: String,
+    },
+}
+
+impl fmt::Display for SourceRootEditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceRootEditError::RootMissing(root) => {
+                write!(f, "source root {root:?} does not exist")
+            }
+            SourceRootEditError::FileAlreadyPresent { root, file_id } => {
+                write!(f, "file {file_id:?} is already in source root {root:?}")
+            }
+            SourceRootEditError::FileMissing { root, file_id } => {
+                write!(f, "file {file_id:?} is not in source root {root:?}")
+            }
+            SourceRootEditError::AppStructureRootMissing {
+                root,
+                referenced_by,
+            } => {
+                write!(
+                    f,
+                    "source root {root:?} referenced by {referenced_by} does not exist"
+                )
+            }
+        }
+    }
 }
 
+impl std::error::Error for SourceRootEditError {}
+
 impl fmt::Debug for Change {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let mut d = fmt.debug_struct("Change");
@@ -42,6 +138,12 @@ impl fmt::Debug for Change {
         if self.app_structure.is_some() {
             d.field("app_structure", &self.app_structure);
         }
+        if !self.root_edits.is_empty() {
+            d.field("root_edits", &self.root_edits);
+        }
+        if self.normalize_line_endings {
+            d.field("normalize_line_endings", &true);
+        }
         d.finish()
     }
 }
@@ -51,46 +153,863 @@ impl Change {
         Change::default()
     }
 
+    pub fn builder() -> ChangeBuilder {
+        ChangeBuilder::default()
+    }
+
+    /// A change setting the roots of `db` to `new_roots`, which only
+    /// edits the roots and files that differ, so that queries depending
+    /// on the others stay valid. Roots are set in full if some of
+    /// `new_roots` don't exist in `db` yet, as edits can't add roots.
+    pub fn diff_roots(db: &dyn RootQueryDb, new_roots: &[SourceRoot]) -> Change {
+        let mut change = Change::new();
+        let all_exist =
+            (0..new_roots.len()).all(|idx| db.has_source_root(SourceRootId(idx as u32)));
+        if !all_exist {
+            change.set_roots(new_roots.to_vec());
+            return change;
+        }
+        for (idx, new_root) in new_roots.iter().enumerate() {
+            let root_id = SourceRootId(idx as u32);
+            let old_root = db.source_root(root_id).source_root(db);
+            if *old_root == *new_root {
+                continue;
+            }
+            let mut old_files: Vec<FileId> = old_root.iter().collect();
+            old_files.sort();
+            for file_id in old_files {
+                let old_path = old_root.path_for_file(&file_id);
+                if new_root.path_for_file(&file_id) != old_path {
+                    change.remove_file_from_root(root_id, file_id);
+                }
+            }
+            let mut new_files: Vec<FileId> = new_root.iter().collect();
+            new_files.sort();
+            for file_id in new_files {
+                let new_path = new_root.path_for_file(&file_id);
+                if let Some(path) =
+                    new_path.filter(|path| old_root.path_for_file(&file_id) != Some(*path))
+                {
+                    change.add_file_to_root(root_id, file_id, path.clone());
+                }
+            }
+        }
+        change
+    }
+
+    /// Check what can be checked without a database: when the change
+    /// sets both the roots and the app structure, the app structure must
+    /// only reference the new roots.
+    pub fn validate(&self) -> Result<(), SourceRootEditError> {
+        if let (Some(roots), Some(app_structure)) = (&self.roots, &self.app_structure) {
+            app_structure.validate(&|root_id| (root_id.0 as usize) < roots.len())?;
+        }
+        Ok(())
+    }
+
+    /// Whether applying the change would leave the database as it is.
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_none()
+            && self.files_changed.is_empty()
+            && self.app_structure.is_none()
+            && self.root_edits.is_empty()
+    }
+
+    /// Reset the change, so that it can be reused.
+    pub fn clear(&mut self) {
+        *self = Change::default();
+    }
+
     pub fn set_roots(&mut self, roots: Vec<SourceRoot>) {
         self.roots = Some(roots);
     }
 
+    /// Add a single file to an existing source root, without
+    /// replacing the rest of the roots.
+    pub fn add_file_to_root(&mut self, root: SourceRootId, file_id: FileId, path: VfsPath) {
+        self.root_edits.push(SourceRootEdit::AddFile {
+            root,
+            file_id,
+            path,
+        });
+    }
+
+    /// Remove a single file from an existing source root, without
+    /// replacing the rest of the roots.
+    pub fn remove_file_from_root(&mut self, root: SourceRootId, file_id: FileId) {
+        self.root_edits
+            .push(SourceRootEdit::RemoveFile { root, file_id });
+    }
+
     pub fn change_file(&mut self, file_id: FileId, new_text: Option<Arc<str>>) {
         self.files_changed.push((file_id, new_text))
     }
 
+    /// Whether to normalize the line endings of the file texts set, see
+    /// `Change::normalize_line_endings`.
+    pub fn set_normalize_line_endings(&mut self, normalize: bool) {
+        self.normalize_line_endings = normalize;
+    }
+
+    /// Set the text of a file.
+    pub fn change_file_text(&mut self, file_id: FileId, new_text: impl Into<Arc<str>>) {
+        self.change_file(file_id, Some(new_text.into()))
+    }
+
+    /// Mark a file as removed. Its text is reset, see `apply`.
+    pub fn remove_file(&mut self, file_id: FileId) {
+        self.change_file(file_id, None)
+    }
+
     pub fn set_app_structure(&mut self, a: AppStructure) {
         self.app_structure = Some(a);
     }
 
+    /// Set the inputs of `db` according to the change, as one
+    /// transaction: the `&mut` borrow guarantees that no other change is
+    /// applied, and no query runs, until it is done. Do not obtain it
+    /// through interior mutability, use `apply_locked` to share the
+    /// database between threads instead.
     pub fn apply(
         self,
         db: &mut dyn RootQueryDb,
         resolve_file_id: &impl Fn(&AbsPathBuf) -> Option<FileId>,
-    ) -> Vec<FileId> {
+    ) -> Result<Vec<FileId>, SourceRootEditError> {
+        self.apply_with_progress(db, resolve_file_id, None)
+    }
+
+    /// Like `apply`, telling the files whose text was set apart from
+    /// the removed ones.
+    pub fn apply_detailed(
+        self,
+        db: &mut dyn RootQueryDb,
+        resolve_file_id: &impl Fn(&AbsPathBuf) -> Option<FileId>,
+    ) -> Result<AppliedFiles, SourceRootEditError> {
+        self.apply_impl(db, resolve_file_id, None)
+    }
+
+    /// Like `apply`, for a database shared behind a lock, which is held
+    /// for the whole change, so that concurrent changes are applied one
+    /// after the other.
+    pub fn apply_locked<D: RootQueryDb>(
+        self,
+        db: &Mutex<D>,
+        resolve_file_id: &impl Fn(&AbsPathBuf) -> Option<FileId>,
+    ) -> Result<Vec<FileId>, SourceRootEditError> {
+        let mut db = db
+            .lock()
+            .expect("a change panicked while applied to the database");
+        self.apply(&mut *db, resolve_file_id)
+    }
+
+    /// Like `apply`, reporting `(files_processed, total)` to `progress`
+    /// while the files of new roots are assigned to them, every
+    /// `PROGRESS_INTERVAL` files and once they all are.
+    pub fn apply_with_progress(
+        self,
+        db: &mut dyn RootQueryDb,
+        resolve_file_id: &impl Fn(&AbsPathBuf) -> Option<FileId>,
+        progress: Option<&mut dyn FnMut(usize, usize)>,
+    ) -> Result<Vec<FileId>, SourceRootEditError> {
+        self.apply_impl(db, resolve_file_id, progress)
+            .map(AppliedFiles::into_files)
+    }
+
+    fn apply_impl(
+        self,
+        db: &mut dyn RootQueryDb,
+        resolve_file_id: &impl Fn(&AbsPathBuf) -> Option<FileId>,
+        mut progress: Option<&mut dyn FnMut(usize, usize)>,
+    ) -> Result<AppliedFiles, SourceRootEditError> {
         let _p = tracing::info_span!("RootDatabase::apply_change").entered();
+        // Setting no input doesn't need a new revision.
+        if self.is_empty() {
+            return Ok(AppliedFiles::default());
+        }
+        // Validate the root edits up front, so that a failing edit
+        // leaves the database untouched.
+        let edited_roots = self.edited_roots(db)?;
+        if let Some(app_structure) = &self.app_structure {
+            app_structure.validate(&|root_id| {
+                self.roots
+                    .as_ref()
+                    .is_some_and(|roots| (root_id.0 as usize) < roots.len())
+                    || db.has_source_root(root_id)
+            })?;
+        }
+        let roots_span = tracing::info_span!("Change::set_roots").entered();
         if let Some(roots) = self.roots {
-            for (idx, root) in roots.into_iter().enumerate() {
-                let root_id = SourceRootId(idx as u32);
+            // Setting a root equal to the current one would only
+            // invalidate the queries depending on it and its files, as
+            // config reloads often do, so such roots are left alone.
+            let roots: Vec<(SourceRootId, SourceRoot)> = roots
+                .into_iter()
+                .enumerate()
+                .map(|(idx, root)| (SourceRootId(idx as u32), root))
+                .filter(|(root_id, root)| {
+                    !db.has_source_root(*root_id)
+                        || *db.source_root(*root_id).source_root(db) != *root
+                })
+                .collect();
+            let total = match progress {
+                Some(_) => roots.iter().map(|(_, root)| root.iter().count()).sum(),
+                None => 0,
+            };
+            let mut processed = 0;
+            for (root_id, root) in roots {
                 for file_id in root.iter() {
-                    db.set_file_source_root(file_id, root_id);
+                    db.set_file_source_root(
+                        file_id,
+                        root_id,
+                        root.path_for_file(&file_id).cloned(),
+                    );
+                    if let Some(progress) = &mut progress {
+                        processed += 1;
+                        if processed % PROGRESS_INTERVAL == 0 {
+                            progress(processed, total);
+                        }
+                    }
                 }
                 db.set_source_root(root_id, Arc::new(root));
             }
+            if let Some(progress) = &mut progress {
+                if processed == 0 || processed % PROGRESS_INTERVAL != 0 {
+                    progress(processed, total);
+                }
+            }
+        }
+
+        // Each file has its own input for the root it is in, which the
+        // queries about it alone read, so only the added and removed
+        // files invalidate them. The file sets of the edited roots
+        // change too, for the queries listing their files.
+        for edit in &self.root_edits {
+            match edit {
+                SourceRootEdit::AddFile {
+                    root,
+                    file_id,
+                    path,
+                } => db.set_file_source_root(*file_id, *root, Some(path.clone())),
+                SourceRootEdit::RemoveFile { file_id, .. } => {
+                    // Left to the catch-all root, as for files in no root
+                    let catch_all = match &self.app_structure {
+                        Some(app_structure) => app_structure.catch_all_source_root,
+                        None => db.catch_all_source_root(),
+                    };
+                    db.set_file_source_root(*file_id, catch_all, None)
+                }
+            }
+        }
+        for (root_id, root) in edited_roots {
+            db.set_source_root(root_id, Arc::new(root));
         }
+        drop(roots_span);
 
         if let Some(set_app_structure) = self.app_structure {
+            let _p = tracing::info_span!("Change::set_app_structure").entered();
             set_app_structure.apply(db, resolve_file_id);
         }
 
-        let mut res = vec![];
+        let _p = tracing::info_span!("Change::set_file_texts").entered();
+        let mut res = AppliedFiles::default();
+        // Files with the same text, such as generated stubs, share it
+        let mut texts: FxHashSet<Arc<str>> = FxHashSet::default();
         for (file_id, text) in self.files_changed {
-            // XXX: can't actually remove the file, just reset the text
+            match text {
+                Some(mut text) => {
+                    // Only copy the texts that need normalizing
+                    if self.normalize_line_endings && text.contains('\r') {
+                        let (normalized, endings) = LineEndings::normalize(text.to_string());
+                        if endings == LineEndings::Dos {
+                            text = Arc::from(normalized);
+                            res.dos_line_endings.push(file_id);
+                        }
+                    }
+                    match texts.get(&text) {
+                        Some(shared) => text = shared.clone(),
+                        None => {
+                            texts.insert(text.clone());
+                        }
+                    }
+                    db.set_file_text(file_id, text);
+                    res.changed.push(file_id);
+                }
+                None => {
+                    // XXX: can't actually remove the file, just reset the text
+                    db.set_file_text(file_id, Arc::from(""));
+                    res.removed.push(file_id);
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    fn edited_roots(
+        &self,
+        db: &dyn RootQueryDb,
+    ) -> Result<FxHashMap<SourceRootId, SourceRoot>, SourceRootEditError> {
+        let mut edited: FxHashMap<SourceRootId, SourceRoot> = FxHashMap::default();
+        for edit in &self.root_edits {
+            let root_id = match edit {
+                SourceRootEdit::AddFile { root, .. } | SourceRootEdit::RemoveFile { root, .. } => {
+                    *root
+                }
+            };
+            if !edited.contains_key(&root_id) {
+                let root = self
+                    .roots
+                    .as_ref()
+                    .and_then(|roots| roots.get(root_id.0 as usize).cloned())
+                    .or_else(|| {
+                        db.has_source_root(root_id)
+                            .then(|| (*db.source_root(root_id).source_root(db)).clone())
+                    })
+                    .ok_or(SourceRootEditError::RootMissing(root_id))?;
+                edited.insert(root_id, root);
+            }
+            let root = edited.get_mut(&root_id).unwrap();
+            match edit {
+                SourceRootEdit::AddFile { file_id, path, .. } => {
+                    if !root.insert_file(*file_id, path.clone()) {
+                        return Err(SourceRootEditError::FileAlreadyPresent {
+                            root: root_id,
+                            file_id: *file_id,
+                        });
+                    }
+                }
+                SourceRootEdit::RemoveFile { file_id, .. } => {
+                    if !root.remove_file(*file_id) {
+                        return Err(SourceRootEditError::FileMissing {
+                            root: root_id,
+                            file_id: *file_id,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(edited)
+    }
+}
+
+/// Builds a `Change` fluently, see `Change::builder`.
+#[derive(Debug, Default)]
+pub struct ChangeBuilder {
+    change: Change,
+}
+
+impl ChangeBuilder {
+    pub fn with_roots(mut self, roots: Vec<SourceRoot>) -> Self {
+        self.change.set_roots(roots);
+        self
+    }
+
+    pub fn change_file(mut self, file_id: FileId, new_text: Option<Arc<str>>) -> Self {
+        self.change.change_file(file_id, new_text);
+        self
+    }
+
+    pub fn set_app_structure(mut self, app_structure: AppStructure) -> Self {
+        self.change.set_app_structure(app_structure);
+        self
+    }
+
+    pub fn normalize_line_endings(mut self, normalize: bool) -> Self {
+        self.change.set_normalize_line_endings(normalize);
+        self
+    }
+
+    /// The change built, once `Change::validate` accepts it.
+    pub fn build(self) -> Result<Change, SourceRootEditError> {
+        self.change.validate()?;
+        Ok(self.change)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use crate::AppStructure;
+    use crate::AppliedFiles;
+    use crate::Change;
+    use crate::FileId;
+    use crate::FileSet;
+    use crate::LineEndings;
+    use crate::PROGRESS_INTERVAL;
+    use crate::RootQueryDb;
+    use crate::SourceDatabase;
+    use crate::SourceRoot;
+    use crate::SourceRootEditError;
+    use crate::SourceRootId;
+    use crate::VfsPath;
+    use crate::fixture::WithFixture;
+    use crate::test_db::TestDB;
+
+    fn file_kind_executions(db: &TestDB, files: &[FileId]) -> usize {
+        db.log_executed(|| {
+            for file_id in files {
+                db.file_kind(*file_id);
+            }
+        })
+        .iter()
+        .filter(|name| name.contains("file_kind"))
+        .count()
+    }
+
+    #[test]
+    fn add_file_to_root_only_invalidates_edited_root() {
+        let (mut db, files, _) = TestDB::with_many_files(
+            r#"
+//- /app_a/src/a.erl app:app_a
+-module(a).
+//- /app_b/src/b.erl app:app_b
+-module(b).
+"#,
+        );
+        let (a, b) = (files[0], files[1]);
+        assert_eq!(file_kind_executions(&db, &[a, b]), 2);
+
+        let root = db.file_source_root(a).source_root_id(&db);
+        let new_file = FileId::from_raw(100);
+        let mut change = Change::new();
+        change.change_file_text(new_file, "-module(c).");
+        change.add_file_to_root(
+            root,
+            new_file,
+            VfsPath::new_virtual_path("/app_a/src/c.erl".to_string()),
+        );
+        change.apply(&mut db, &|_| None).unwrap();
+
+        // Only `c` is new, `a` in the same root and `b` are untouched.
+        assert_eq!(file_kind_executions(&db, &[a, b, new_file]), 1);
+        assert_eq!(db.file_source_root(new_file).source_root_id(&db), root);
+    }
+
+    #[test]
+    fn remove_file_from_root_resets_file_source_root() {
+        let (mut db, files, _) = TestDB::with_many_files(
+            r#"
+//- /app_a/src/a.erl app:app_a
+-module(a).
+//- /app_a/src/b.erl app:app_a
+-module(b).
+"#,
+        );
+        let (a, b) = (files[0], files[1]);
+        assert_eq!(file_kind_executions(&db, &[a, b]), 2);
+        let root = db.file_source_root(a).source_root_id(&db);
+
+        let mut change = Change::new();
+        change.remove_file_from_root(root, b);
+        change.apply(&mut db, &|_| None).unwrap();
+
+        let file_source_root = db.file_source_root(b);
+        assert_eq!(
+            file_source_root.source_root_id(&db),
+            db.catch_all_source_root()
+        );
+        assert_eq!(file_source_root.path(&db), None);
+        // `a` stays in the root and is untouched.
+        assert_eq!(file_kind_executions(&db, &[a, b]), 1);
+    }
+
+    #[test]
+    fn empty_change() {
+        let (mut db, file_id) = TestDB::with_single_file("-module(a).");
+        assert!(Change::new().is_empty());
+        assert_eq!(Change::new().apply(&mut db, &|_| None), Ok(vec![]));
+
+        let mut change = Change::new();
+        change.change_file_text(file_id, "-module(b).");
+        assert!(!change.is_empty());
+        change.clear();
+        assert!(change.is_empty());
+        change.apply(&mut db, &|_| None).unwrap();
+        assert_eq!(&*db.file_text(file_id).text(&db), "-module(a).");
+
+        let mut change = Change::new();
+        change.remove_file_from_root(db.file_source_root(file_id).source_root_id(&db), file_id);
+        assert!(!change.is_empty());
+    }
+
+    #[test]
+    fn change_file_text() {
+        let (mut db, files, _) = TestDB::with_many_files(
+            r#"
+//- /src/a.erl
+-module(a).
+//- /src/b.erl
+-module(b).
+"#,
+        );
+        let (a, b) = (files[0], files[1]);
+        let mut change = Change::new();
+        change.change_file_text(a, String::from("-module(a2)."));
+        change.change_file_text(b, "-module(b2).");
+        assert_eq!(change.apply(&mut db, &|_| None), Ok(vec![a, b]));
+        assert_eq!(&*db.file_text(a).text(&db), "-module(a2).");
+        assert_eq!(&*db.file_text(b).text(&db), "-module(b2).");
+
+        let mut change = Change::new();
+        change.remove_file(b);
+        change.apply(&mut db, &|_| None).unwrap();
+        assert_eq!(&*db.file_text(b).text(&db), "");
+    }
+
+    #[test]
+    fn app_structure_with_missing_root() {
+        let (mut db, file_id) = TestDB::with_single_file("-module(a).");
+        let missing = SourceRootId(1000);
+        let mut app_structure = AppStructure::default();
+        app_structure.add_app_data(missing, None, None);
+
+        let mut change = Change::new();
+        change.change_file_text(file_id, "-module(b).");
+        change.set_app_structure(app_structure);
+        assert_eq!(
+            change.apply(&mut db, &|_| None),
+            Err(SourceRootEditError::AppStructureRootMissing {
+                root: missing,
+                referenced_by: "an app without data".to_string(),
+            })
+        );
+        // Nothing was applied
+        assert_eq!(&*db.file_text(file_id).text(&db), "-module(a).");
+    }
+
+    #[test]
+    fn apply_reports_progress() {
+        let (mut db, _) = TestDB::with_single_file("-module(a).");
+        let total = PROGRESS_INTERVAL + 1;
+        let mut file_set = FileSet::default();
+        for idx in 0..total {
+            file_set.insert(
+                FileId::from_raw(1000 + idx as u32),
+                VfsPath::new_virtual_path(format!("/src/m{idx}.erl")),
+            );
+        }
+        let mut change = Change::new();
+        change.set_roots(vec![SourceRoot::new(file_set)]);
+
+        let mut reports = Vec::new();
+        change
+            .apply_with_progress(
+                &mut db,
+                &|_| None,
+                Some(&mut |processed, total| reports.push((processed, total))),
+            )
+            .unwrap();
+        assert_eq!(reports, vec![(PROGRESS_INTERVAL, total), (total, total)]);
+    }
+
+    #[test]
+    fn apply_locked_serializes_changes() {
+        let (db, files, _) = TestDB::with_many_files(
+            r#"
+//- /src/a.erl
+-module(a).
+//- /src/b.erl
+-module(b).
+"#,
+        );
+        let (a, b) = (files[0], files[1]);
+        let db = Mutex::new(db);
+        std::thread::scope(|scope| {
+            for thread in 0..2 {
+                let db = &db;
+                scope.spawn(move || {
+                    for n in 0..50 {
+                        let text = format!("%% {thread}-{n}");
+                        let mut change = Change::new();
+                        change.change_file_text(a, text.as_str());
+                        change.change_file_text(b, text.as_str());
+                        change.apply_locked(db, &|_| None).unwrap();
+                        // Both files always come from the same change.
+                        let db = db.lock().unwrap();
+                        assert_eq!(db.file_text(a).text(&*db), db.file_text(b).text(&*db));
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn apply_detailed_partitions_files() {
+        let (mut db, files, _) = TestDB::with_many_files(
+            r#"
+//- /src/a.erl
+-module(a).
+//- /src/b.erl
+-module(b).
+//- /src/c.erl
+-module(c).
+"#,
+        );
+        let (a, b, c) = (files[0], files[1], files[2]);
+        let mut change = Change::new();
+        change.remove_file(a);
+        change.change_file_text(b, "-module(b2).");
+        change.remove_file(c);
+        assert_eq!(
+            change.apply_detailed(&mut db, &|_| None),
+            Ok(AppliedFiles {
+                changed: vec![b],
+                removed: vec![a, c],
+                dos_line_endings: vec![],
+            })
+        );
+
+        let mut change = Change::new();
+        change.remove_file(a);
+        change.change_file_text(b, "-module(b3).");
+        assert_eq!(change.apply(&mut db, &|_| None), Ok(vec![b, a]));
+    }
+
+    #[test]
+    fn normalize_line_endings_round_trip() {
+        let (mut db, files, _) = TestDB::with_many_files(
+            r#"
+//- /src/a.erl
+-module(a).
+//- /src/b.erl
+-module(b).
+"#,
+        );
+        let (a, b) = (files[0], files[1]);
+        let crlf = "-module(a).\r\nf() -> ok.\r\n";
+
+        let mut change = Change::new();
+        change.change_file_text(a, crlf);
+        change.apply(&mut db, &|_| None).unwrap();
+        assert_eq!(&*db.file_text(a).text(&db), crlf);
+
+        let mut change = Change::new();
+        change.set_normalize_line_endings(true);
+        change.change_file_text(a, crlf);
+        change.change_file_text(b, "-module(b).\n");
+        assert_eq!(
+            change.apply_detailed(&mut db, &|_| None),
+            Ok(AppliedFiles {
+                changed: vec![a, b],
+                removed: vec![],
+                dos_line_endings: vec![a],
+            })
+        );
+        let text = db.file_text(a).text(&db);
+        assert_eq!(&*text, "-module(a).\nf() -> ok.\n");
+        assert_eq!(LineEndings::Dos.revert(text.to_string()), crlf);
+        assert_eq!(&*db.file_text(b).text(&db), "-module(b).\n");
+    }
+
+    #[test]
+    fn identical_texts_are_shared() {
+        let (mut db, files, _) = TestDB::with_many_files(
+            r#"
+//- /src/a.erl
+-module(a).
+//- /src/b.erl
+-module(b).
+//- /src/c.erl
+-module(c).
+"#,
+        );
+        let (a, b, c) = (files[0], files[1], files[2]);
+        let stub = "%% generated\n";
+
+        let mut change = Change::new();
+        change.change_file_text(a, stub);
+        change.change_file_text(b, stub);
+        change.change_file_text(c, "-module(c).\n");
+        change.apply(&mut db, &|_| None).unwrap();
+        let (a_text, b_text) = (db.file_text(a).text(&db), db.file_text(b).text(&db));
+        assert_eq!(&*a_text, stub);
+        assert!(Arc::ptr_eq(&a_text, &b_text));
+        assert!(!Arc::ptr_eq(&a_text, &db.file_text(c).text(&db)));
+    }
+
+    #[test]
+    fn change_builder() {
+        let file_id = FileId::from_raw(0);
+        let mut file_set = FileSet::default();
+        file_set.insert(file_id, VfsPath::new_virtual_path("/src/a.erl".to_string()));
+        let roots = vec![SourceRoot::new(file_set)];
+        let mut app_structure = AppStructure::default();
+        app_structure.add_app_data(SourceRootId(0), None, None);
+
+        let built = Change::builder()
+            .with_roots(roots.clone())
+            .change_file(file_id, Some("-module(a).".into()))
+            .set_app_structure(app_structure.clone())
+            .build()
+            .unwrap();
+
+        let mut change = Change::new();
+        change.set_roots(roots.clone());
+        change.change_file(file_id, Some("-module(a).".into()));
+        change.set_app_structure(app_structure);
+        assert_eq!(built, change);
+
+        let mut app_structure = AppStructure::default();
+        app_structure.add_app_data(SourceRootId(1), None, None);
+        assert_eq!(
+            Change::builder()
+                .with_roots(roots)
+                .set_app_structure(app_structure)
+                .build(),
+            Err(SourceRootEditError::AppStructureRootMissing {
+                root: SourceRootId(1),
+                referenced_by: "an app without data".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn diff_roots_touches_only_added_file() {
+        let (mut db, files, _) = TestDB::with_many_files(
+            r#"
+//- /app_a/src/a.erl app:app_a
+-module(a).
+//- /app_b/src/b.erl app:app_b
+-module(b).
+"#,
+        );
+        let (a, b) = (files[0], files[1]);
+        assert_eq!(file_kind_executions(&db, &[a, b]), 2);
+        let mut roots: Vec<SourceRoot> = (0..)
+            .map(SourceRootId)
+            .take_while(|root_id| db.has_source_root(*root_id))
+            .map(|root_id| (*db.source_root(root_id).source_root(&db)).clone())
+            .collect();
+        assert_eq!(Change::diff_roots(&db, &roots), Change::new());
+
+        let root = db.file_source_root(b).source_root_id(&db);
+        let new_file = FileId::from_raw(100);
+        let path = VfsPath::new_virtual_path("/app_b/src/c.erl".to_string());
+        let mut file_set = FileSet::default();
+        for file_id in roots[root.0 as usize].iter() {
+            let path = roots[root.0 as usize].path_for_file(&file_id).unwrap();
+            file_set.insert(file_id, path.clone());
+        }
+        file_set.insert(new_file, path.clone());
+        roots[root.0 as usize] = SourceRoot::new(file_set);
+
+        let mut change = Change::diff_roots(&db, &roots);
+        let mut expected = Change::new();
+        expected.add_file_to_root(root, new_file, path);
+        assert_eq!(change, expected);
+
+        change.change_file_text(new_file, "-module(c).");
+        change.apply(&mut db, &|_| None).unwrap();
+        assert_eq!(db.file_source_root(new_file).source_root_id(&db), root);
+        // `b` shares the edited root, `a` is untouched.
+        assert_eq!(file_kind_executions(&db, &[a, b]), 1);
+    }
+
+    #[test]
+    fn identical_roots_invalidate_nothing() {
+        let (mut db, files, _) = TestDB::with_many_files(
+            r#"
+//- /app_a/src/a.erl app:app_a
+-module(a).
+//- /app_b/src/b.erl app:app_b
+-module(b).
+"#,
+        );
+        let (a, b) = (files[0], files[1]);
+        assert_eq!(file_kind_executions(&db, &[a, b]), 2);
+        let roots: Vec<SourceRoot> = (0..)
+            .map(SourceRootId)
+            .take_while(|root_id| db.has_source_root(*root_id))
+            .map(|root_id| (*db.source_root(root_id).source_root(&db)).clone())
+            .collect();
+
+        let mut change = Change::new();
+        change.set_roots(roots);
+        change.apply(&mut db, &|_| None).unwrap();
+        assert_eq!(file_kind_executions(&db, &[a, b]), 0);
+    }
+
+    /// Records the names of the spans created while it is the default
+    /// subscriber.
+    #[derive(Default)]
+    struct SpanRecorder {
+        names: Mutex<Vec<&'static str>>,
+    }
+
+    impl tracing::Subscriber for SpanRecorder {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut names = self.names.lock().unwrap();
+            names.push(span.metadata().name());
+            tracing::span::Id::from_u64(names.len() as u64)
+        }
+
+        fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
 
-            let text = text.unwrap_or_else(|| Arc::from(""));
-            db.set_file_text(file_id, text);
-            res.push(file_id);
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, _event: &tracing::Event<'_>) {}
+
+        fn enter(&self, _span: &tracing::span::Id) {}
+
+        fn exit(&self, _span: &tracing::span::Id) {}
+    }
+
+    #[test]
+    fn apply_traces_phases() {
+        let (mut db, file_id) = TestDB::with_single_file("-module(a).");
+        let mut file_set = FileSet::default();
+        file_set.insert(file_id, VfsPath::new_virtual_path("/src/a.erl".to_string()));
+        let mut app_structure = AppStructure::default();
+        app_structure.add_app_data(SourceRootId(0), None, None);
+        let mut change = Change::new();
+        change.set_roots(vec![SourceRoot::new(file_set)]);
+        change.set_app_structure(app_structure);
+        change.change_file_text(file_id, "-module(b).");
+
+        let recorder = Arc::new(SpanRecorder::default());
+        tracing::subscriber::with_default(recorder.clone(), || {
+            change.apply(&mut db, &|_| None).unwrap();
+        });
+        let names = recorder.names.lock().unwrap();
+        for phase in [
+            "Change::set_roots",
+            "Change::set_app_structure",
+            "Change::set_file_texts",
+        ] {
+            assert!(names.contains(&phase), "{phase} missing from {names:?}");
         }
-        res
+    }
+
+    #[test]
+    fn root_edit_errors() {
+        let (mut db, file_id) = TestDB::with_single_file("-module(a).");
+        let root = db.file_source_root(file_id).source_root_id(&db);
+        let path = VfsPath::new_virtual_path("/src/a.erl".to_string());
+
+        let mut change = Change::new();
+        change.add_file_to_root(root, file_id, path.clone());
+        assert_eq!(
+            change.apply(&mut db, &|_| None),
+            Err(SourceRootEditError::FileAlreadyPresent { root, file_id })
+        );
+
+        let missing = SourceRootId(1000);
+        let mut change = Change::new();
+        change.add_file_to_root(missing, FileId::from_raw(100), path);
+        assert_eq!(
+            change.apply(&mut db, &|_| None),
+            Err(SourceRootEditError::RootMissing(missing))
+        );
+
+        let mut change = Change::new();
+        change.remove_file_from_root(root, file_id);
+        change.apply(&mut db, &|_| None).unwrap();
+        let source_root = db.source_root(root).source_root(&db);
+        assert_eq!(source_root.iter().count(), 0);
     }
 }
@@ -17,7 +17,6 @@ use std::fs;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
-use std::sync::Arc;
 
 use elp_project_model::AppName;
 use elp_project_model::AppType;
@@ -86,7 +85,9 @@ pub trait WithFixture: Default + SourceDatabase + 'static {
     fn with_fixture(fixture_str: &str) -> (Self, ChangeFixture) {
         let (fixture, change) = ChangeFixture::parse(fixture_str);
         let mut db = Self::default();
-        change.apply(&mut db, &|path| fixture.resolve_file_id(path));
+        change
+            .apply(&mut db, &|path| fixture.resolve_file_id(path))
+            .expect("fixture change should apply");
         (db, fixture)
     }
 }
@@ -197,7 +198,7 @@ impl ChangeFixture {
 
             assert!(entry.path.starts_with(&source_root_prefix));
 
-            let app_name = entry.app_data.name.clone();
+            let app_key = app_key(&entry.app_data);
 
             if let Some(otp_extra) = entry.otp {
                 if otp.is_none() {
@@ -206,7 +207,7 @@ impl ChangeFixture {
             }
             app_map.combine(entry.app_data);
 
-            change.change_file(file_id, Some(Arc::from(entry.text)));
+            change.change_file_text(file_id, entry.text);
 
             let path = if diagnostics_enabled.needs_fixture_on_disk()
                 && entry.path.char_indices().nth(0) == Some((0, '/'))
@@ -218,7 +219,7 @@ impl ChangeFixture {
             } else {
                 VfsPath::new_real_path(entry.path)
             };
-            app_files.insert(app_name, file_id, path.clone());
+            app_files.insert(app_key, file_id, path.clone());
             files_by_path.insert(path, file_id);
             files.push(file_id);
             tags.insert(file_id, entry.tags);
@@ -230,9 +231,9 @@ impl ChangeFixture {
             // We need to add the erlang module to the file contents too.
             let erts_app: ProjectAppData = OTP_ERLANG_APP.clone();
             app_map.combine(erts_app.clone());
-            change.change_file(file_id, Some(Arc::from(OTP_ERLANG_MODULE.1.clone())));
+            change.change_file_text(file_id, OTP_ERLANG_MODULE.1.clone());
             app_files.insert(
-                erts_app.name,
+                app_key(&erts_app),
                 file_id,
                 VfsPath::new_real_path(OTP_ERLANG_MODULE.0.to_string_lossy().to_string()),
             );
@@ -296,6 +297,7 @@ impl ChangeFixture {
             let loaded_project = Project::load(
                 &manifest,
                 elp_config.eqwalizer,
+                elp_config.compile,
                 &BuckQueryConfig::BuildGeneratedCode,
                 &|_| {},
             )
@@ -321,7 +323,7 @@ impl ChangeFixture {
         // We must iterate here in project_apps order, it defines the
         // mapping of apps to SourceRootIds
         for (_project_id, app) in project_apps.all_apps {
-            if let Some(file_set) = app_files.app_map.get(&app.name) {
+            if let Some(file_set) = app_files.get(app) {
                 let root = SourceRoot::new(file_set.clone());
                 roots.push(root);
             } else {
@@ -411,14 +413,23 @@ fn inc_file_id(file_id: &mut FileId) {
     *file_id = FileId::from_raw(file_id.index() + 1);
 }
 
+/// Fixture apps are merged by name, except OTP apps, which are kept
+/// apart by directory so that several versions of one can coexist.
+type AppKey = (AppName, Option<AbsPathBuf>);
+
+fn app_key(app: &ProjectAppData) -> AppKey {
+    let dir = (app.app_type == AppType::Otp).then(|| app.dir.clone());
+    (app.name.clone(), dir)
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct AppMap {
-    app_map: FxHashMap<AppName, ProjectAppData>,
+    app_map: FxHashMap<AppKey, ProjectAppData>,
 }
 
 impl AppMap {
     fn combine(&mut self, other: ProjectAppData) {
-        match self.app_map.entry(other.name.clone()) {
+        match self.app_map.entry(app_key(&other)) {
             Entry::Occupied(mut occupied) => {
                 occupied.get_mut().combine(other);
             }
@@ -443,16 +454,27 @@ impl AppMap {
 
 #[derive(Debug, Clone, Default)]
 pub struct SourceRootMap {
-    app_map: FxHashMap<AppName, FileSet>,
+    app_map: FxHashMap<AppKey, FileSet>,
 }
 
 impl SourceRootMap {
-    fn insert(&mut self, app_name: AppName, file_id: FileId, path: VfsPath) {
+    fn insert(&mut self, app_key: AppKey, file_id: FileId, path: VfsPath) {
         self.app_map
-            .entry(app_name)
+            .entry(app_key)
             .or_default()
             .insert(file_id, path);
     }
+
+    /// Looks an app up by name alone if its directory doesn't match,
+    /// as when the project was reloaded from the fixture on disk.
+    fn get(&self, app: &ProjectAppData) -> Option<&FileSet> {
+        self.app_map.get(&app_key(app)).or_else(|| {
+            self.app_map
+                .iter()
+                .find(|((name, _), _)| *name == app.name)
+                .map(|(_, file_set)| file_set)
+        })
+    }
 }
 
 #[cfg(test)]
@@ -633,6 +655,9 @@ bar() -> ?FOO.
                                 "/",
                             ),
                             deps_ebins: [],
+                            macros: [],
+                            features: None,
+                            parse_transforms: [],
                             otp_project_id: Some(
                                 ProjectId(
                                     1,
@@ -649,6 +674,7 @@ bar() -> ?FOO.
                                                 2,
                                             ),
                                         },
+                                        duplicates: {},
                                     },
                                 ),
                                 app_map: {
@@ -663,10 +689,12 @@ bar() -> ?FOO.
                                         1,
                                     ),
                                 },
+                                duplicates: {},
                             },
                             eqwalizer_config: EqwalizerConfig {
                                 enable_all: true,
                                 max_tasks: 4,
+                                max_check_parallelism: None,
                             },
                             include_mapping: Some(
                                 IncludeMapping {
@@ -687,6 +715,9 @@ bar() -> ?FOO.
                                 "/opt/lib",
                             ),
                             deps_ebins: [],
+                            macros: [],
+                            features: None,
+                            parse_transforms: [],
                             otp_project_id: Some(
                                 ProjectId(
                                     1,
@@ -701,10 +732,12 @@ bar() -> ?FOO.
                                         2,
                                     ),
                                 },
+                                duplicates: {},
                             },
                             eqwalizer_config: EqwalizerConfig {
                                 enable_all: true,
                                 max_tasks: 4,
+                                max_check_parallelism: None,
                             },
                             include_mapping: Some(
                                 IncludeMapping {
@@ -820,6 +853,9 @@ foo() -> ?BAR.
                                 "/",
                             ),
                             deps_ebins: [],
+                            macros: [],
+                            features: None,
+                            parse_transforms: [],
                             otp_project_id: Some(
                                 ProjectId(
                                     1,
@@ -834,10 +870,12 @@ foo() -> ?BAR.
                                         0,
                                     ),
                                 },
+                                duplicates: {},
                             },
                             eqwalizer_config: EqwalizerConfig {
                                 enable_all: true,
                                 max_tasks: 4,
+                                max_check_parallelism: None,
                             },
                             include_mapping: Some(
                                 IncludeMapping {
@@ -854,6 +892,9 @@ foo() -> ?BAR.
                                 "/",
                             ),
                             deps_ebins: [],
+                            macros: [],
+                            features: None,
+                            parse_transforms: [],
                             otp_project_id: Some(
                                 ProjectId(
                                     1,
@@ -862,10 +903,12 @@ foo() -> ?BAR.
                             app_roots: AppRoots {
                                 otp: None,
                                 app_map: {},
+                                duplicates: {},
                             },
                             eqwalizer_config: EqwalizerConfig {
                                 enable_all: true,
                                 max_tasks: 4,
+                                max_check_parallelism: None,
                             },
                             include_mapping: Some(
                                 IncludeMapping {
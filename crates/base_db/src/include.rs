@@ -11,13 +11,19 @@
 use std::sync::Arc;
 
 use elp_syntax::SmolStr;
+use paths::AbsPath;
+use paths::AbsPathBuf;
 use vfs::FileId;
 use vfs::VfsPath;
 
+use crate::AbsPathExt;
 use crate::AppData;
+use crate::AppType;
+use crate::ProjectData;
 use crate::ProjectId;
 use crate::RootQueryDb;
 use crate::SourceRoot;
+use crate::SourceRootId;
 
 pub struct IncludeCtx<'a> {
     db: &'a dyn RootQueryDb,
@@ -48,6 +54,43 @@ impl<'a> IncludeCtx<'a> {
             .or_else(|| self.db.resolve_remote(self.file_id, path.into()))
     }
 
+    /// When the application an `-include_lib` path starts with is
+    /// available in several versions, none of which the including app
+    /// is built against, the directory of the version picked for it.
+    pub fn guessed_include_lib_app(&self, path: &str) -> Option<AbsPathBuf> {
+        if self.resolve_include(path).is_some() {
+            return None;
+        }
+        let project_id = self.db.file_project_id(self.file_id)?;
+        let project_data = self.db.project_data(project_id).project_data(self.db);
+        if let Some(include_mapping) = &project_data.include_mapping {
+            if include_mapping.get(&SmolStr::new(path)).is_some() {
+                return None;
+            }
+        }
+        let (app_name, _) = path.split_once('/')?;
+        match app_root_for_include_lib(self.db, self.file_id, &project_data, app_name)? {
+            (source_root_id, true) => Some(self.db.app_data(source_root_id)?.dir.clone()),
+            (_, false) => None,
+        }
+    }
+
+    /// When an `-include_lib` path can't be resolved, and the
+    /// application it starts with is an OTP one, the directory of that
+    /// application, under the configured OTP root if any.
+    pub fn unresolved_otp_include_lib(&self, path: &str) -> Option<AbsPathBuf> {
+        if self.resolve_include_lib(path).is_some() {
+            return None;
+        }
+        let project_id = self.db.file_project_id(self.file_id)?;
+        let project_data = self.db.project_data(project_id).project_data(self.db);
+        let (app_name, _) = path.split_once('/')?;
+        let (source_root_id, _) =
+            app_root_for_include_lib(self.db, self.file_id, &project_data, app_name)?;
+        let app_data = self.db.app_data(source_root_id)?;
+        (app_data.app_type == AppType::Otp).then(|| app_data.dir.clone())
+    }
+
     pub fn resolve_include_doc(&self, path: &str) -> Option<FileId> {
         self.resolve_relative(path)
     }
@@ -66,12 +109,12 @@ impl<'a> IncludeCtx<'a> {
         if let Some(file_id) = db.mapped_include_file(project_id, path.clone()) {
             Some(file_id)
         } else {
-            let path: &str = &path;
             let app_data = db.file_app_data(file_id)?;
-            app_data.include_path.iter().find_map(|include| {
-                let name = include.join(path);
-                db.include_file_id(app_data.project_id, VfsPath::from(name.clone()))
-            })
+            local_search_dirs(&app_data, &path)
+                .into_iter()
+                .find_map(|(dir, path)| {
+                    db.include_file_id(project_id, VfsPath::from(dir.join(path)))
+                })
         }
     }
 
@@ -91,24 +134,100 @@ impl<'a> IncludeCtx<'a> {
             None
         };
         include.unwrap_or_else(|| {
-            let (app_name, include_path) = path.split_once('/')?;
-            let source_root_id = project_data.app_roots.get(app_name)?;
-            let target_app_data = db.app_data(source_root_id)?;
-            let path = target_app_data.dir.join(include_path);
-            db.include_file_id(project_id, VfsPath::from(path.clone()))
-                .or_else(|| {
-                    find_generated_include_lib(db, project_id, include_path, &target_app_data)
+            remote_search_dirs(db, file_id, &project_data, &path)
+                .into_iter()
+                .find_map(|(dir, path)| {
+                    db.include_file_id(project_id, VfsPath::from(dir.join(path)))
                 })
         })
     }
+
+    /// Called via salsa for inserting in the graph
+    pub(crate) fn include_search_paths_query(
+        db: &dyn RootQueryDb,
+        file_id: FileId,
+        path: SmolStr,
+        kind: IncludeKind,
+    ) -> Arc<Vec<AbsPathBuf>> {
+        let mut paths: Vec<AbsPathBuf> = Vec::new();
+        let mut push = |dir: &AbsPath| {
+            if !paths.iter().any(|path| path.eq_ignoring_trailing_sep(dir)) {
+                paths.push(dir.to_path_buf());
+            }
+        };
+        // `resolve_relative`
+        let source_root_id = db.file_source_root(file_id).source_root_id(db);
+        let source_root = db.source_root(source_root_id).source_root(db);
+        if let Some(dir) = source_root
+            .path_for_file(&file_id)
+            .and_then(|path| path.as_path()?.parent())
+        {
+            push(dir);
+        }
+        let project_id = match db.file_project_id(file_id) {
+            Some(project_id) => project_id,
+            None => return Arc::new(paths),
+        };
+        let project_data = db.project_data(project_id).project_data(db);
+        // `resolve_local_query` and `resolve_remote_query` try the
+        // include mapping first, and the latter stops there if the path
+        // is mapped
+        let mapped = project_data
+            .include_mapping
+            .as_ref()
+            .and_then(|include_mapping| include_mapping.get(&path));
+        if let Some(dir) = mapped.and_then(|file| file.parent()) {
+            push(dir);
+        }
+        if let Some(app_data) = db.file_app_data(file_id) {
+            for (dir, _) in local_search_dirs(&app_data, &path) {
+                push(&dir);
+            }
+        }
+        if kind == IncludeKind::IncludeLib && mapped.is_none() {
+            for (dir, _) in remote_search_dirs(db, file_id, &project_data, &path) {
+                push(&dir);
+            }
+        }
+        Arc::new(paths)
+    }
 }
 
-fn find_generated_include_lib(
+/// Whether an include is an `-include` or an `-include_lib`, which is
+/// also looked up in the app its path starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IncludeKind {
+    Include,
+    IncludeLib,
+}
+
+/// Where `resolve_local_query` looks for an unmapped `path`, in order:
+/// each directory on the include path of the including app.
+fn local_search_dirs<'a>(app_data: &AppData, path: &'a str) -> Vec<(AbsPathBuf, &'a str)> {
+    app_data
+        .include_path
+        .iter()
+        .map(|dir| (dir.clone(), path))
+        .collect()
+}
+
+/// Where `resolve_remote_query` looks for an unmapped `app/rest` path,
+/// in order, each directory with the path to look up in it.
+fn remote_search_dirs<'a>(
     db: &dyn RootQueryDb,
-    project_id: ProjectId,
-    include_path: &str,
-    target_app_data: &AppData,
-) -> Option<FileId> {
+    file_id: FileId,
+    project_data: &ProjectData,
+    path: &'a str,
+) -> Vec<(AbsPathBuf, &'a str)> {
+    let (app_name, include_path) = match path.split_once('/') {
+        Some(split) => split,
+        None => return Vec::new(),
+    };
+    let target_app_data = match include_lib_app(db, file_id, project_data, app_name) {
+        Some(target_app_data) => target_app_data,
+        None => return Vec::new(),
+    };
+    let mut dirs = vec![(target_app_data.dir.clone(), include_path)];
     // buck2 builds create an include file mapping when
     // invoking the OTP compiler, by manipulating symlinks
     // in the output directory.
@@ -124,11 +243,85 @@ fn find_generated_include_lib(
     // set of include paths/
     // Note: we use the more relaxed include_path here, as the thrift
     // includes are not all for direct dependencies at present.
-    let path = include_path.strip_prefix("include/")?;
-    target_app_data.include_path.iter().find_map(|dir| {
-        let path = dir.join(path);
-        db.include_file_id(project_id, VfsPath::from(path.clone()))
-    })
+    if let Some(path) = include_path.strip_prefix("include/") {
+        dirs.extend(
+            target_app_data
+                .include_path
+                .iter()
+                .map(|dir| (dir.clone(), path)),
+        );
+    }
+    dirs
+}
+
+/// The app named `app_name` an `-include_lib` in `file_id` refers to.
+fn include_lib_app(
+    db: &dyn RootQueryDb,
+    file_id: FileId,
+    project_data: &ProjectData,
+    app_name: &str,
+) -> Option<Arc<AppData>> {
+    let (source_root_id, _) = app_root_for_include_lib(db, file_id, project_data, app_name)?;
+    db.app_data(source_root_id)
+}
+
+/// The source root of the app named `app_name` an `-include_lib` in
+/// `file_id` refers to, and whether it had to be guessed among several
+/// versions of the app.
+fn app_root_for_include_lib(
+    db: &dyn RootQueryDb,
+    file_id: FileId,
+    project_data: &ProjectData,
+    app_name: &str,
+) -> Option<(SourceRootId, bool)> {
+    let candidates = project_data.app_roots.candidates(app_name);
+    if let [source_root_id] = candidates[..] {
+        return Some((source_root_id, false));
+    }
+    let candidates: Vec<(SourceRootId, Arc<AppData>)> = candidates
+        .into_iter()
+        .filter_map(|source_root_id| Some((source_root_id, db.app_data(source_root_id)?)))
+        .collect();
+    select_app_version(&candidates, db.file_app_data(file_id).as_deref())
+}
+
+/// Prefer the version the including app is built against, i.e. the one
+/// its include path points into. Otherwise guess the latest version, as
+/// `code:lib_dir/1` would.
+fn select_app_version(
+    candidates: &[(SourceRootId, Arc<AppData>)],
+    includer: Option<&AppData>,
+) -> Option<(SourceRootId, bool)> {
+    if let Some(includer) = includer {
+        let pinned = candidates.iter().find(|(_, app)| {
+            includer
+                .include_path
+                .iter()
+                .any(|dir| dir.starts_with(&app.dir))
+        });
+        if let Some((source_root_id, _)) = pinned {
+            return Some((*source_root_id, false));
+        }
+    }
+    candidates
+        .iter()
+        .max_by_key(|(_, app)| app_version(app))
+        .map(|(source_root_id, _)| (*source_root_id, true))
+}
+
+/// The version in the directory name of an app, e.g. `[3, 17, 2]` for
+/// `stdlib-3.17.2`. Non-numeric parts count as 0.
+fn app_version(app: &AppData) -> Vec<u64> {
+    app.dir
+        .file_name()
+        .and_then(|name| name.split_once('-'))
+        .map(|(_, version)| {
+            version
+                .split('.')
+                .map(|part| part.parse().unwrap_or(0))
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 pub fn generated_file_include_lib(
@@ -137,7 +330,7 @@ pub fn generated_file_include_lib(
     included_file_id: FileId,
     include_path: VfsPath,
 ) -> Option<String> {
-    // In `find_generated_include_lib`, the processing does
+    // In `remote_search_dirs`, the processing does
     // - split the path into app and rest
     // - get the app_data based on the app
     // - if the rest starts with "include/"
@@ -163,3 +356,66 @@ pub fn generated_file_include_lib(
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use elp_project_model::AppName;
+    use elp_project_model::AppType;
+    use paths::AbsPathBuf;
+    use paths::Utf8PathBuf;
+
+    use super::select_app_version;
+    use crate::AppData;
+    use crate::ProjectId;
+    use crate::SourceRootId;
+
+    fn app_data(name: &str, dir: &str, include_path: &[&str]) -> Arc<AppData> {
+        let path = |p: &str| AbsPathBuf::assert(Utf8PathBuf::from(p));
+        Arc::new(AppData {
+            project_id: ProjectId(0),
+            name: AppName(name.to_string()),
+            buck_target_name: None,
+            dir: path(dir),
+            include_dirs: vec![],
+            include_path: include_path.iter().map(|p| path(p)).collect(),
+            src_path: vec![],
+            extra_src_dirs: vec![],
+            macros: vec![],
+            parse_transforms: vec![],
+            app_type: AppType::Otp,
+            ebin_path: None,
+            is_test_target: None,
+        })
+    }
+
+    fn candidates() -> Vec<(SourceRootId, Arc<AppData>)> {
+        vec![
+            (SourceRootId(1), app_data("foo", "/opt/lib/foo-1.10", &[])),
+            (SourceRootId(2), app_data("foo", "/opt/lib/foo-1.9.3", &[])),
+        ]
+    }
+
+    #[test]
+    fn select_pinned_app_version() {
+        let includer = app_data("main", "/main", &["/opt/lib", "/opt/lib/foo-1.9.3/include"]);
+        assert_eq!(
+            select_app_version(&candidates(), Some(&includer)),
+            Some((SourceRootId(2), false))
+        );
+    }
+
+    #[test]
+    fn select_latest_app_version() {
+        let includer = app_data("main", "/main", &["/opt/lib"]);
+        assert_eq!(
+            select_app_version(&candidates(), Some(&includer)),
+            Some((SourceRootId(1), true))
+        );
+        assert_eq!(
+            select_app_version(&candidates(), None),
+            Some((SourceRootId(1), true))
+        );
+    }
+}
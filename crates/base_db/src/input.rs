@@ -21,6 +21,8 @@ use elp_project_model::Project;
 use elp_project_model::ProjectAppData;
 use elp_project_model::buck::IncludeMapping;
 use elp_project_model::buck::TargetFullName;
+use elp_project_model::merge_macros;
+use elp_project_model::merge_parse_transforms;
 use fxhash::FxHashMap;
 use paths::RelPath;
 use paths::Utf8Path;
@@ -31,6 +33,7 @@ use vfs::file_set::FileSet;
 
 use crate::AppDataIndex;
 use crate::RootQueryDb;
+use crate::change::SourceRootEditError;
 
 /// Files are grouped into source roots. A source root is a directory on the
 /// file systems which is watched for changes. Typically it corresponds to an OTP
@@ -75,6 +78,31 @@ impl SourceRoot {
         self.file_set.iter()
     }
 
+    /// Returns `false` if the file is already present.
+    pub(crate) fn insert_file(&mut self, file_id: FileId, path: VfsPath) -> bool {
+        if self.file_set.path_for_file(&file_id).is_some() {
+            return false;
+        }
+        self.file_set.insert(file_id, path);
+        true
+    }
+
+    /// Returns `false` if the file is not present.
+    pub(crate) fn remove_file(&mut self, file_id: FileId) -> bool {
+        if self.file_set.path_for_file(&file_id).is_none() {
+            return false;
+        }
+        // `FileSet` has no removal, so rebuild it without the file.
+        let mut file_set = FileSet::default();
+        for id in self.file_set.iter().filter(|id| *id != file_id) {
+            if let Some(path) = self.file_set.path_for_file(&id) {
+                file_set.insert(id, path.clone());
+            }
+        }
+        self.file_set = file_set;
+        true
+    }
+
     pub fn iter_app_files<'a>(
         &'a self,
         app_data: &'a AppData,
@@ -113,6 +141,15 @@ pub struct ProjectData {
     pub source_roots: Vec<SourceRootId>,
     pub root_dir: AbsPathBuf,
     pub deps_ebins: Vec<AbsPathBuf>,
+    /// Project-level predefined macros. App-level definitions in
+    /// `AppData::macros` take precedence, see `AppData::effective_macros`.
+    pub macros: Vec<eetf::Term>,
+    /// Language features enabled for the project, see
+    /// `elp_project_model::CompileConfig::features`.
+    pub features: Option<Vec<String>>,
+    /// Project-level parse transforms, applied before the app-level
+    /// ones, see `AppData::effective_parse_transforms`.
+    pub parse_transforms: Vec<eetf::Term>,
     pub otp_project_id: Option<ProjectId>,
     pub app_roots: AppRoots,
     pub eqwalizer_config: EqwalizerConfig,
@@ -146,6 +183,18 @@ pub struct AppData {
 }
 
 impl AppData {
+    /// The macros to predefine when compiling a file in this app:
+    /// the project-level defaults, overridden by the app-level ones.
+    pub fn effective_macros(&self, project_data: &ProjectData) -> Vec<eetf::Term> {
+        merge_macros(&project_data.macros, &self.macros)
+    }
+
+    /// The parse transforms to apply when compiling a file in this app:
+    /// the project-level ones, then the app-level ones.
+    pub fn effective_parse_transforms(&self, project_data: &ProjectData) -> Vec<eetf::Term> {
+        merge_parse_transforms(&project_data.parse_transforms, &self.parse_transforms)
+    }
+
     fn is_src_file(&self, path: &VfsPath) -> bool {
         if let Some(path) = path.as_path() {
             // src_dirs are recursive, check path begins with one
@@ -177,7 +226,7 @@ impl AppData {
 }
 
 /// Note that `AppStructure` is build-system agnostic
-#[derive(Debug, Clone, Default /* Serialize, Deserialize */)]
+#[derive(Debug, Clone, Default, PartialEq, Eq /* Serialize, Deserialize */)]
 pub struct AppStructure {
     pub(crate) app_map: FxHashMap<SourceRootId, (Option<AppData>, Option<ApplicableFiles>)>,
     pub(crate) project_map: FxHashMap<ProjectId, ProjectData>,
@@ -204,6 +253,40 @@ impl AppStructure {
         assert!(prev.is_none());
     }
 
+    /// Check that the source roots referenced by the apps and projects
+    /// exist, according to `root_exists`. The catch-all root is not
+    /// checked, it is only needed once a file falls outside of all apps.
+    pub fn validate(
+        &self,
+        root_exists: &dyn Fn(SourceRootId) -> bool,
+    ) -> Result<(), SourceRootEditError> {
+        let missing = |root: SourceRootId, referenced_by: String| {
+            Err(SourceRootEditError::AppStructureRootMissing {
+                root,
+                referenced_by,
+            })
+        };
+        for (root, (app_data, _)) in &self.app_map {
+            if !root_exists(*root) {
+                let referenced_by = match app_data {
+                    Some(app_data) => format!("app {}", app_data.name),
+                    None => "an app without data".to_string(),
+                };
+                return missing(*root, referenced_by);
+            }
+        }
+        for (project_id, project_data) in &self.project_map {
+            if let Some(root) = project_data
+                .source_roots
+                .iter()
+                .find(|root| !root_exists(**root))
+            {
+                return missing(*root, format!("project {}", project_id.0));
+            }
+        }
+        Ok(())
+    }
+
     /// Set the salsa inputs according to this AppStructure
     pub fn apply(
         self,
@@ -242,11 +325,19 @@ impl AppStructure {
 pub struct AppRoots {
     otp: Option<Arc<AppRoots>>,
     app_map: FxHashMap<AppName, SourceRootId>,
+    /// All the source roots of the apps inserted more than once, e.g.
+    /// several versions of an OTP app, in insertion order.
+    duplicates: FxHashMap<AppName, Vec<SourceRootId>>,
 }
 
 impl AppRoots {
     pub fn insert(&mut self, app: AppName, source_root_id: SourceRootId) {
-        self.app_map.insert(app, source_root_id);
+        if let Some(previous) = self.app_map.insert(app.clone(), source_root_id) {
+            self.duplicates
+                .entry(app)
+                .or_insert_with(|| vec![previous])
+                .push(source_root_id);
+        }
     }
 
     pub fn set_otp(&mut self, otp: Option<Arc<AppRoots>>) {
@@ -263,6 +354,35 @@ impl AppRoots {
             .cloned()
             .or_else(|| self.otp.as_ref().and_then(|otp| otp.get(app)))
     }
+
+    /// The source roots of all the apps named `app`, where `get` only
+    /// returns the last one inserted.
+    pub fn candidates<Q>(&self, app: &Q) -> Vec<SourceRootId>
+    where
+        AppName: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match (self.duplicates.get(app), self.app_map.get(app)) {
+            (Some(duplicates), _) => duplicates.clone(),
+            (None, Some(source_root_id)) => vec![*source_root_id],
+            (None, None) => self
+                .otp
+                .as_ref()
+                .map(|otp| otp.candidates(app))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The names of all the apps, including the OTP ones, sorted.
+    pub fn app_names(&self) -> Vec<&AppName> {
+        let mut names: Vec<&AppName> = self.app_map.keys().collect();
+        if let Some(otp) = &self.otp {
+            names.extend(otp.app_names());
+        }
+        names.sort_by(|a, b| a.0.cmp(&b.0));
+        names.dedup();
+        names
+    }
 }
 
 // ---------------------------------------------------------------------
@@ -393,6 +513,14 @@ impl<'a> ProjectApps<'a> {
                 source_roots: project_source_roots,
                 root_dir: project.root().into_owned(),
                 deps_ebins: project.deps_ebins(),
+                // Checked when the config is loaded, in `ElpConfig::try_parse`,
+                // but a config built some other way may not have been
+                macros: project.compile_config.macros().unwrap_or_else(|err| {
+                    log::error!("Ignoring the macros of project {}: {err:#}", project.root());
+                    vec![]
+                }),
+                features: project.compile_config.features.clone(),
+                parse_transforms: project.compile_config.parse_transforms(),
                 otp_project_id: self.otp_project_id,
                 app_roots,
                 eqwalizer_config: project.eqwalizer_config.clone(),
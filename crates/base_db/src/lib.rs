@@ -29,7 +29,11 @@ use lazy_static::lazy_static;
 mod change;
 mod include;
 mod input;
+mod line_endings;
 mod module_index;
+mod path_ext;
+#[cfg(test)]
+mod test_db;
 
 // ---------------------------------------------------------------------
 // Public API
@@ -37,13 +41,19 @@ mod module_index;
 pub mod fixture;
 // @fb-only
 pub mod test_utils;
+pub use change::AppliedFiles;
 pub use change::Change;
+pub use change::ChangeBuilder;
+pub use change::PROGRESS_INTERVAL;
+pub use change::SourceRootEdit;
+pub use change::SourceRootEditError;
 pub use elp_project_model::AppType;
 pub use elp_project_model::test_fixture::CURSOR_MARKER;
 pub use elp_project_model::test_fixture::RangeOrOffset;
 pub use elp_project_model::test_fixture::extract_offset;
 pub use elp_project_model::test_fixture::remove_annotations;
 pub use include::IncludeCtx;
+pub use include::IncludeKind;
 pub use include::generated_file_include_lib;
 pub use input::AppData;
 pub use input::AppDataId;
@@ -56,9 +66,13 @@ pub use input::ProjectData;
 pub use input::ProjectId;
 pub use input::SourceRoot;
 pub use input::SourceRootId;
+pub use line_endings::LineEndings;
 pub use module_index::ModuleIndex;
 pub use module_index::ModuleName;
 pub use module_index::Modules;
+pub use path_ext::AbsPathBufExt;
+pub use path_ext::AbsPathExt;
+pub use path_ext::rel_join;
 pub use paths::AbsPath;
 pub use paths::AbsPathBuf;
 pub use paths::RelPath;
@@ -188,6 +202,10 @@ impl Files {
         *source_root
     }
 
+    pub fn has_source_root(&self, source_root_id: SourceRootId) -> bool {
+        self.source_roots.contains_key(&source_root_id)
+    }
+
     pub fn set_source_root(
         &self,
         db: &mut dyn SourceDatabase,
@@ -219,14 +237,16 @@ impl Files {
         db: &mut dyn SourceDatabase,
         id: FileId,
         source_root_id: SourceRootId,
+        path: Option<VfsPath>,
     ) {
         let file_source_roots = Arc::clone(&self.file_source_roots);
         match file_source_roots.entry(id) {
             Entry::Occupied(mut occupied) => {
                 occupied.get_mut().set_source_root_id(db).to(source_root_id);
+                occupied.get_mut().set_path(db).to(path);
             }
             Entry::Vacant(vacant) => {
-                let file_source_root = FileSourceRootInput::builder(source_root_id).new(db);
+                let file_source_root = FileSourceRootInput::builder(source_root_id, path).new(db);
                 vacant.insert(file_source_root);
             }
         };
@@ -322,6 +342,11 @@ pub struct FileText {
 #[salsa::input]
 pub struct FileSourceRootInput {
     pub source_root_id: SourceRootId,
+    /// The path of the file in its source root, `None` once removed
+    /// from it. Queries about the file alone read this rather than the
+    /// whole root, so that they stay valid when files are added to or
+    /// removed from the root.
+    pub path: Option<VfsPath>,
 }
 
 #[salsa::input]
@@ -405,6 +430,20 @@ pub trait RootQueryDb: SourceDatabase + salsa::Database {
 
     #[salsa::invoke(IncludeCtx::resolve_remote_query)]
     fn resolve_remote(&self, file_id: FileId, path: SmolStr) -> Option<FileId>;
+
+    /// The directories an include of `path` in `file_id` is looked up
+    /// in, in the order `IncludeCtx` tries them: the directory of the
+    /// file, that of the file the include mapping maps `path` to, then
+    /// the include path of its app. For an `-include_lib` of an unmapped
+    /// path, the directory and include path of the app it starts with
+    /// follow.
+    #[salsa::invoke(IncludeCtx::include_search_paths_query)]
+    fn include_search_paths(
+        &self,
+        file_id: FileId,
+        path: SmolStr,
+        kind: IncludeKind,
+    ) -> Arc<Vec<AbsPathBuf>>;
 }
 
 #[salsa::db]
@@ -418,10 +457,17 @@ pub trait SourceDatabase: salsa::Database {
 
     fn set_source_root(&mut self, source_root_id: SourceRootId, source_root: Arc<SourceRoot>);
 
-    /// Source root of the file.
+    fn has_source_root(&self, id: SourceRootId) -> bool;
+
+    /// Source root of the file, and its path there.
     fn file_source_root(&self, id: FileId) -> FileSourceRootInput;
 
-    fn set_file_source_root(&mut self, id: FileId, source_root_id: SourceRootId);
+    fn set_file_source_root(
+        &mut self,
+        id: FileId,
+        source_root_id: SourceRootId,
+        path: Option<VfsPath>,
+    );
 
     fn app_data_by_id(&self, id: AppDataId) -> AppDataInput;
 
@@ -597,9 +643,7 @@ fn parse(db: &dyn RootQueryDb, file_id: FileId) -> Parse<SourceFile> {
 }
 
 pub fn path_for_file(db: &dyn RootQueryDb, file_id: FileId) -> Option<VfsPath> {
-    let source_root_id = db.file_source_root(file_id).source_root_id(db);
-    let source_root = db.source_root(source_root_id).source_root(db);
-    source_root.path_for_file(&file_id).cloned()
+    db.file_source_root(file_id).path(db)
 }
 
 fn is_generated(db: &dyn RootQueryDb, file_id: FileId) -> bool {
@@ -629,10 +673,8 @@ fn is_test_suite_or_test_helper(db: &dyn RootQueryDb, file_id: FileId) -> Option
     // Context for T171541590
     let _ = stdx::panic_context::enter(format!("\nis_test_suite_or_test_helper: {file_id:?}"));
     let app_data = db.file_app_data(file_id)?;
-    let root_id = db.file_source_root(file_id).source_root_id(db);
-    let root = db.source_root(root_id).source_root(db);
-    let path = root.path_for_file(&file_id)?;
-    if app_data.is_extra_src_file(path) {
+    let path = path_for_file(db, file_id)?;
+    if app_data.is_extra_src_file(&path) {
         Some(true)
     } else {
         Some(false)
@@ -682,10 +724,9 @@ static ref IGNORED_SOURCES: Vec<Regex> = {
 fn file_kind(db: &dyn RootQueryDb, file_id: FileId) -> FileKind {
     // Context for T171541590
     let _ = stdx::panic_context::enter(format!("\nfile_kind: {file_id:?}"));
-    let source_root_id = db.file_source_root(file_id).source_root_id(db);
-    let source_root = db.source_root(source_root_id).source_root(db);
-    let ignored_path = source_root
-        .path_for_file(&file_id)
+    let path = path_for_file(db, file_id);
+    let ignored_path = path
+        .as_ref()
         .and_then(|path| path.as_path())
         .map(|path| {
             let path = path.as_os_str().to_str().unwrap();
@@ -699,9 +740,7 @@ fn file_kind(db: &dyn RootQueryDb, file_id: FileId) -> FileKind {
         // sources, do not process
         FileKind::OutsideProjectModel
     } else {
-        let name_and_ext = source_root
-            .path_for_file(&file_id)
-            .and_then(|path| path.name_and_extension());
+        let name_and_ext = path.as_ref().and_then(|path| path.name_and_extension());
         match name_and_ext {
             Some((name, Some("erl"))) => {
                 if name.ends_with("_SUITE") {
@@ -154,6 +154,12 @@ impl ModuleIndex {
     pub fn duplicates(&self, module_name: &ModuleName) -> Option<FxHashSet<FileId>> {
         self.duplicates.get(module_name).cloned()
     }
+
+    /// Project-owned modules defined by more than one file, with all
+    /// the files defining them
+    pub fn iter_duplicates(&self) -> impl Iterator<Item = (&ModuleName, &FxHashSet<FileId>)> + '_ {
+        self.duplicates.iter()
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -0,0 +1,587 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Helpers on `AbsPathBuf` and `AbsPath`, which come from the `paths`
+//! crate of rust-analyzer, so are added as extension traits, and on
+//! `RelPath`.
+
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use paths::AbsPath;
+use paths::AbsPathBuf;
+use paths::RelPath;
+use paths::RelPathBuf;
+use paths::Utf8Component;
+use paths::Utf8Path;
+use paths::Utf8PathBuf;
+
+pub trait AbsPathExt {
+    /// The `Normal` components of the path, without any drive prefix or
+    /// root, so that Windows and Unix paths can be compared.
+    fn components_without_prefix(&self) -> impl Iterator<Item = &str>;
+
+    /// Whether the final component starts with a dot. On Windows a file
+    /// that exists is also hidden if it has `FILE_ATTRIBUTE_HIDDEN`; that
+    /// is the only case which touches the filesystem.
+    fn is_hidden(&self) -> bool;
+
+    /// Like `strip_prefix`, but compares components case-insensitively on
+    /// Windows, where the filesystem is.
+    fn strip_prefix_ci(&self, base: &AbsPath) -> Option<&RelPath>;
+
+    /// Whether the path is `dir` or lies beneath it, comparing components
+    /// lexically (symlinks are not resolved), case-insensitively on Windows.
+    fn is_sub_path_of(&self, dir: &AbsPath) -> bool;
+
+    /// The path with `.` and `..` resolved lexically and, on Windows,
+    /// lowercased with forward slashes. For comparing and displaying paths
+    /// deterministically across platforms; never use the result for IO.
+    fn to_logical_canonical(&self) -> String;
+
+    /// Whether the paths are equal, case-insensitively and treating `/`
+    /// and `\` alike on Windows. Unlike comparing the results of
+    /// `to_logical_canonical`, this never allocates.
+    fn eq_ci(&self, other: &AbsPath) -> bool;
+
+    /// Whether the paths are equal once a single trailing separator is
+    /// dropped from each. Paths we build are normalized without one, but
+    /// roots supplied by config files may keep it.
+    fn eq_ignoring_trailing_sep(&self, other: &AbsPath) -> bool;
+
+    /// The `file://` URI of the path, with `.` and `..` resolved and the
+    /// drive letter lowercased, as VS Code does. This is the string the
+    /// LSP `Url` of the path is parsed from, for when it is only needed
+    /// as a string.
+    fn to_uri_string(&self) -> String;
+}
+
+impl AbsPathExt for AbsPath {
+    fn components_without_prefix(&self) -> impl Iterator<Item = &str> {
+        let path: &Utf8Path = self.as_ref();
+        path.components().filter_map(|component| match component {
+            Utf8Component::Normal(name) => Some(name),
+            _ => None,
+        })
+    }
+
+    fn is_hidden(&self) -> bool {
+        if self.file_name().is_some_and(|name| name.starts_with('.')) {
+            return true;
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::fs::MetadataExt;
+            const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+            if let Ok(metadata) = std::fs::metadata(self) {
+                return metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0;
+            }
+        }
+        false
+    }
+
+    fn strip_prefix_ci(&self, base: &AbsPath) -> Option<&RelPath> {
+        let path: &Utf8Path = self.as_ref();
+        let base: &Utf8Path = base.as_ref();
+        let mut components = path.components();
+        for base_component in base.components() {
+            match components.next() {
+                Some(component) if same_component(component, base_component) => {}
+                _ => return None,
+            }
+        }
+        Some(RelPath::new_unchecked(components.as_path()))
+    }
+
+    fn is_sub_path_of(&self, dir: &AbsPath) -> bool {
+        self.strip_prefix_ci(dir).is_some()
+    }
+
+    fn to_logical_canonical(&self) -> String {
+        let normalized = self.normalize();
+        if cfg!(windows) {
+            normalized.as_str().replace('\\', "/").to_lowercase()
+        } else {
+            normalized.as_str().to_string()
+        }
+    }
+
+    fn eq_ci(&self, other: &AbsPath) -> bool {
+        same_str(self.as_str(), other.as_str())
+    }
+
+    fn eq_ignoring_trailing_sep(&self, other: &AbsPath) -> bool {
+        same_str(
+            without_trailing_sep(self.as_str()),
+            without_trailing_sep(other.as_str()),
+        )
+    }
+
+    fn to_uri_string(&self) -> String {
+        let path: &Utf8Path = self.as_ref();
+        let has_dots = path
+            .components()
+            .any(|component| matches!(component, Utf8Component::CurDir | Utf8Component::ParentDir));
+        if has_dots {
+            file_uri(self.normalize().as_str())
+        } else {
+            file_uri(self.as_str())
+        }
+    }
+}
+
+fn file_uri(path: &str) -> String {
+    let mut uri = String::with_capacity("file:///".len() + path.len());
+    uri.push_str("file://");
+    let windows_path = path.strip_prefix(r"\\?\").unwrap_or(path);
+    let bytes = windows_path.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        // Need to lowercase drive letter, as VS Code does
+        uri.push('/');
+        uri.push(bytes[0].to_ascii_lowercase() as char);
+        uri.push(':');
+        push_encoded_path(&mut uri, &windows_path[2..], &['\\', '/']);
+    } else if let Some(unc) = path.strip_prefix(r"\\") {
+        let (host, rest) = unc.split_at(unc.find('\\').unwrap_or(unc.len()));
+        uri.push_str(host);
+        push_encoded_path(&mut uri, rest, &['\\']);
+    } else {
+        push_encoded_path(&mut uri, path, &['/']);
+    }
+    uri
+}
+
+/// Append `path` to `uri`, turning each of `separators` into `/` and
+/// percent-encoding the bytes `Url::from_file_path` would. A `\` that is
+/// not a separator is encoded too, as URL parsers take it for one.
+fn push_encoded_path(uri: &mut String, path: &str, separators: &[char]) {
+    for c in path.chars() {
+        if separators.contains(&c) {
+            uri.push('/');
+        } else if c.is_ascii_graphic()
+            && !matches!(
+                c,
+                '"' | '#' | '%' | '<' | '>' | '?' | '\\' | '`' | '{' | '}'
+            )
+        {
+            uri.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                uri.push_str(&format!("%{byte:02X}"));
+            }
+        }
+    }
+}
+
+fn without_trailing_sep(path: &str) -> &str {
+    path.strip_suffix(std::path::is_separator).unwrap_or(path)
+}
+
+fn same_component(a: Utf8Component, b: Utf8Component) -> bool {
+    same_str(a.as_str(), b.as_str())
+}
+
+fn same_str(a: &str, b: &str) -> bool {
+    if cfg!(windows) {
+        eq_ignore_case(a, b)
+    } else {
+        a == b
+    }
+}
+
+/// Compares ASCII strings byte by byte, so that paths of different lengths
+/// are rejected up front. Anything else is lowercased char by char, which
+/// may change its length, but still without allocating.
+fn eq_ignore_case(a: &str, b: &str) -> bool {
+    if a.is_ascii() && b.is_ascii() {
+        let fold = |byte: u8| match byte {
+            b'\\' => b'/',
+            _ => byte.to_ascii_lowercase(),
+        };
+        return a.len() == b.len() && a.bytes().zip(b.bytes()).all(|(x, y)| fold(x) == fold(y));
+    }
+    let fold = |s: &str| {
+        s.chars()
+            .map(|c| if c == '\\' { '/' } else { c })
+            .flat_map(char::to_lowercase)
+    };
+    fold(a).eq(fold(b))
+}
+
+pub trait AbsPathBufExt {
+    /// The parent directory, owned and normalized, or `None` for a root.
+    fn parent_owned(&self) -> Option<AbsPathBuf>;
+
+    /// `prefix` followed by `components`, normalized once. Returns `None`
+    /// unless every component is a single `Normal` one (no separators,
+    /// `.` or `..`), so a component can never move outside of `prefix`.
+    fn from_components(
+        prefix: &AbsPath,
+        components: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Option<AbsPathBuf>;
+
+    /// Expands the environment variables in `template`, written `$VAR` or
+    /// `${VAR}` on Unix and `%VAR%` on Windows, and normalizes the result.
+    /// Fails if a variable is unset or the expanded path is relative.
+    fn from_env(template: &str) -> Result<AbsPathBuf>;
+}
+
+impl AbsPathBufExt for AbsPathBuf {
+    fn parent_owned(&self) -> Option<AbsPathBuf> {
+        self.parent().map(|parent| parent.normalize())
+    }
+
+    fn from_components(
+        prefix: &AbsPath,
+        components: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Option<AbsPathBuf> {
+        let mut path = prefix.to_path_buf();
+        for component in components {
+            let component = component.as_ref();
+            let mut parsed = Utf8Path::new(component).components();
+            match (parsed.next(), parsed.next()) {
+                (Some(Utf8Component::Normal(name)), None) if name == component => path.push(name),
+                _ => return None,
+            }
+        }
+        Some(path.normalize())
+    }
+
+    fn from_env(template: &str) -> Result<AbsPathBuf> {
+        from_env_with(template, |name| std::env::var(name).ok())
+    }
+}
+
+fn from_env_with(template: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<AbsPathBuf> {
+    let marker = if cfg!(windows) { '%' } else { '$' };
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(marker) {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, remainder) = if cfg!(windows) {
+            after
+                .split_once('%')
+                .ok_or_else(|| anyhow!("unterminated `%` in `{template}`"))?
+        } else if let Some(braced) = after.strip_prefix('{') {
+            braced
+                .split_once('}')
+                .ok_or_else(|| anyhow!("unterminated `${{` in `{template}`"))?
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            after.split_at(end)
+        };
+        if name.is_empty() {
+            bail!("empty variable name in `{template}`");
+        }
+        let value =
+            lookup(name).ok_or_else(|| anyhow!("environment variable `{name}` is not set"))?;
+        expanded.push_str(&value);
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+    let path = AbsPathBuf::try_from(Utf8PathBuf::from(expanded))
+        .map_err(|path| anyhow!("`{template}` expands to relative path `{path}`"))?;
+    Ok(path.normalize())
+}
+
+/// Join `more` onto `base` lexically, without touching the file system.
+/// Returns `None` if `more` is absolute, or if a `..` component would
+/// escape `base`.
+pub fn rel_join(base: &RelPath, more: impl AsRef<Utf8Path>) -> Option<RelPathBuf> {
+    let mut res = <RelPath as AsRef<Utf8Path>>::as_ref(base).to_path_buf();
+    let mut depth = 0;
+    for component in more.as_ref().components() {
+        match component {
+            Utf8Component::Normal(name) => {
+                res.push(name);
+                depth += 1;
+            }
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                if depth == 0 {
+                    return None;
+                }
+                res.pop();
+                depth -= 1;
+            }
+            Utf8Component::RootDir | Utf8Component::Prefix(_) => return None,
+        }
+    }
+    RelPathBuf::try_from(res).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abs(path: &str) -> AbsPathBuf {
+        AbsPathBuf::assert(Utf8PathBuf::from(path))
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn parent_owned() {
+        assert_eq!(
+            abs("/app/src/foo.erl").parent_owned(),
+            Some(abs("/app/src"))
+        );
+        assert_eq!(abs("/app/src/../foo.erl").parent_owned(), Some(abs("/app")));
+        assert_eq!(abs("/").parent_owned(), None);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn parent_owned() {
+        assert_eq!(abs(r"C:\app\foo.erl").parent_owned(), Some(abs(r"C:\app")));
+        assert_eq!(abs(r"C:\").parent_owned(), None);
+    }
+
+    #[test]
+    fn components_without_prefix() {
+        let path = if cfg!(windows) {
+            abs("C:/app/src/foo.erl")
+        } else {
+            abs("/app/src/foo.erl")
+        };
+        assert_eq!(
+            path.components_without_prefix().collect::<Vec<_>>(),
+            vec!["app", "src", "foo.erl"]
+        );
+    }
+
+    #[test]
+    fn is_hidden() {
+        let root = if cfg!(windows) { "C:/app" } else { "/app" };
+        assert!(abs(&format!("{root}/.git")).is_hidden());
+        assert!(!abs(&format!("{root}/src/foo.erl")).is_hidden());
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn is_hidden_attribute() {
+        let dir = std::env::temp_dir();
+        let file = dir.join("elp_is_hidden_attribute.erl");
+        std::fs::write(&file, "").unwrap();
+        let path = abs(file.to_str().unwrap());
+        assert!(!path.is_hidden());
+        let status = std::process::Command::new("attrib")
+            .arg("+h")
+            .arg(&file)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert!(path.is_hidden());
+        std::process::Command::new("attrib")
+            .arg("-h")
+            .arg(&file)
+            .status()
+            .unwrap();
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn from_components() {
+        let root = if cfg!(windows) { "C:/app" } else { "/app" };
+        assert_eq!(
+            AbsPathBuf::from_components(&abs(root), ["src", "nested", "foo.erl"]),
+            Some(abs(&format!("{root}/src/nested/foo.erl")))
+        );
+        assert_eq!(
+            AbsPathBuf::from_components(&abs(root), ["src", "../etc"]),
+            None
+        );
+        assert_eq!(AbsPathBuf::from_components(&abs(root), [".."]), None);
+    }
+
+    #[test]
+    fn from_env() {
+        let (root, template, unset, relative) = if cfg!(windows) {
+            ("C:/otp", "%OTP_ROOT%/lib", "%UNSET%/lib", "%RELATIVE%/lib")
+        } else {
+            ("/otp", "${OTP_ROOT}/lib", "$UNSET/lib", "$RELATIVE/lib")
+        };
+        let lookup = |name: &str| match name {
+            "OTP_ROOT" => Some(root.to_string()),
+            "RELATIVE" => Some("otp".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            from_env_with(template, lookup).unwrap(),
+            abs(&format!("{root}/lib"))
+        );
+        assert_eq!(
+            from_env_with(unset, lookup).unwrap_err().to_string(),
+            "environment variable `UNSET` is not set"
+        );
+        assert!(
+            from_env_with(relative, lookup)
+                .unwrap_err()
+                .to_string()
+                .contains("expands to relative path")
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn strip_prefix_ci() {
+        let path = abs("/app/src/foo.erl");
+        assert_eq!(
+            path.strip_prefix_ci(&abs("/app")),
+            Some(RelPath::new_unchecked(Utf8Path::new("src/foo.erl")))
+        );
+        assert_eq!(path.strip_prefix_ci(&abs("/App")), None);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn strip_prefix_ci() {
+        let path = abs(r"C:\App\Src\foo.erl");
+        assert_eq!(
+            path.strip_prefix_ci(&abs("c:/app")),
+            Some(RelPath::new_unchecked(Utf8Path::new(r"Src\foo.erl")))
+        );
+        assert_eq!(path.strip_prefix_ci(&abs("c:/other")), None);
+    }
+
+    #[test]
+    fn is_sub_path_of() {
+        let root = if cfg!(windows) { "C:/app" } else { "/app" };
+        let dir = abs(&format!("{root}/src"));
+        assert!(abs(&format!("{root}/src/nested/foo.erl")).is_sub_path_of(&dir));
+        assert!(dir.is_sub_path_of(&dir));
+        assert!(!abs(&format!("{root}/src2/foo.erl")).is_sub_path_of(&dir));
+        assert!(!abs(&format!("{root}/test")).is_sub_path_of(&dir));
+        assert_eq!(
+            abs(&format!("{root}/SRC/foo.erl")).is_sub_path_of(&dir),
+            cfg!(windows)
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn to_logical_canonical() {
+        assert_eq!(
+            abs("/app/./src/../include/foo.hrl").to_logical_canonical(),
+            "/app/include/foo.hrl"
+        );
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn to_logical_canonical() {
+        assert_eq!(
+            abs(r"C:\App\Src\..\Include\foo.hrl").to_logical_canonical(),
+            abs("c:/app/include/FOO.hrl").to_logical_canonical()
+        );
+        assert_eq!(
+            abs("C:/App/include/foo.hrl").to_logical_canonical(),
+            "c:/app/include/foo.hrl"
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn eq_ignoring_trailing_sep() {
+        let dir = abs("/app/src");
+        assert!(abs("/app/src/").eq_ignoring_trailing_sep(&dir));
+        assert!(dir.eq_ignoring_trailing_sep(&abs("/app/src/")));
+        assert!(dir.eq_ignoring_trailing_sep(&dir));
+        assert!(!abs("/app/src2/").eq_ignoring_trailing_sep(&dir));
+        assert!(!abs("/app/").eq_ignoring_trailing_sep(&dir));
+        assert!(abs("/").eq_ignoring_trailing_sep(&abs("/")));
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn eq_ignoring_trailing_sep() {
+        let dir = abs(r"C:\app\src");
+        assert!(abs(r"C:\app\src\").eq_ignoring_trailing_sep(&dir));
+        assert!(dir.eq_ignoring_trailing_sep(&abs(r"C:\app\src\")));
+        assert!(abs("C:/App/Src/").eq_ignoring_trailing_sep(&dir));
+        assert!(!abs(r"C:\app\src2\").eq_ignoring_trailing_sep(&dir));
+        assert!(abs(r"C:\").eq_ignoring_trailing_sep(&abs(r"C:\")));
+    }
+
+    #[test]
+    fn eq_ignore_case_ascii() {
+        assert!(eq_ignore_case("C:/App/Src/foo.erl", r"c:\app\src\FOO.erl"));
+        assert!(!eq_ignore_case("C:/app/src/foo.erl", "C:/app/src/bar.erl"));
+        assert!(!eq_ignore_case("C:/app/src", "C:/app/src2"));
+        assert!(!eq_ignore_case("C:/app/src", "C:/app/src/"));
+    }
+
+    #[test]
+    fn eq_ignore_case_non_ascii() {
+        assert!(eq_ignore_case("C:/Équipe/foo.erl", r"c:\équipe\FOO.erl"));
+        assert!(!eq_ignore_case("C:/équipe/foo.erl", "C:/equipe/foo.erl"));
+        // The Kelvin sign lowercases to a one byte `k`
+        assert!(eq_ignore_case("C:/\u{212A}/foo.erl", "c:/k/foo.erl"));
+        assert!(!eq_ignore_case("C:/Ü/foo.erl", "C:/Ü/foo.erl2"));
+    }
+
+    #[test]
+    fn eq_ci() {
+        let root = if cfg!(windows) { "C:/app" } else { "/app" };
+        let dir = abs(&format!("{root}/src"));
+        assert!(dir.eq_ci(&dir));
+        assert!(!abs(&format!("{root}/src2")).eq_ci(&dir));
+        assert_eq!(abs(&format!("{root}/SRC")).eq_ci(&dir), cfg!(windows));
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn to_uri_string() {
+        assert_eq!(
+            abs("/home/me/my project/a b.erl").to_uri_string(),
+            "file:///home/me/my%20project/a%20b.erl"
+        );
+        assert_eq!(
+            abs(r"/tmp/#1/100%/back\slash/ü.erl").to_uri_string(),
+            "file:///tmp/%231/100%25/back%5Cslash/%C3%BC.erl"
+        );
+        assert_eq!(
+            abs("/app/./src/../include/a.hrl").to_uri_string(),
+            "file:///app/include/a.hrl"
+        );
+    }
+
+    #[test]
+    fn file_uri_windows() {
+        assert_eq!(
+            file_uri(r"C:\Users\me\my project\a.erl"),
+            "file:///c:/Users/me/my%20project/a.erl"
+        );
+        assert_eq!(file_uri(r"\\?\D:\src\a.erl"), "file:///d:/src/a.erl");
+        assert_eq!(
+            file_uri(r"\\server\share\a.erl"),
+            "file://server/share/a.erl"
+        );
+    }
+
+    #[test]
+    fn rel_join_components() {
+        let base = RelPath::new_unchecked(Utf8Path::new("include"));
+        assert_eq!(
+            rel_join(base, "./sub/../foo.hrl").as_deref(),
+            Some(RelPath::new_unchecked(Utf8Path::new("include/foo.hrl")))
+        );
+    }
+
+    #[test]
+    fn rel_join_rejects_escape() {
+        let base = RelPath::new_unchecked(Utf8Path::new("include"));
+        assert_eq!(rel_join(base, "../src/foo.hrl"), None);
+        assert_eq!(rel_join(base, "/src/foo.hrl"), None);
+    }
+}
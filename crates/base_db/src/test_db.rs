@@ -0,0 +1,144 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Database used for testing `base_db`.
+
+use std::fmt;
+use std::panic;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use crate::AppData;
+use crate::AppDataId;
+use crate::AppDataInput;
+use crate::FileId;
+use crate::FileSourceRootInput;
+use crate::FileText;
+use crate::Files;
+use crate::ProjectData;
+use crate::ProjectDataInput;
+use crate::ProjectId;
+use crate::SourceAppDataInput;
+use crate::SourceDatabase;
+use crate::SourceRoot;
+use crate::SourceRootId;
+use crate::SourceRootInput;
+use crate::VfsPath;
+
+#[salsa::db]
+#[derive(Default, Clone)]
+pub(crate) struct TestDB {
+    storage: salsa::Storage<TestDB>,
+    files: Arc<Files>,
+    events: Arc<Mutex<Option<Vec<salsa::Event>>>>,
+}
+
+#[salsa::db]
+impl salsa::Database for TestDB {
+    fn salsa_event(&self, event: &dyn Fn() -> salsa::Event) {
+        let mut events = self.events.lock().unwrap();
+        if let Some(events) = &mut *events {
+            events.push(event());
+        }
+    }
+}
+
+impl fmt::Debug for TestDB {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestDB").finish()
+    }
+}
+
+#[salsa::db]
+impl SourceDatabase for TestDB {
+    fn file_text(&self, file_id: FileId) -> FileText {
+        self.files.file_text(file_id)
+    }
+
+    fn set_file_text(&mut self, file_id: FileId, text: Arc<str>) {
+        let files = self.files.clone();
+        files.set_file_text(self, file_id, text);
+    }
+
+    fn source_root(&self, source_root_id: SourceRootId) -> SourceRootInput {
+        self.files.source_root(source_root_id)
+    }
+
+    fn set_source_root(&mut self, source_root_id: SourceRootId, source_root: Arc<SourceRoot>) {
+        let files = self.files.clone();
+        files.set_source_root(self, source_root_id, source_root);
+    }
+
+    fn has_source_root(&self, source_root_id: SourceRootId) -> bool {
+        self.files.has_source_root(source_root_id)
+    }
+
+    fn file_source_root(&self, id: FileId) -> FileSourceRootInput {
+        self.files.file_source_root(id)
+    }
+
+    fn set_file_source_root(
+        &mut self,
+        id: FileId,
+        source_root_id: SourceRootId,
+        path: Option<VfsPath>,
+    ) {
+        let files = self.files.clone();
+        files.set_file_source_root(self, id, source_root_id, path);
+    }
+
+    fn app_data_by_id(&self, id: AppDataId) -> AppDataInput {
+        self.files.app_data(id)
+    }
+
+    fn set_app_data_by_id(&mut self, id: AppDataId, app_data: Option<Arc<AppData>>) {
+        let files = self.files.clone();
+        files.set_app_data(self, id, app_data)
+    }
+
+    fn app_data_id(&self, source_root_id: SourceRootId) -> SourceAppDataInput {
+        self.files.source_app_data(source_root_id)
+    }
+
+    fn set_app_data_id(&mut self, id: SourceRootId, app_data_id: AppDataId) {
+        let files = self.files.clone();
+        files.set_source_app_data(self, id, app_data_id)
+    }
+
+    fn project_data(&self, project_id: ProjectId) -> ProjectDataInput {
+        self.files.project_data(project_id)
+    }
+
+    fn set_project_data(&mut self, id: ProjectId, project_data: Arc<ProjectData>) {
+        let files = self.files.clone();
+        files.set_project_data(self, id, project_data)
+    }
+}
+
+impl panic::RefUnwindSafe for TestDB {}
+
+impl TestDB {
+    /// Run `f`, returning the debug names of the queries it executed.
+    pub(crate) fn log_executed(&self, f: impl FnOnce()) -> Vec<String> {
+        *self.events.lock().unwrap() = Some(Vec::new());
+        f();
+        let events = self.events.lock().unwrap().take().unwrap();
+        events
+            .into_iter()
+            .filter_map(|e| match e.kind {
+                salsa::EventKind::WillExecute { database_key } => Some(
+                    salsa::Database::ingredient_debug_name(self, database_key.ingredient_index())
+                        .to_string(),
+                ),
+                _ => None,
+            })
+            .collect()
+    }
+}
@@ -83,6 +83,9 @@ pub struct ParseAll {
     pub stats: bool,
     /// When printing statistics, include the list of modules parsed
     pub list_modules: bool,
+    /// Dump the ASTs as indented Erlang terms, to .etf.txt files
+    #[bpaf(hide)]
+    pub pretty: bool,
 }
 
 #[derive(Clone, Debug, Bpaf)]
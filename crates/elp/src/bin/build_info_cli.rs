@@ -15,6 +15,7 @@ use std::io::Write;
 use anyhow::Result;
 use elp_ide::elp_ide_db::elp_base_db::AbsPath;
 use elp_ide::elp_ide_db::elp_base_db::AbsPathBuf;
+use elp_project_model::CompileConfig;
 use elp_project_model::ElpConfig;
 use elp_project_model::EqwalizerConfig;
 use elp_project_model::IncludeParentDirs;
@@ -32,7 +33,13 @@ pub(crate) fn save_build_info(args: BuildInfo, query_config: &BuckQueryConfig) -
     let root = fs::canonicalize(&args.project)?;
     let root = AbsPathBuf::assert_utf8(root);
     let (_elp_config, manifest) = ProjectManifest::discover(&root)?;
-    let project = Project::load(&manifest, EqwalizerConfig::default(), query_config, &|_| {})?;
+    let project = Project::load(
+        &manifest,
+        EqwalizerConfig::default(),
+        CompileConfig::default(),
+        query_config,
+        &|_| {},
+    )?;
     let mut writer = File::create(&args.to)?;
     let json_str = serde_json::to_string_pretty::<JsonConfig>(&project.as_json(root))?;
     writer.write_all(json_str.as_bytes())?;
@@ -84,7 +91,13 @@ fn load_project(
     query_config: &BuckQueryConfig,
 ) -> Result<(ProjectManifest, Project)> {
     let (elp_config, manifest) = ProjectManifest::discover(root)?;
-    let project = Project::load(&manifest, elp_config.eqwalizer, query_config, &|_| {})?;
+    let project = Project::load(
+        &manifest,
+        elp_config.eqwalizer,
+        elp_config.compile,
+        query_config,
+        &|_| {},
+    )?;
     Ok((manifest, project))
 }
 
@@ -94,6 +107,12 @@ fn load_fallback(
 ) -> Result<(ProjectManifest, Project)> {
     let manifest = ProjectManifest::discover_no_manifest(root, IncludeParentDirs::Yes);
     let elp_config = ElpConfig::default();
-    let project = Project::load(&manifest, elp_config.eqwalizer, query_config, &|_| {})?;
+    let project = Project::load(
+        &manifest,
+        elp_config.eqwalizer,
+        elp_config.compile,
+        query_config,
+        &|_| {},
+    )?;
     Ok((manifest, project))
 }
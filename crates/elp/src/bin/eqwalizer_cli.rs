@@ -38,6 +38,7 @@ use elp_ide::elp_ide_db::elp_base_db::FileId;
 use elp_ide::elp_ide_db::elp_base_db::IncludeOtp;
 use elp_ide::elp_ide_db::elp_base_db::ModuleName;
 use elp_ide::elp_ide_db::elp_base_db::VfsPath;
+use elp_ide::eqwalize_in_parallel;
 use elp_project_model::AppName;
 use elp_project_model::DiscoverConfig;
 use elp_project_model::ProjectBuildData;
@@ -468,24 +469,23 @@ fn eqwalize(
 
     let files_count = file_ids.len();
     let pb = reporter.progress(files_count as u64, "EqWAlizing");
+    let percentage_pb = pb.clone();
     let output = loaded.with_eqwalizer_progress_bar(pb.clone(), move |analysis| {
         let project_id = loaded.project_id;
-        let max_tasks = loaded.project.eqwalizer_config.max_tasks;
-        let chunk_size = files_count.div_ceil(max_tasks);
-        file_ids
-            .chunks(chunk_size)
-            .par_bridge()
-            .map_with(analysis, move |analysis, file_ids| {
+        let eqwalizer_config = &loaded.project.eqwalizer_config;
+        let chunk_size = files_count.div_ceil(eqwalizer_config.max_tasks);
+        eqwalize_in_parallel(
+            eqwalizer_config,
+            analysis,
+            &file_ids,
+            chunk_size,
+            move |percentage| percentage_pb.set_prefix(format!("EqWAlizing {percentage}%")),
+            |analysis, file_ids| {
                 analysis
                     .eqwalizer_diagnostics(project_id, file_ids.to_vec())
                     .expect("cancelled")
-            })
-            .fold(EqwalizerDiagnostics::default, |acc, output| {
-                acc.combine((*output).clone())
-            })
-            .reduce(EqwalizerDiagnostics::default, |acc, other| {
-                acc.combine(other)
-            })
+            },
+        )
     });
     let eqwalized = pb.position();
     pb.finish();
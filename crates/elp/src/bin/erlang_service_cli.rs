@@ -26,6 +26,7 @@ use elp_ide::Analysis;
 use elp_ide::elp_ide_db::elp_base_db::FileId;
 use elp_ide::elp_ide_db::elp_base_db::IncludeOtp;
 use elp_ide::erlang_service::DiagnosticLocation;
+use elp_ide::erlang_service::pretty_etf;
 use elp_log::timeit;
 use elp_project_model::AppType;
 use elp_project_model::DiscoverConfig;
@@ -52,7 +53,8 @@ pub fn parse_all(args: &ParseAll, cli: &mut dyn Cli, query_config: &BuckQueryCon
     build::compile_deps(&loaded, cli)?;
     fs::create_dir_all(&args.to)?;
 
-    let parse_diagnostics = do_parse_all(cli, &loaded, &args.to, &args.module, args.buck)?;
+    let parse_diagnostics =
+        do_parse_all(cli, &loaded, &args.to, &args.module, args.buck, args.pretty)?;
     if args.stats {
         dump_stats(cli, args.list_modules);
     }
@@ -74,6 +76,7 @@ pub fn do_parse_all(
     to: &Path,
     module: &Option<String>,
     buck: bool,
+    pretty: bool,
 ) -> Result<Vec<ParseDiagnostic>> {
     let module_index = loaded.analysis().module_index(loaded.project_id)?;
     let file_cnt = module_index.len_own();
@@ -101,7 +104,7 @@ pub fn do_parse_all(
                     return empty;
                 }
 
-                do_parse_one(db, Some((name, to)), file_id)
+                do_parse_one(db, Some((name, to)), file_id, pretty)
                     .with_context(|| format!("Failed to parse module {}", name.as_str()))
             },
         )
@@ -117,6 +120,7 @@ pub fn do_parse_one(
     db: &Analysis,
     to: Option<(&str, &Path)>,
     file_id: FileId,
+    pretty: bool,
 ) -> Result<Vec<ParseDiagnostic>> {
     if let Some((name, _to)) = to {
         add_stat(name.to_string());
@@ -129,8 +133,13 @@ pub fn do_parse_one(
     let result = db.module_ast(file_id)?;
     if result.is_ok() {
         if let Some((name, to)) = to {
-            let to_path = to.join(format!("{name}.etf"));
-            fs::write(to_path, &*result.ast)?;
+            if pretty {
+                let to_path = to.join(format!("{name}.etf.txt"));
+                fs::write(to_path, pretty_etf::pretty_print(&result.ast)?)?;
+            } else {
+                let to_path = to.join(format!("{name}.etf"));
+                fs::write(to_path, &*result.ast)?;
+            }
         }
         Ok(vec![])
     } else {
@@ -189,7 +189,7 @@ fn do_parse_one(
             let project_id = db.project_id(file_id).unwrap().unwrap();
             let max_tasks = elp_project_model::EqwalizerConfig::default().max_tasks;
             if let Some(diags) = db
-                .eqwalizer_diagnostics_by_project(project_id, vec![file_id], max_tasks)
+                .eqwalizer_diagnostics_by_project(project_id, vec![file_id], max_tasks, |_| {})
                 .unwrap()
             {
                 diagnostics.set_eqwalizer_project(diags);
@@ -580,6 +580,8 @@ impl<'a> Lints<'a> {
                                 roots: None,
                                 files_changed: vec![(file_id, Some(Arc::from(source)))],
                                 app_structure: None,
+                                root_edits: Vec::new(),
+                                normalize_line_endings: false,
                             },
                             &|path| {
                                 self.vfs
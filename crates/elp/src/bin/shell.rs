@@ -268,7 +268,11 @@ fn process_changes_to_vfs_store(loaded: &mut LoadResult) -> bool {
         for (idx, set) in sets.into_iter().enumerate() {
             let root_id = SourceRootId(idx as u32);
             for file_id in set.iter() {
-                raw_database.set_file_source_root(file_id, root_id);
+                raw_database.set_file_source_root(
+                    file_id,
+                    root_id,
+                    set.path_for_file(&file_id).cloned(),
+                );
             }
             let root = SourceRoot::new(set);
             raw_database.set_source_root(root_id, Arc::new(root));
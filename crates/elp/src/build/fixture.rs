@@ -29,7 +29,9 @@ use crate::line_endings::LineEndings;
 pub fn load_result(fixture_str: &str) -> LoadResult {
     let (fixture, change, project) = ChangeFixture::parse_detail(fixture_str);
     let mut db = RootDatabase::default();
-    change.apply(&mut db, &|path| fixture.resolve_file_id(path));
+    change
+        .apply(&mut db, &|path| fixture.resolve_file_id(path))
+        .expect("fixture change should apply");
 
     let analysis_host = AnalysisHost::new(db);
     let (vfs, line_ending_map) = load_info_from_fixture(fixture_str);
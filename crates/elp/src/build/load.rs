@@ -82,6 +82,7 @@ pub fn load_project_at(
     let project = Project::load(
         &manifest,
         elp_config.eqwalizer.clone(),
+        elp_config.compile.clone(),
         query_config,
         &|_progress| {},
     )?;
@@ -184,7 +185,7 @@ fn load_database(
     for (idx, set) in sets.into_iter().enumerate() {
         let root_id = SourceRootId(idx as u32);
         for file_id in set.iter() {
-            db.set_file_source_root(file_id, root_id);
+            db.set_file_source_root(file_id, root_id, set.path_for_file(&file_id).cloned());
         }
         let root = SourceRoot::new(set);
         db.set_source_root(root_id, Arc::new(root));
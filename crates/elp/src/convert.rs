@@ -21,17 +21,17 @@ use elp_ide::diagnostics::DiagnosticTag;
 use elp_ide::diagnostics::RelatedInformation;
 use elp_ide::diagnostics::Severity;
 use elp_ide::elp_ide_db::EqwalizerDiagnostic;
+use elp_ide::elp_ide_db::EqwalizerDiagnostics;
 use elp_ide::elp_ide_db::LineIndex;
 use elp_ide::elp_ide_db::assists::AssistContextDiagnostic;
 use elp_ide::elp_ide_db::assists::AssistContextDiagnosticCode;
 use elp_ide::elp_ide_db::elp_base_db::AbsPath;
 use elp_ide::elp_ide_db::elp_base_db::AbsPathBuf;
+use elp_ide::elp_ide_db::elp_base_db::AbsPathExt;
 use elp_ide::elp_ide_db::elp_base_db::VfsPath;
 use lsp_types::DiagnosticRelatedInformation;
 use lsp_types::Location;
 use lsp_types::Url;
-use paths::Utf8Component;
-use paths::Utf8Prefix;
 
 use crate::arc_types;
 use crate::from_proto;
@@ -119,13 +119,58 @@ pub fn lsp_to_assist_context_diagnostic(
     }
 }
 
+/// The range of an eqWAlizer diagnostic, which is computed from the
+/// file eqWAlizer last read, so may extend beyond the end of the file.
+pub fn eqwalizer_range(line_index: &LineIndex, d: &EqwalizerDiagnostic) -> lsp_types::Range {
+    range(line_index, line_index.clamp(d.range))
+}
+
+/// Conversion of eqWAlizer results to LSP diagnostics.
+pub trait EqwalizerDiagnosticsExt {
+    /// The LSP diagnostics of a module, with `line_index` the line
+    /// index of its file. Results without diagnostics give none.
+    fn to_lsp(&self, line_index: &LineIndex) -> Vec<lsp_types::Diagnostic>;
+}
+
+impl EqwalizerDiagnosticsExt for EqwalizerDiagnostics {
+    fn to_lsp(&self, line_index: &LineIndex) -> Vec<lsp_types::Diagnostic> {
+        match self {
+            EqwalizerDiagnostics::Diagnostics { errors, .. } => errors
+                .values()
+                .flatten()
+                .map(|d| eqwalizer_to_lsp_diagnostic(line_index, d))
+                .collect(),
+            EqwalizerDiagnostics::NoAst { .. } | EqwalizerDiagnostics::Error(_) => vec![],
+        }
+    }
+}
+
+fn eqwalizer_to_lsp_diagnostic(
+    line_index: &LineIndex,
+    d: &EqwalizerDiagnostic,
+) -> lsp_types::Diagnostic {
+    lsp_types::Diagnostic {
+        range: eqwalizer_range(line_index, d),
+        severity: Some(lsp_types::DiagnosticSeverity::ERROR),
+        code: Some(lsp_types::NumberOrString::String(d.code.clone())),
+        code_description: lsp_types::Url::parse(&d.uri)
+            .ok()
+            .map(|href| lsp_types::CodeDescription { href }),
+        source: Some("eqWAlizer".into()),
+        message: d.full_message(),
+        related_information: None,
+        tags: None,
+        data: None,
+    }
+}
+
 pub fn eqwalizer_to_arc_diagnostic(
     d: &EqwalizerDiagnostic,
     line_index: &LineIndex,
     relative_path: &Path,
     eqwalizer_enabled: bool,
 ) -> arc_types::Diagnostic {
-    let pos = position(line_index, d.range.start());
+    let pos = eqwalizer_range(line_index, d).start;
     let line_num = pos.line + 1;
     let character = Some(pos.character + 1);
     let severity = if eqwalizer_enabled {
@@ -187,44 +232,12 @@ fn from_related(
     })
 }
 
-// Taken from rust-analyzer to_proto.rs
-
 /// Returns a `Url` object from a given path, will lowercase drive letters if present.
 /// This will only happen when processing windows paths.
 ///
 /// When processing non-windows path, this is essentially the same as `Url::from_file_path`.
 pub(crate) fn url_from_abs_path(path: &AbsPath) -> lsp_types::Url {
-    let url = lsp_types::Url::from_file_path(path).unwrap();
-    match path.components().next() {
-        Some(Utf8Component::Prefix(prefix))
-            if matches!(
-                prefix.kind(),
-                Utf8Prefix::Disk(_) | Utf8Prefix::VerbatimDisk(_)
-            ) =>
-        {
-            // Need to lowercase driver letter
-        }
-        _ => return url,
-    }
-
-    let driver_letter_range = {
-        let mut segments = url.as_str().splitn(3, ':');
-        let start = match segments.next() {
-            Some(scheme) => scheme.len() + ':'.len_utf8(),
-            None => return url,
-        };
-        match segments.next() {
-            Some(drive_letter) => start..(start + drive_letter.len()),
-            None => return url,
-        }
-    };
-
-    // Note: lowercasing the `path` itself doesn't help, the `Url::parse`
-    // machinery *also* canonicalizes the drive letter. So, just massage the
-    // string in place.
-    let mut url: String = url.into();
-    url[driver_letter_range].make_ascii_lowercase();
-    lsp_types::Url::parse(&url).unwrap()
+    lsp_types::Url::parse(&path.to_uri_string()).unwrap()
 }
 
 fn ide_to_arc_severity(severity: Severity) -> arc_types::Severity {
@@ -261,3 +274,98 @@ pub fn ide_to_arc_diagnostic(
         None,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(windows))]
+    fn uri_string_matches_url() {
+        for path in [
+            "/home/me/my project/a b.erl",
+            "/tmp/100%/a.erl",
+            "/tmp/#1/ü.erl",
+            r"/tmp/back\slash/a.erl",
+            "/tmp/./src/../include/a.hrl",
+        ] {
+            let path = AbsPath::assert(paths::Utf8Path::new(path));
+            assert_eq!(path.to_uri_string(), url_from_abs_path(path).as_str());
+        }
+        for path in [
+            "/home/me/my project/a b.erl",
+            "/tmp/100%/a.erl",
+            "/tmp/#1/ü.erl",
+        ] {
+            let path = AbsPath::assert(paths::Utf8Path::new(path));
+            assert_eq!(
+                path.to_uri_string(),
+                Url::from_file_path(path).unwrap().as_str()
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn uri_string_matches_url() {
+        for path in [
+            r"C:\Users\me\my project\a b.erl",
+            r"C:\tmp\100%\#1\a.erl",
+            r"D:\src\.\app\..\a.erl",
+        ] {
+            let path = AbsPath::assert(paths::Utf8Path::new(path));
+            assert_eq!(path.to_uri_string(), url_from_abs_path(path).as_str());
+        }
+    }
+
+    #[test]
+    fn eqwalizer_diagnostics_to_lsp() {
+        let text = "-module(m).\nf() ->\n    ok.\n";
+        let line_index = LineIndex::new(text);
+        let diagnostic = |start: u32, end: u32| EqwalizerDiagnostic {
+            range: TextRange::new(start.into(), end.into()),
+            message: "Expected: integer()".to_string(),
+            uri: "https://fb.me/eqwalizer_errors#incompatible_types".to_string(),
+            code: "incompatible_types".to_string(),
+            expression: Some("ok".to_string()),
+            explanation: None,
+            diagnostic: None,
+        };
+        // From `f() ->` to `ok`, and from `ok` to beyond the end of the file.
+        let diagnostics = EqwalizerDiagnostics::Diagnostics {
+            errors: [(
+                "m".to_string(),
+                vec![diagnostic(12, 25), diagnostic(23, 40)],
+            )]
+            .into_iter()
+            .collect(),
+            type_info: Default::default(),
+        };
+
+        let lsp = diagnostics.to_lsp(&line_index);
+        let ranges: Vec<_> = lsp.iter().map(|d| d.range).collect();
+        assert_eq!(
+            ranges,
+            vec![
+                lsp_types::Range::new(
+                    lsp_types::Position::new(1, 0),
+                    lsp_types::Position::new(2, 6)
+                ),
+                lsp_types::Range::new(
+                    lsp_types::Position::new(2, 4),
+                    lsp_types::Position::new(3, 0)
+                ),
+            ]
+        );
+        assert_eq!(
+            lsp[0].message,
+            "`ok`.\nExpected: integer()\n        See https://fb.me/eqwalizer_errors#incompatible_types"
+        );
+        assert_eq!(lsp[0].source.as_deref(), Some("eqWAlizer"));
+        assert!(
+            EqwalizerDiagnostics::Error("failed".to_string())
+                .to_lsp(&line_index)
+                .is_empty()
+        );
+    }
+}
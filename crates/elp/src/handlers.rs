@@ -816,16 +816,56 @@ pub(crate) fn handle_semantic_tokens_full(
     let highlights = snap
         .analysis
         .highlight(file_id, snap.eqwalizer_types.get(&file_id).cloned())?;
-    let semantic_tokens = to_proto::semantic_tokens(&text, &line_index, highlights);
+    let (semantic_tokens, result) = match &params.partial_result_params.partial_result_token {
+        Some(token) => {
+            // Send the tokens as they are encoded, the response then only
+            // carries the result id.
+            let mut data = Vec::new();
+            let result_id = to_proto::semantic_tokens_streaming(
+                &text,
+                &line_index,
+                highlights,
+                SEMANTIC_TOKENS_CHUNK_SIZE,
+                |chunk| {
+                    snap.send_partial_result(
+                        token,
+                        lsp_types::SemanticTokensPartialResult {
+                            data: chunk.clone(),
+                        },
+                    );
+                    data.extend(chunk);
+                },
+            );
+            let result = lsp_types::SemanticTokens {
+                result_id: Some(result_id.clone()),
+                data: vec![],
+            };
+            let semantic_tokens = lsp_types::SemanticTokens {
+                result_id: Some(result_id),
+                data,
+            };
+            (semantic_tokens, result)
+        }
+        None => {
+            let semantic_tokens = to_proto::semantic_tokens(&text, &line_index, highlights);
+            (semantic_tokens.clone(), semantic_tokens)
+        }
+    };
 
     // Unconditionally cache the tokens
     snap.semantic_tokens_cache
         .lock()
-        .insert(params.text_document.uri, semantic_tokens.clone());
+        .entry(params.text_document.uri)
+        .or_default()
+        .insert(semantic_tokens);
 
-    Ok(Some(semantic_tokens.into()))
+    Ok(Some(result.into()))
 }
 
+/// How many semantic tokens are sent at a time, to clients that accept
+/// partial results.
+const SEMANTIC_TOKENS_CHUNK_SIZE: usize = 10_000;
+
 pub(crate) fn handle_semantic_tokens_full_delta(
     snap: Snapshot,
     params: SemanticTokensDeltaParams,
@@ -841,20 +881,14 @@ pub(crate) fn handle_semantic_tokens_full_delta(
         .highlight(file_id, snap.eqwalizer_types.get(&file_id).cloned())?;
     let semantic_tokens = to_proto::semantic_tokens(&text, &line_index, highlights);
 
-    let mut cache = snap.semantic_tokens_cache.lock();
-    let cached_tokens = cache.entry(params.text_document.uri).or_default();
-
-    if let Some(prev_id) = &cached_tokens.result_id {
-        if *prev_id == params.previous_result_id {
-            let delta = to_proto::semantic_token_delta(cached_tokens, &semantic_tokens);
-            *cached_tokens = semantic_tokens;
-            return Ok(Some(delta.into()));
-        }
-    }
-
-    *cached_tokens = semantic_tokens.clone();
+    let result = snap
+        .semantic_tokens_cache
+        .lock()
+        .entry(params.text_document.uri)
+        .or_default()
+        .delta(&params.previous_result_id, semantic_tokens);
 
-    Ok(Some(semantic_tokens.into()))
+    Ok(Some(result))
 }
 
 pub(crate) fn handle_semantic_tokens_range(
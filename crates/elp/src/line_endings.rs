@@ -8,30 +8,7 @@
  * above-listed licenses.
  */
 
-//! We maintain invariant that all internal strings use `\n` as line separator.
-//! This module does line ending conversion and detection (so that we can
-//! convert back to `\r\n` on the way out).
+//! Line ending conversion lives in `elp_base_db`, so that `Change` can
+//! normalize file texts on ingestion too.
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub enum LineEndings {
-    Unix,
-    Dos,
-}
-
-impl LineEndings {
-    /// Replaces `\r\n` with `\n` in `src`.
-    pub fn normalize(src: String) -> (String, Self) {
-        if !src.as_bytes().contains(&b'\r') {
-            (src, Self::Unix)
-        } else {
-            (src.replace("\r\n", "\n"), Self::Dos)
-        }
-    }
-
-    pub fn revert(&self, src: String) -> String {
-        match self {
-            Self::Unix => src,
-            Self::Dos => src.replace('\n', "\r\n"),
-        }
-    }
-}
+pub use elp_ide::elp_ide_db::elp_base_db::LineEndings;
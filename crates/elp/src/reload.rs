@@ -54,12 +54,12 @@ impl ProjectFolders {
             .flat_map(|(project_id, app)| {
                 iter::repeat(project_id).zip(app.all_source_and_include_dirs())
             })
-            .filter_map(|(project_id, root)| {
-                if Some(*project_id) != project_apps.otp_project_id {
-                    Some(format!("{root}/**/*.{{e,h}}rl"))
-                } else {
-                    None
-                }
+            .filter(|(project_id, _)| Some(**project_id) != project_apps.otp_project_id)
+            .flat_map(|(_, root)| {
+                [
+                    format!("{root}/**/*.{{e,h}}rl"),
+                    format!("{root}/**/*.ast.json"),
+                ]
             })
             .collect();
 
@@ -125,7 +125,14 @@ fn loader_config(project_apps: &ProjectApps<'_>) -> Vec<loader::Entry> {
             .map(|chunk| -> Vec<AbsPathBuf> { chunk.into() })
             .map(|include| {
                 loader::Entry::Directories(loader::Directories {
-                    extensions: vec!["erl".to_string(), "hrl".to_string(), "escript".to_string()],
+                    // `json` for the `.ast.json` sidecars eqWAlizer may
+                    // load stubs from
+                    extensions: vec![
+                        "erl".to_string(),
+                        "hrl".to_string(),
+                        "escript".to_string(),
+                        "json".to_string(),
+                    ],
                     include,
                     exclude: vec![],
                 })
@@ -10,6 +10,7 @@
 
 //! Semantic Tokens helpers
 
+use std::collections::VecDeque;
 use std::ops;
 
 use lsp_types::Range;
@@ -17,7 +18,9 @@ use lsp_types::SemanticToken;
 use lsp_types::SemanticTokenModifier;
 use lsp_types::SemanticTokenType;
 use lsp_types::SemanticTokens;
+use lsp_types::SemanticTokensDelta;
 use lsp_types::SemanticTokensEdit;
+use lsp_types::SemanticTokensFullDeltaResult;
 
 macro_rules! define_semantic_token_types {
     (
@@ -39,6 +42,9 @@ macro_rules! define_semantic_token_types {
     };
 }
 
+// Clients may cache the legend, so the index of a token type or modifier
+// must never change: new ones go at the end of the `custom` list, even
+// standard ones, by giving their LSP name. See `legend_is_append_only`.
 define_semantic_token_types![
     standard {
         // COMMENT,
@@ -63,6 +69,7 @@ define_semantic_token_types![
 
     custom {
         (GENERIC, "generic"),
+        (OPERATOR, "operator"),
     }
 ];
 
@@ -96,10 +103,11 @@ define_semantic_token_modifiers![
         (EXPORTED_TYPE, "exported_type"),
         (DEPRECATED_FUNCTION, "deprecated_function"),
         (TYPE_DYNAMIC, "type_dynamic"),
+        (DEFINITION, "definition"),
     }
 ];
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub(crate) struct ModifierSet(pub(crate) u32);
 
 impl ops::BitOrAssign<SemanticTokenModifier> for ModifierSet {
@@ -161,15 +169,138 @@ impl SemanticTokensBuilder {
         self.prev_char = range.start.character;
     }
 
+    /// Push a new token of type `ty`, resolving its index in the legend.
+    /// Prefer `push` when pushing many tokens of the same type.
+    pub(crate) fn push_typed(
+        &mut self,
+        range: Range,
+        ty: SemanticTokenType,
+        modifiers: ModifierSet,
+    ) {
+        self.push(range, type_index(ty), modifiers.0);
+    }
+
     pub(crate) fn build(self) -> SemanticTokens {
         SemanticTokens {
             result_id: Some(self.id),
             data: self.data,
         }
     }
+
+    /// Take the tokens pushed so far. Tokens pushed afterwards are still
+    /// encoded relative to the last one taken, so the chunks taken
+    /// concatenate to what `build` would have returned.
+    fn take_chunk(&mut self) -> Vec<SemanticToken> {
+        std::mem::take(&mut self.data)
+    }
+}
+
+/// Like `SemanticTokensBuilder`, handing the encoded tokens to `sink`
+/// every `chunk_size` tokens rather than holding them all in memory, so
+/// that they can be sent before the whole file is processed.
+pub(crate) struct StreamingSemanticTokensBuilder<F: FnMut(Vec<SemanticToken>)> {
+    builder: SemanticTokensBuilder,
+    chunk_size: usize,
+    sink: F,
+}
+
+impl<F: FnMut(Vec<SemanticToken>)> StreamingSemanticTokensBuilder<F> {
+    pub(crate) fn new(id: String, chunk_size: usize, sink: F) -> Self {
+        StreamingSemanticTokensBuilder {
+            builder: SemanticTokensBuilder::new(id),
+            chunk_size: chunk_size.max(1),
+            sink,
+        }
+    }
+
+    /// Push a new token, see `SemanticTokensBuilder::push_typed`.
+    pub(crate) fn push_typed(
+        &mut self,
+        range: Range,
+        ty: SemanticTokenType,
+        modifiers: ModifierSet,
+    ) {
+        self.builder.push_typed(range, ty, modifiers);
+        if self.builder.data.len() >= self.chunk_size {
+            (self.sink)(self.builder.take_chunk());
+        }
+    }
+
+    /// Hand the remaining tokens to the sink, returning the result id.
+    pub(crate) fn finish(mut self) -> String {
+        let chunk = self.builder.take_chunk();
+        if !chunk.is_empty() {
+            (self.sink)(chunk);
+        }
+        self.builder.id
+    }
+}
+
+/// The tokens recently sent for a document, by `result_id`, so that a
+/// `semanticTokens/full/delta` request can be answered with the edits
+/// from the tokens it names.
+#[derive(Debug, Default)]
+pub(crate) struct SemanticTokensCache {
+    results: VecDeque<SemanticTokens>,
+}
+
+impl SemanticTokensCache {
+    /// How many results are kept, the oldest ones are evicted first.
+    const CAPACITY: usize = 4;
+
+    pub(crate) fn insert(&mut self, tokens: SemanticTokens) {
+        if self.results.len() == Self::CAPACITY {
+            self.results.pop_front();
+        }
+        self.results.push_back(tokens);
+    }
+
+    pub(crate) fn get(&self, result_id: &str) -> Option<&SemanticTokens> {
+        self.results
+            .iter()
+            .find(|tokens| tokens.result_id.as_deref() == Some(result_id))
+    }
+
+    /// Record `tokens`, and return them as edits from the tokens of
+    /// `previous_result_id` if those are still known, in full otherwise.
+    pub(crate) fn delta(
+        &mut self,
+        previous_result_id: &str,
+        tokens: SemanticTokens,
+    ) -> SemanticTokensFullDeltaResult {
+        let result = match self.get(previous_result_id) {
+            Some(previous) => SemanticTokensDelta {
+                result_id: tokens.result_id.clone(),
+                edits: diff_tokens(&previous.data, &tokens.data),
+            }
+            .into(),
+            None => tokens.clone().into(),
+        };
+        self.insert(tokens);
+        result
+    }
 }
 
 pub(crate) fn diff_tokens(old: &[SemanticToken], new: &[SemanticToken]) -> Vec<SemanticTokensEdit> {
+    match (old.is_empty(), new.is_empty()) {
+        (true, true) => return vec![],
+        (true, false) => {
+            return vec![SemanticTokensEdit {
+                start: 0,
+                delete_count: 0,
+                data: Some(new.into()),
+            }];
+        }
+        (false, true) => {
+            return vec![SemanticTokensEdit {
+                start: 0,
+                delete_count: 5 * old.len() as u32,
+                data: Some(vec![]),
+            }];
+        }
+        (false, false) => {}
+    }
+
     let offset = new
         .iter()
         .zip(old.iter())
@@ -185,6 +316,7 @@ pub(crate) fn diff_tokens(old: &[SemanticToken], new: &[SemanticToken]) -> Vec<S
         .zip(old.iter().rev())
         .take_while(|&(n, p)| n == p)
         .count();
+    debug_assert!(offset_from_end <= old.len() && offset_from_end <= new.len());
 
     let (old, _) = old.split_at(old.len() - offset_from_end);
     let (new, _) = new.split_at(new.len() - offset_from_end);
@@ -221,6 +353,198 @@ mod tests {
         }
     }
 
+    #[test]
+    fn legend_is_append_only() {
+        let types = [
+            (FUNCTION, 0),
+            (MACRO, 1),
+            (NAMESPACE, 2),
+            (STRING, 3),
+            (STRUCT, 4),
+            (TYPE_PARAMETER, 5),
+            (VARIABLE, 6),
+            (GENERIC, 7),
+            (OPERATOR, 8),
+        ];
+        for (ty, idx) in types {
+            assert_eq!(type_index(ty.clone()), idx, "index of {ty:?} changed");
+        }
+        let modifiers = [
+            (BOUND, 0),
+            (EXPORTED_FUNCTION, 1),
+            (EXPORTED_TYPE, 2),
+            (DEPRECATED_FUNCTION, 3),
+            (TYPE_DYNAMIC, 4),
+            (DEFINITION, 5),
+        ];
+        for (modifier, idx) in modifiers {
+            let mut set = ModifierSet::default();
+            set |= modifier.clone();
+            assert_eq!(set.0, 1 << idx, "index of {modifier:?} changed");
+        }
+    }
+
+    #[test]
+    fn push_typed_matches_push() {
+        let range = |line, start, end| {
+            Range::new(
+                lsp_types::Position::new(line, start),
+                lsp_types::Position::new(line, end),
+            )
+        };
+        let mut modifiers = ModifierSet::default();
+        modifiers |= EXPORTED_FUNCTION;
+        modifiers |= DEPRECATED_FUNCTION;
+
+        let mut typed = SemanticTokensBuilder::new("1".to_string());
+        typed.push_typed(range(0, 0, 3), FUNCTION, modifiers);
+        typed.push_typed(range(2, 4, 7), GENERIC, ModifierSet::default());
+
+        let mut raw = SemanticTokensBuilder::new("1".to_string());
+        raw.push(range(0, 0, 3), type_index(FUNCTION), (1 << 1) | (1 << 3));
+        raw.push(range(2, 4, 7), type_index(GENERIC), 0);
+
+        assert_eq!(typed.build(), raw.build());
+    }
+
+    #[test]
+    fn streaming_matches_build() {
+        let ranges: Vec<Range> = (0..10)
+            .map(|i| {
+                // Several tokens per line, so that chunks end both within
+                // and at the end of a line
+                let (line, start) = (i / 3, (i % 3) * 4);
+                Range::new(
+                    lsp_types::Position::new(line, start),
+                    lsp_types::Position::new(line, start + 2),
+                )
+            })
+            .collect();
+
+        let ty = |i: usize| SUPPORTED_TYPES[i % 3].clone();
+        let mut builder = SemanticTokensBuilder::new("1".to_string());
+        for (i, range) in ranges.iter().enumerate() {
+            builder.push_typed(*range, ty(i), ModifierSet::default());
+        }
+        let built = builder.build();
+
+        for chunk_size in [1, 3, 4, 100] {
+            let mut chunks: Vec<Vec<SemanticToken>> = vec![];
+            let mut streaming =
+                StreamingSemanticTokensBuilder::new("1".to_string(), chunk_size, |chunk| {
+                    chunks.push(chunk)
+                });
+            for (i, range) in ranges.iter().enumerate() {
+                streaming.push_typed(*range, ty(i), ModifierSet::default());
+            }
+            let id = streaming.finish();
+            assert_eq!(chunks.len(), ranges.len().div_ceil(chunk_size));
+            let streamed = SemanticTokens {
+                result_id: Some(id),
+                data: chunks.concat(),
+            };
+            assert_eq!(streamed, built, "chunk size {chunk_size}");
+        }
+    }
+
+    fn tokens(id: &str, data: Vec<SemanticToken>) -> SemanticTokens {
+        SemanticTokens {
+            result_id: Some(id.to_string()),
+            data,
+        }
+    }
+
+    #[test]
+    fn cache_full_then_delta() {
+        let mut cache = SemanticTokensCache::default();
+        cache.insert(tokens(
+            "1",
+            vec![
+                from((1, 2, 3, 4, 5)),
+                from((6, 7, 8, 9, 10)),
+                from((11, 12, 13, 14, 15)),
+            ],
+        ));
+
+        let edited = tokens(
+            "2",
+            vec![
+                from((1, 2, 3, 4, 5)),
+                from((6, 7, 9, 9, 10)),
+                from((11, 12, 13, 14, 15)),
+            ],
+        );
+        assert_eq!(
+            cache.delta("1", edited.clone()),
+            SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                result_id: Some("2".to_string()),
+                edits: vec![SemanticTokensEdit {
+                    start: 5,
+                    delete_count: 5,
+                    data: Some(vec![from((6, 7, 9, 9, 10))])
+                }],
+            })
+        );
+        assert_eq!(cache.get("2"), Some(&edited));
+
+        // An unknown previous result gets the tokens in full
+        let unknown = tokens("3", vec![from((1, 2, 3, 4, 5))]);
+        assert_eq!(
+            cache.delta("0", unknown.clone()),
+            SemanticTokensFullDeltaResult::Tokens(unknown)
+        );
+    }
+
+    #[test]
+    fn cache_evicts_oldest() {
+        let mut cache = SemanticTokensCache::default();
+        for id in 0..=SemanticTokensCache::CAPACITY {
+            cache.insert(tokens(&id.to_string(), vec![]));
+        }
+        assert!(cache.get("0").is_none());
+        assert!(cache.get("1").is_some());
+        assert!(
+            cache
+                .get(&SemanticTokensCache::CAPACITY.to_string())
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_diff_empty_old() {
+        let after = [from((1, 2, 3, 4, 5)), from((6, 7, 8, 9, 10))];
+
+        let edits = diff_tokens(&[], &after);
+        assert_eq!(
+            edits,
+            vec![SemanticTokensEdit {
+                start: 0,
+                delete_count: 0,
+                data: Some(after.to_vec())
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_empty_new() {
+        let before = [from((1, 2, 3, 4, 5)), from((6, 7, 8, 9, 10))];
+
+        let edits = diff_tokens(&before, &[]);
+        assert_eq!(
+            edits,
+            vec![SemanticTokensEdit {
+                start: 0,
+                delete_count: 10,
+                data: Some(vec![])
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_both_empty() {
+        assert!(diff_tokens(&[], &[]).is_empty());
+    }
+
     #[test]
     fn test_diff_insert_at_end() {
         let before = [from((1, 2, 3, 4, 5)), from((6, 7, 8, 9, 10))];
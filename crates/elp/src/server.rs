@@ -16,7 +16,9 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::task::Poll;
 use std::time::Duration;
+use std::time::Instant;
 
 use always_assert::always;
 use anyhow::Result;
@@ -27,6 +29,7 @@ use crossbeam_channel::Sender;
 use crossbeam_channel::select;
 use dispatch::NotificationDispatcher;
 use elp_eqwalizer::ast::Pos;
+use elp_eqwalizer::db::refresh_ebin_beam_files;
 use elp_eqwalizer::types::Type;
 use elp_ide::Analysis;
 use elp_ide::AnalysisHost;
@@ -37,6 +40,7 @@ use elp_ide::diagnostics::LintConfig;
 use elp_ide::diagnostics_collection::DiagnosticCollection;
 use elp_ide::elp_ide_db::elp_base_db::AbsPath;
 use elp_ide::elp_ide_db::elp_base_db::AbsPathBuf;
+use elp_ide::elp_ide_db::elp_base_db::AbsPathExt;
 use elp_ide::elp_ide_db::elp_base_db::AppDataId;
 use elp_ide::elp_ide_db::elp_base_db::AppDataIndex;
 use elp_ide::elp_ide_db::elp_base_db::ChangedFile;
@@ -112,6 +116,7 @@ use crate::project_loader::ProjectLoader;
 use crate::project_loader::ReloadManager;
 use crate::read_lint_config_file;
 use crate::reload::ProjectFolders;
+use crate::semantic_tokens::SemanticTokensCache;
 use crate::snapshot::SharedMap;
 use crate::snapshot::Snapshot;
 use crate::task_pool::TaskPool;
@@ -135,6 +140,9 @@ const SLOW_DURATION: Duration = Duration::from_millis(300);
 /// If the main loop exceeds this time, log the specific request causing the problem
 const TOO_SLOW_DURATION: Duration = Duration::from_millis(3000);
 const INCLUDE_GENERATED: bool = true;
+/// How long a pass over the opened documents waits for their eqWAlizer
+/// diagnostics, those not ready by then are picked up by the next pass.
+const EQWALIZER_DIAGNOSTICS_DEADLINE: Duration = Duration::from_secs(2);
 
 enum Event {
     Lsp(lsp_server::Message),
@@ -151,12 +159,15 @@ pub enum Task {
     ShowMessageRequest(lsp_types::ShowMessageRequestParams),
     FetchProject(Spinner, Vec<Project>),
     NativeDiagnostics(Vec<(FileId, LabeledDiagnostics)>),
+    /// Also tells whether some documents are still checked in the
+    /// background, see `EQWALIZER_DIAGNOSTICS_DEADLINE`.
     EqwalizerDiagnostics(
         Spinner,
         Vec<(FileId, Vec<diagnostics::Diagnostic>, Arc<Vec<(Pos, Type)>>)>,
+        bool,
     ),
     EqwalizerProjectDiagnostics(
-        Spinner,
+        Arc<ProgressBar>,
         Vec<(ProjectId, Vec<(FileId, Vec<diagnostics::Diagnostic>)>)>,
     ),
     EdocDiagnostics(Spinner, Vec<(FileId, Vec<diagnostics::Diagnostic>)>),
@@ -237,6 +248,7 @@ pub struct Server {
     eqwalizer_pool: TaskHandle,
     diagnostics: Arc<DiagnosticCollection>,
     eqwalizer_types: Arc<EqwalizerTypes>,
+    semantic_tokens_cache: Arc<Mutex<FxHashMap<Url, SemanticTokensCache>>>,
     req_queue: ReqQueue,
     progress: ProgressManager,
     mem_docs: Arc<RwLock<MemDocs>>,
@@ -304,6 +316,7 @@ impl Server {
             eqwalizer_pool,
             diagnostics: Arc::new(DiagnosticCollection::default()),
             eqwalizer_types: Arc::new(FxHashMap::default()),
+            semantic_tokens_cache: Arc::new(Mutex::new(FxHashMap::default())),
             req_queue: ReqQueue::default(),
             mem_docs: Arc::new(RwLock::new(MemDocs::default())),
             newly_opened_documents: Vec::default(),
@@ -337,6 +350,13 @@ impl Server {
 
         // Run config-based initialisation
         this.update_configuration(config);
+        // Only read on start-up, to finish the modules a previous
+        // session left in flight.
+        if let Some(path) = this.config.eqwalizer().in_flight_file {
+            this.analysis_host
+                .raw_database()
+                .persist_eqwalizing_in_flight(Some(PathBuf::from(path)));
+        }
         this
     }
 
@@ -347,10 +367,12 @@ impl Server {
             self.analysis_host.analysis(),
             Arc::clone(&self.diagnostics),
             Arc::clone(&self.eqwalizer_types),
+            Arc::clone(&self.semantic_tokens_cache),
             Arc::clone(&self.vfs),
             Arc::clone(&self.mem_docs),
             Arc::clone(&self.line_ending_map),
             Arc::clone(&self.projects),
+            self.connection.sender.clone(),
         )
     }
 
@@ -469,12 +491,16 @@ impl Server {
                     self.reload_manager.lock().set_reload_done();
                 }
                 Task::NativeDiagnostics(diags) => self.native_diagnostics_completed(diags),
-                Task::EqwalizerDiagnostics(spinner, diags_types) => {
+                Task::EqwalizerDiagnostics(spinner, diags_types, pending) => {
                     spinner.end();
-                    self.eqwalizer_diagnostics_completed(diags_types)
+                    self.eqwalizer_diagnostics_completed(diags_types);
+                    if pending {
+                        self.update_eqwalizer_diagnostics();
+                    }
                 }
-                Task::EqwalizerProjectDiagnostics(spinner, diags) => {
-                    spinner.end();
+                Task::EqwalizerProjectDiagnostics(bar, diags) => {
+                    // The last reference, ending the bar
+                    drop(bar);
                     self.eqwalizer_project_diagnostics_completed(diags)
                 }
                 Task::EdocDiagnostics(spinner, diags) => {
@@ -755,6 +781,7 @@ impl Server {
             })?
             .on::<notification::DidCloseTextDocument>(|this, params| {
                 let url = params.text_document.uri;
+                this.semantic_tokens_cache.lock().remove(&url);
                 let analysis = this.snapshot().analysis;
                 let mut diagnostics = Vec::new();
                 if let Ok(path) = convert::vfs_path(&url) {
@@ -1075,6 +1102,7 @@ impl Server {
                     }
                 });
             raw_database.set_app_index(app_data_index);
+            refresh_ebin_beam_files(raw_database);
             for remove in paths_to_remove {
                 Arc::make_mut(&mut self.unresolved_app_id_paths).remove(&remove);
             }
@@ -1090,7 +1118,11 @@ impl Server {
             for (idx, set) in sets.into_iter().enumerate() {
                 let root_id = SourceRootId(idx as u32);
                 for file_id in set.iter() {
-                    raw_database.set_file_source_root(file_id, root_id);
+                    raw_database.set_file_source_root(
+                        file_id,
+                        root_id,
+                        set.path_for_file(&file_id).cloned(),
+                    );
                 }
                 let root = SourceRoot::new(set);
                 raw_database.set_source_root(root_id, Arc::new(root));
@@ -1147,12 +1179,20 @@ impl Server {
 
         let include_otp = self.config.enable_otp_diagnostics();
         self.task_pool.handle.spawn(move || {
+            let deadline = Instant::now() + EQWALIZER_DIAGNOSTICS_DEADLINE;
+            let mut pending = false;
             let diagnostics_types = opened_documents
                 .into_iter()
                 .filter_map(|file_id| {
-                    let diags = snapshot
-                        .eqwalizer_diagnostics(file_id, include_otp)
-                        .unwrap_or_default();
+                    let diags = match snapshot.eqwalizer_diagnostics(file_id, include_otp, deadline)
+                    {
+                        Poll::Ready(diags) => diags.unwrap_or_default(),
+                        // Left as they are until the next pass
+                        Poll::Pending => {
+                            pending = true;
+                            return None;
+                        }
+                    };
                     let types = snapshot
                         .eqwalizer_types(file_id, include_otp)
                         .unwrap_or_default();
@@ -1164,7 +1204,7 @@ impl Server {
                 })
                 .collect();
 
-            Task::EqwalizerDiagnostics(spinner, diagnostics_types)
+            Task::EqwalizerDiagnostics(spinner, diagnostics_types, pending)
         });
     }
 
@@ -1176,26 +1216,38 @@ impl Server {
         log::info!("Recomputing EqWAlizer (project-wide) diagnostics");
 
         let snapshot = self.snapshot();
-        let spinner = self
-            .progress
-            .begin_spinner("EqWAlizing All (project-wide)".to_string());
+        let bar = Arc::new(
+            self.progress
+                .begin_bar("EqWAlizing All (project-wide)".to_string(), None),
+        );
         let max_tasks = self.config.eqwalizer().max_tasks;
 
         self.eqwalizer_pool.handle.spawn(move || {
+            let projects = snapshot.projects.len();
             let diagnostics = snapshot
                 .projects
                 .iter()
                 .enumerate()
                 .filter_map(|(id, _project)| {
                     let project_id = ProjectId(id as u32);
+                    // The projects are checked one after the other, so
+                    // each has an equal share of the bar.
+                    let bar = bar.clone();
+                    let on_progress = move |percentage: u32| {
+                        bar.report(id * 100 + percentage as usize, projects * 100)
+                    };
                     Some((
                         project_id,
-                        snapshot.eqwalizer_project_diagnostics(project_id, max_tasks)?,
+                        snapshot.eqwalizer_project_diagnostics(
+                            project_id,
+                            max_tasks,
+                            on_progress,
+                        )?,
                     ))
                 })
                 .collect();
 
-            Task::EqwalizerProjectDiagnostics(spinner, diagnostics)
+            Task::EqwalizerProjectDiagnostics(bar, diagnostics)
         });
     }
 
@@ -1329,7 +1381,7 @@ impl Server {
         let mut projects: Vec<Project> = self.projects.iter().cloned().collect();
         for project in new_projects {
             let idx = projects.iter().enumerate().find_map(|(id, p)| {
-                if p.root() == project.root() {
+                if p.root().eq_ignoring_trailing_sep(&project.root()) {
                     Some(id)
                 } else {
                     None
@@ -1629,6 +1681,7 @@ impl Server {
         let mut project = Project::load(
             &manifest,
             elp_config.eqwalizer.clone(),
+            elp_config.compile.clone(),
             query_config,
             &|message| spinner.report(message.to_string()),
         );
@@ -1647,10 +1700,13 @@ impl Server {
                 }
             }
             if !fallback_used {
-                project =
-                    Project::load(&fallback, elp_config.eqwalizer, query_config, &|message| {
-                        spinner.report(message.to_string())
-                    });
+                project = Project::load(
+                    &fallback,
+                    elp_config.eqwalizer,
+                    elp_config.compile,
+                    query_config,
+                    &|message| spinner.report(message.to_string()),
+                );
                 if let Err(err) = &project {
                     log::error!(
                         "Failed to load project for fallback manifest {manifest:?}, error: {err:?}"
@@ -1918,9 +1974,15 @@ impl Server {
                 } else {
                     std::mem::take(&mut files)
                 };
+                // The bar is reported once per chunk below
                 if snapshot
                     .analysis
-                    .eqwalizer_diagnostics_by_project(project_id, file_ids.clone(), max_tasks)
+                    .eqwalizer_diagnostics_by_project(
+                        project_id,
+                        file_ids.clone(),
+                        max_tasks,
+                        |_| {},
+                    )
                     .is_err()
                 {
                     //got canceled
@@ -10,9 +10,12 @@
 
 use std::fmt;
 use std::sync::Arc;
+use std::task::Poll;
+use std::time::Instant;
 
 use anyhow::Context;
 use anyhow::Result;
+use crossbeam_channel::Sender;
 use elp_eqwalizer::ast::Pos;
 use elp_eqwalizer::types::Type;
 use elp_ide::Analysis;
@@ -30,8 +33,9 @@ use elp_log::timeit_with_telemetry;
 use elp_project_model::Project;
 use fxhash::FxHashMap;
 use itertools::Itertools;
-use lsp_types::SemanticTokens;
 use lsp_types::Url;
+use lsp_types::notification;
+use lsp_types::notification::Notification as _;
 use parking_lot::Mutex;
 use parking_lot::RwLock;
 use serde::Deserialize;
@@ -41,6 +45,7 @@ use crate::config::Config;
 use crate::convert;
 use crate::line_endings::LineEndings;
 use crate::mem_docs::MemDocs;
+use crate::semantic_tokens::SemanticTokensCache;
 use crate::server::EqwalizerTypes;
 use crate::server::file_id_to_path;
 use crate::server::file_id_to_url;
@@ -102,11 +107,12 @@ pub struct Snapshot {
     pub(crate) analysis: Analysis,
     pub(crate) diagnostics: Arc<DiagnosticCollection>,
     pub(crate) eqwalizer_types: Arc<EqwalizerTypes>,
-    pub(crate) semantic_tokens_cache: Arc<Mutex<FxHashMap<Url, SemanticTokens>>>,
+    pub(crate) semantic_tokens_cache: Arc<Mutex<FxHashMap<Url, SemanticTokensCache>>>,
     vfs: Arc<RwLock<Vfs>>,
     pub(crate) mem_docs: Arc<RwLock<MemDocs>>,
     line_ending_map: SharedMap<FileId, LineEndings>,
     pub(crate) projects: Arc<Vec<Project>>,
+    sender: Sender<lsp_server::Message>,
 }
 
 impl Snapshot {
@@ -117,10 +123,12 @@ impl Snapshot {
         analysis: Analysis,
         diagnostics: Arc<DiagnosticCollection>,
         eqwalizer_types: Arc<EqwalizerTypes>,
+        semantic_tokens_cache: Arc<Mutex<FxHashMap<Url, SemanticTokensCache>>>,
         vfs: Arc<RwLock<Vfs>>,
         mem_docs: Arc<RwLock<MemDocs>>,
         line_ending_map: Arc<RwLock<FxHashMap<FileId, LineEndings>>>,
         projects: Arc<Vec<Project>>,
+        sender: Sender<lsp_server::Message>,
     ) -> Self {
         Snapshot {
             config,
@@ -128,11 +136,27 @@ impl Snapshot {
             analysis,
             diagnostics,
             eqwalizer_types,
-            semantic_tokens_cache: Arc::new(Default::default()),
+            semantic_tokens_cache,
             vfs,
             mem_docs,
             line_ending_map,
             projects,
+            sender,
+        }
+    }
+
+    /// Report part of the result of a request, for clients that passed a
+    /// `partialResultToken`. A client that has gone away is not an error.
+    pub(crate) fn send_partial_result(
+        &self,
+        token: &lsp_types::ProgressToken,
+        value: impl Serialize,
+    ) {
+        let params = serde_json::json!({ "token": token, "value": value });
+        let notification =
+            lsp_server::Notification::new(notification::Progress::METHOD.to_string(), params);
+        if let Err(err) = self.sender.send(notification.into()) {
+            log::warn!("Could not send partial result: {err}");
         }
     }
 
@@ -194,24 +218,30 @@ impl Snapshot {
             .ok()
     }
 
+    /// `Poll::Pending` when not computed by `deadline`, see
+    /// `Analysis::eqwalizer_diagnostics_for_file_by_deadline`.
     pub fn eqwalizer_diagnostics(
         &self,
         file_id: FileId,
         include_otp: bool,
-    ) -> Option<Vec<diagnostics::Diagnostic>> {
+        deadline: Instant,
+    ) -> Poll<Option<Vec<diagnostics::Diagnostic>>> {
         if !include_otp && self.is_otp(file_id) {
-            return None;
+            return Poll::Ready(None);
         }
 
         let file_url = self.file_id_to_url(file_id);
         let _timer = timeit_with_telemetry!(TelemetryData::EqwalizerDiagnostics { file_url });
-        self.analysis.eqwalizer_diagnostics_for_file(file_id).ok()?
+        self.analysis
+            .eqwalizer_diagnostics_for_file_by_deadline(file_id, deadline)
+            .unwrap_or(Poll::Ready(None))
     }
 
     pub fn eqwalizer_project_diagnostics(
         &self,
         project_id: ProjectId,
         max_tasks: usize,
+        on_progress: impl Fn(u32) + Send + Sync + 'static,
     ) -> Option<Vec<(FileId, Vec<diagnostics::Diagnostic>)>> {
         let module_index = self.analysis.module_index(project_id).ok()?;
 
@@ -240,7 +270,7 @@ impl Snapshot {
             timeit_with_telemetry!(TelemetryData::EqwalizerProjectDiagnostics { project_name });
 
         self.analysis
-            .eqwalizer_diagnostics_by_project(project_id, file_ids, max_tasks)
+            .eqwalizer_diagnostics_by_project(project_id, file_ids, max_tasks, on_progress)
             .ok()?
     }
 
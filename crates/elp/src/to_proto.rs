@@ -543,19 +543,51 @@ pub(crate) fn semantic_tokens(
     line_index: &LineIndex,
     highlights: Vec<HlRange>,
 ) -> lsp_types::SemanticTokens {
-    let id = TOKEN_RESULT_COUNTER
+    let mut builder = semantic_tokens::SemanticTokensBuilder::new(next_token_result_id());
+    push_semantic_tokens(text, line_index, highlights, |range, ty, mods| {
+        builder.push_typed(range, ty, mods)
+    });
+    builder.build()
+}
+
+/// Like `semantic_tokens`, but hands the tokens to `sink` in chunks of
+/// `chunk_size` as they are encoded. Returns the result id.
+pub(crate) fn semantic_tokens_streaming(
+    text: &str,
+    line_index: &LineIndex,
+    highlights: Vec<HlRange>,
+    chunk_size: usize,
+    sink: impl FnMut(Vec<lsp_types::SemanticToken>),
+) -> String {
+    let mut builder = semantic_tokens::StreamingSemanticTokensBuilder::new(
+        next_token_result_id(),
+        chunk_size,
+        sink,
+    );
+    push_semantic_tokens(text, line_index, highlights, |range, ty, mods| {
+        builder.push_typed(range, ty, mods)
+    });
+    builder.finish()
+}
+
+fn next_token_result_id() -> String {
+    TOKEN_RESULT_COUNTER
         .fetch_add(1, Ordering::SeqCst)
-        .to_string();
-    let mut builder = semantic_tokens::SemanticTokensBuilder::new(id);
+        .to_string()
+}
 
+fn push_semantic_tokens(
+    text: &str,
+    line_index: &LineIndex,
+    highlights: Vec<HlRange>,
+    mut push: impl FnMut(lsp_types::Range, lsp_types::SemanticTokenType, semantic_tokens::ModifierSet),
+) {
     for highlight_range in highlights {
         if highlight_range.highlight.is_empty() {
             continue;
         }
 
         let (ty, mods) = semantic_token_type_and_modifiers(highlight_range.highlight);
-        let token_index = semantic_tokens::type_index(ty);
-        let modifier_bitset = mods.0;
 
         for mut text_range in line_index.lines(highlight_range.range) {
             if text[text_range].ends_with('\n') {
@@ -563,20 +595,9 @@ pub(crate) fn semantic_tokens(
                     TextRange::new(text_range.start(), text_range.end() - TextSize::of('\n'));
             }
             let range = range(line_index, text_range);
-            builder.push(range, token_index, modifier_bitset);
+            push(range, ty.clone(), mods);
         }
     }
-
-    builder.build()
-}
-
-pub(crate) fn semantic_token_delta(
-    previous: &lsp_types::SemanticTokens,
-    current: &lsp_types::SemanticTokens,
-) -> lsp_types::SemanticTokensDelta {
-    let result_id = current.result_id.clone();
-    let edits = semantic_tokens::diff_tokens(&previous.data, &current.data);
-    lsp_types::SemanticTokensDelta { result_id, edits }
 }
 
 fn semantic_token_type_and_modifiers(
@@ -595,6 +616,8 @@ fn semantic_token_type_and_modifiers(
             SymbolKind::Variable => semantic_tokens::VARIABLE,
             SymbolKind::Callback => semantic_tokens::FUNCTION,
         },
+        HlTag::QuotedAtom => semantic_tokens::STRING,
+        HlTag::Operator => semantic_tokens::OPERATOR,
         HlTag::None => semantic_tokens::GENERIC,
     };
 
@@ -605,6 +628,7 @@ fn semantic_token_type_and_modifiers(
             HlMod::ExportedType => semantic_tokens::EXPORTED_TYPE,
             HlMod::DeprecatedFunction => semantic_tokens::DEPRECATED_FUNCTION,
             HlMod::TypeDynamic => semantic_tokens::TYPE_DYNAMIC,
+            HlMod::Definition => semantic_tokens::DEFINITION,
         };
         mods |= modifier;
     }
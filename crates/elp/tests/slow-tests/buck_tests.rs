@@ -85,6 +85,7 @@ mod tests {
         let project = Project::load(
             &buck_config,
             elp_config.eqwalizer,
+            elp_config.compile,
             &BUCK_QUERY_CONFIG,
             &|_| {},
         )
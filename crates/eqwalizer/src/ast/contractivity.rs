@@ -39,6 +39,7 @@
 //! Algorithm design by @ilyaklyuchnikov, see D30779530 for more information.
 
 use std::collections::BTreeMap;
+use std::fmt;
 use std::iter;
 use std::sync::Arc;
 
@@ -56,6 +57,7 @@ use fxhash::FxHashMap;
 use fxhash::FxHashSet;
 
 use super::Id;
+use super::Pos;
 use super::RemoteId;
 use super::stub::ModuleStub;
 use super::stub::VStub;
@@ -160,6 +162,20 @@ fn he_prop(s: &Prop, t: &Prop) -> bool {
     s.req == t.req && is_he(&s.tp, &t.tp)
 }
 
+/// A type declaration rejected for not being contractive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractivityError {
+    pub id: Id,
+    /// Position of the declaration
+    pub pos: Pos,
+}
+
+impl fmt::Display for ContractivityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "type {} is not contractive", self.id)
+    }
+}
+
 pub struct StubContractivityChecker<'d> {
     db: &'d dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
@@ -198,6 +214,10 @@ impl StubContractivityChecker<'_> {
         } else {
             stub.invalid_ids.insert(t.id.clone());
             stub.invalids.push(self.to_invalid(t));
+            stub.contractivity_errors.push(ContractivityError {
+                id: t.id.clone(),
+                pos: t.pos.clone(),
+            });
         }
     }
 
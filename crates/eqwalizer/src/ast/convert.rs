@@ -388,6 +388,7 @@ impl Converter {
                         id,
                         params,
                         body,
+                        opaque: false,
                     })));
                 }
             }
@@ -408,6 +409,7 @@ impl Converter {
                         id,
                         params,
                         body,
+                        opaque: true,
                     })));
                 }
             }
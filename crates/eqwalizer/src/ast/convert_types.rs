@@ -187,6 +187,7 @@ impl TypeConverter {
             params,
             body,
             pos: decl.pos,
+            opaque: decl.opaque,
         })
     }
 
@@ -70,14 +70,77 @@ use crate::ast;
 use crate::ast::Visibility;
 use crate::db::EqwalizerDiagnosticsDatabase;
 
+/// Counts of the work done by `StubExpander`, to explain why some
+/// modules are slow to check.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpansionStats {
+    /// Type declarations, records, specs and callbacks expanded.
+    pub forms_expanded: usize,
+    /// Type ids resolved to remote ids.
+    pub types_resolved: usize,
+    /// Type ids resolved by looking up the types of another module.
+    pub remote_refs_followed: usize,
+}
+
 struct Expander<'d> {
     module: StringId,
     project_id: ProjectId,
     invalids: Vec<Invalid>,
     db: &'d dyn EqwalizerDiagnosticsDatabase,
+    depth: usize,
+    max_depth: usize,
+    /// Set when expansion was abandoned for nesting deeper than
+    /// `max_depth`, the `Invalid` returned in this case is a placeholder.
+    too_deep: bool,
+    /// Only collected when requested, see `StubExpander::with_stats`.
+    stats: Option<ExpansionStats>,
 }
 
 impl Expander<'_> {
+    fn new(
+        db: &dyn EqwalizerDiagnosticsDatabase,
+        project_id: ProjectId,
+        module: StringId,
+    ) -> Expander<'_> {
+        Expander {
+            module,
+            invalids: vec![],
+            db,
+            project_id,
+            depth: 0,
+            max_depth: db
+                .module_eqwalizer_config(project_id, ModuleName::new(module.as_str()))
+                .max_type_expansion_depth,
+            too_deep: false,
+            stats: None,
+        }
+    }
+
+    fn enter(&mut self, t: &ExtType) -> Result<(), Invalid> {
+        if self.depth >= self.max_depth {
+            self.too_deep = true;
+            return Err(Invalid::UnknownId(UnknownId {
+                pos: t.pos().clone(),
+                id: RemoteId {
+                    module: self.module,
+                    name: StringId::from(""),
+                    arity: 0,
+                },
+            }));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Turns an abandoned expansion into an error naming `name`.
+    fn check_depth(&mut self, name: impl FnOnce() -> String) -> Result<(), TypeConversionError> {
+        if self.too_deep {
+            self.too_deep = false;
+            return Err(TypeConversionError::ExpansionTooDeep(name()));
+        }
+        Ok(())
+    }
+
     fn expand_fun_spec(&mut self, fun_spec: ExternalFunSpec) -> Result<ExternalFunSpec, Invalid> {
         let types = self.expand_cfts(fun_spec.types)?;
         Ok(ExternalFunSpec { types, ..fun_spec })
@@ -224,8 +287,18 @@ impl Expander<'_> {
     }
 
     fn expand_type(&mut self, t: ExtType) -> Result<ExtType, Invalid> {
+        self.enter(&t)?;
+        let result = self.expand_type_inner(t);
+        self.depth -= 1;
+        result
+    }
+
+    fn expand_type_inner(&mut self, t: ExtType) -> Result<ExtType, Invalid> {
         match t {
             ExtType::LocalExtType(ty) => {
+                if let Some(stats) = &mut self.stats {
+                    stats.types_resolved += 1;
+                }
                 let id = RemoteId {
                     module: self.module,
                     name: ty.id.name,
@@ -243,6 +316,12 @@ impl Expander<'_> {
                     name: ty.id.name,
                     arity: ty.id.arity,
                 };
+                if let Some(stats) = &mut self.stats {
+                    stats.types_resolved += 1;
+                    if ty.id.module != self.module {
+                        stats.remote_refs_followed += 1;
+                    }
+                }
                 let module = ModuleName::new(&ty.id.module);
                 if let Some(visibility) = self
                     .db
@@ -396,6 +475,18 @@ impl Expander<'_> {
         t: ExtType,
         sub: &FxHashMap<StringId, ExtType>,
         stack: &FxHashSet<StringId>,
+    ) -> Result<ExtType, Invalid> {
+        self.enter(&t)?;
+        let result = self.expand_constraints_inner(t, sub, stack);
+        self.depth -= 1;
+        result
+    }
+
+    fn expand_constraints_inner(
+        &mut self,
+        t: ExtType,
+        sub: &FxHashMap<StringId, ExtType>,
+        stack: &FxHashSet<StringId>,
     ) -> Result<ExtType, Invalid> {
         match t {
             ExtType::LocalExtType(ty) => {
@@ -564,12 +655,7 @@ impl StubExpander<'_> {
         module: StringId,
         ast: &AST,
     ) -> StubExpander<'d> {
-        let expander = Expander {
-            module,
-            invalids: vec![],
-            db,
-            project_id,
-        };
+        let expander = Expander::new(db, project_id, module);
         let type_converter = TypeConverter::new(module);
         let stub = ModuleStub {
             module,
@@ -594,7 +680,10 @@ impl StubExpander<'_> {
     }
 
     fn add_type_decl(&mut self, t: ExternalTypeDecl) -> Result<(), TypeConversionError> {
-        match self.expander.expand_type_decl(t) {
+        let id = t.id.clone();
+        let expanded = self.expander.expand_type_decl(t);
+        self.expander.check_depth(|| format!("type {id}"))?;
+        match expanded {
             Ok(decl) => {
                 let decl = self.type_converter.convert_type_decl(decl)?;
                 self.stub.types.insert(decl.id.clone(), Arc::new(decl));
@@ -609,7 +698,10 @@ impl StubExpander<'_> {
     }
 
     fn add_record_decl(&mut self, t: ExternalRecDecl) -> Result<(), TypeConversionError> {
-        match self.expander.expand_rec_decl(t) {
+        let name = t.name;
+        let expanded = self.expander.expand_rec_decl(t);
+        self.expander.check_depth(|| format!("record #{name}"))?;
+        match expanded {
             Ok(decl) => match self.type_converter.convert_rec_decl(decl)? {
                 Ok(decl) => {
                     self.stub.records.insert(decl.name, Arc::new(decl));
@@ -630,7 +722,10 @@ impl StubExpander<'_> {
     }
 
     fn add_spec(&mut self, t: ExternalFunSpec) -> Result<(), TypeConversionError> {
-        match self.expander.expand_fun_spec(t) {
+        let id = t.id.clone();
+        let expanded = self.expander.expand_fun_spec(t);
+        self.expander.check_depth(|| format!("spec {id}"))?;
+        match expanded {
             Ok(decl) => {
                 if decl.types.len() == 1 {
                     let spec = self.type_converter.convert_spec(decl)?;
@@ -668,16 +763,38 @@ impl StubExpander<'_> {
                     id: id.clone(),
                     params: vec![],
                     body,
+                    opaque: false,
                 };
                 self.stub.types.insert(id, Arc::new(decl));
             })
         }
     }
 
+    /// Collect `ExpansionStats` while expanding.
+    pub fn with_stats(mut self) -> Self {
+        self.expander.stats = Some(ExpansionStats::default());
+        self
+    }
+
+    pub fn stats(&self) -> Option<&ExpansionStats> {
+        self.expander.stats.as_ref()
+    }
+
     pub fn expand(&mut self, forms: &[ExternalForm]) -> Result<(), TypeConversionError> {
         let mut callbacks: Vec<Callback> = vec![];
         let mut optional_callbacks: BTreeSet<Id> = BTreeSet::default();
         for form in forms {
+            if let Some(stats) = &mut self.expander.stats {
+                if matches!(
+                    form,
+                    ExternalForm::ExternalTypeDecl(_)
+                        | ExternalForm::ExternalFunSpec(_)
+                        | ExternalForm::ExternalRecDecl(_)
+                        | ExternalForm::ExternalCallback(_)
+                ) {
+                    stats.forms_expanded += 1;
+                }
+            }
             match form {
                 ExternalForm::File(f) => {
                     self.current_file = f.file;
@@ -700,7 +817,10 @@ impl StubExpander<'_> {
                 ExternalForm::ExternalFunSpec(s) => self.add_spec(s.clone())?,
                 ExternalForm::ExternalRecDecl(r) => self.add_record_decl(r.clone())?,
                 ExternalForm::ExternalCallback(cb) => {
-                    match self.expander.expand_callback(cb.clone()) {
+                    let expanded = self.expander.expand_callback(cb.clone());
+                    self.expander
+                        .check_depth(|| format!("callback {}", cb.id))?;
+                    match expanded {
                         Ok(cb) => {
                             let cb = self.type_converter.convert_callback(cb)?;
                             callbacks.push(cb);
@@ -740,12 +860,7 @@ impl CastExpander<'_> {
         project_id: ProjectId,
         module: StringId,
     ) -> CastExpander<'_> {
-        let expander = Expander {
-            module,
-            invalids: vec![],
-            db,
-            project_id,
-        };
+        let expander = Expander::new(db, project_id, module);
         let type_converter = TypeConverter::new(module);
         CastExpander {
             expander,
@@ -767,6 +882,8 @@ impl CastExpander<'_> {
 
     pub fn expand(&mut self, ty: ExtType) -> Result<Result<Type, Invalid>, TypeConversionError> {
         let result = self.expander.expand_type(ty);
+        self.expander
+            .check_depth(|| "type in type cast".to_string())?;
         if let Some(invalid) = self.expander.invalids.first() {
             return Ok(Err(invalid.to_owned()));
         }
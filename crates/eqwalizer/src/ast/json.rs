@@ -0,0 +1,84 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! The JSON encoding of Erlang ASTs accepted by `from_json`.
+//!
+//! A document is a JSON array of forms in the Erlang abstract format,
+//! i.e. what `epp:parse_file/2` returns, so that it goes through the
+//! same conversion as the ETF produced by the Erlang service. Terms are
+//! encoded as follows:
+//!
+//! | Erlang          | JSON                             |
+//! |-----------------|----------------------------------|
+//! | atom `foo`      | `"foo"`                          |
+//! | integer `42`    | `42`                             |
+//! | float `1.5`     | `1.5`                            |
+//! | proper list     | `[...]`                          |
+//! | tuple `{a, 1}`  | `{"tuple": ["a", 1]}`            |
+//! | string `"a.erl"`| `{"string": "a.erl"}`            |
+//!
+//! For example, the forms of `-module(m).` compiled from `m.erl` are
+//!
+//! ```json
+//! [
+//!   {"tuple": ["attribute", 1, "file", {"tuple": [{"string": "m.erl"}, 1]}]},
+//!   {"tuple": ["attribute", 1, "module", "m"]}
+//! ]
+//! ```
+//!
+//! Integers must fit in 64 bits; other terms, such as binaries, maps or
+//! improper lists, don't occur in the forms eqWAlizer converts.
+
+use eetf::Term;
+use serde_json::Value;
+
+/// The Erlang term encoded by `value`, or a description of where it
+/// doesn't follow the encoding.
+pub fn to_term(value: &Value) -> Result<Term, String> {
+    match value {
+        Value::String(name) => Ok(eetf::Atom::from(name.as_str()).into()),
+        Value::Number(number) => {
+            if let Some(value) = number.as_i64() {
+                match i32::try_from(value) {
+                    Ok(value) => Ok(eetf::FixInteger::from(value).into()),
+                    Err(_) => Ok(eetf::BigInteger::from(value).into()),
+                }
+            } else if let (true, Some(value)) = (number.is_f64(), number.as_f64()) {
+                Ok(eetf::Float { value }.into())
+            } else {
+                Err(format!("integer {number} out of range"))
+            }
+        }
+        Value::Array(elements) => {
+            let elements = elements
+                .iter()
+                .map(to_term)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(eetf::List::from(elements).into())
+        }
+        Value::Object(object) => match object.iter().next() {
+            Some((tag, Value::Array(elements))) if object.len() == 1 && tag == "tuple" => {
+                let elements = elements
+                    .iter()
+                    .map(to_term)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(eetf::Tuple::from(elements).into())
+            }
+            Some((tag, Value::String(string))) if object.len() == 1 && tag == "string" => {
+                Ok(eetf::ByteList {
+                    bytes: string.as_bytes().to_vec(),
+                }
+                .into())
+            }
+            _ => Err(format!("unexpected object {value}")),
+        },
+        Value::Bool(_) | Value::Null => Err(format!("unexpected {value}")),
+    }
+}
@@ -31,11 +31,14 @@ pub mod contractivity;
 pub mod convert;
 pub mod convert_types;
 pub mod expand;
+pub mod json;
 pub mod preprocess;
 pub mod stub;
 pub mod subst;
 pub mod trans_valid;
 
+mod md5;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
     ParseError,
@@ -43,6 +46,8 @@ pub enum Error {
     ModuleNotFound(String),
     BEAMNotFound(PathBuf),
     InvalidBEAM,
+    StaleBEAM(PathBuf),
+    InvalidFragment(String),
     ConversionError(ConversionError),
     TypeConversionError(TypeConversionError),
 }
@@ -142,6 +147,8 @@ pub enum TypeConversionError {
     UnexpectedType,
     UnknownBuiltin(String, usize),
     UnexpectedShapeProp,
+    ExpansionTooDeep(String),
+    InvalidJson(String),
 }
 
 impl fmt::Display for TypeConversionError {
@@ -150,6 +157,12 @@ impl fmt::Display for TypeConversionError {
             TypeConversionError::UnknownBuiltin(name, arity) => {
                 format!("unknown builtin {name}/{arity}")
             }
+            TypeConversionError::ExpansionTooDeep(name) => {
+                format!("{name} is nested too deeply to expand")
+            }
+            TypeConversionError::InvalidJson(msg) => {
+                format!("invalid JSON AST: {msg}")
+            }
             err => format!("{err:?}"),
         };
         write!(f, "eqWAlizer stub expansion failed with {message}")
@@ -169,6 +182,27 @@ pub fn from_bytes(bytes: &Vec<u8>, filter_stub: bool) -> Result<AST, Error> {
     Err(Error::ConversionError(ConversionError::InvalidDecode))
 }
 
+/// Like `from_bytes`, for the abstract forms of a module encoded as
+/// described in the `json` module.
+pub fn from_json(bytes: &[u8], filter_stub: bool) -> Result<AST, Error> {
+    let invalid = |msg: String| Error::TypeConversionError(TypeConversionError::InvalidJson(msg));
+    let value: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|err| invalid(err.to_string()))?;
+    if !value.is_array() {
+        return Err(invalid("expected an array of forms".to_string()));
+    }
+    let forms = json::to_term(&value).map_err(invalid)?;
+    let converted_forms = convert::convert_forms(&forms, false, filter_stub)?;
+    let forms = &converted_forms.forms;
+    if !forms.iter().any(|f| matches!(f, ExternalForm::Module(_))) {
+        return Err(invalid("missing module attribute".to_string()));
+    }
+    if !forms.iter().any(|f| matches!(f, ExternalForm::File(_))) {
+        return Err(invalid("missing file attribute".to_string()));
+    }
+    Ok(preprocess::preprocess(converted_forms))
+}
+
 pub fn from_beam(bytes: &Vec<u8>) -> Result<AST, Error> {
     let mut cursor = Cursor::new(bytes);
     let mut buf: [u8; 4] = [0; 4];
@@ -186,7 +220,7 @@ pub fn from_beam(bytes: &Vec<u8>) -> Result<AST, Error> {
     if &buf != b"BEAM" {
         return Err(Error::InvalidBEAM);
     }
-    while (bytes.len() as u64) - cursor.position() > 8 {
+    while cursor.position() + 8 <= bytes.len() as u64 {
         cursor.read(&mut tag).map_err(|_| Error::InvalidBEAM)?;
         cursor.read(&mut buf).map_err(|_| Error::InvalidBEAM)?;
         let length = u32::from_be_bytes(buf);
@@ -205,6 +239,47 @@ pub fn from_beam(bytes: &Vec<u8>) -> Result<AST, Error> {
     Err(Error::InvalidBEAM)
 }
 
+/// Hash of a module source, as stored in the `ElpH` chunk of BEAM
+/// files by builds that want ELP to detect them going stale: the MD5
+/// digest of the source file bytes, i.e. `erlang:md5(Source)`, which
+/// can be added with the `{extra_chunks, [{<<"ElpH">>, Md5}]}` compiler
+/// option.
+pub fn source_hash(text: &str) -> [u8; 16] {
+    md5::digest(text.as_bytes())
+}
+
+/// The source hash recorded in the `ElpH` chunk of a BEAM file, if any.
+/// BEAM files cut short, e.g. while being written, are invalid.
+pub fn beam_source_hash(bytes: &[u8]) -> Result<Option<[u8; 16]>, Error> {
+    if bytes.len() < 12 || &bytes[0..4] != b"FOR1" || &bytes[8..12] != b"BEAM" {
+        return Err(Error::InvalidBEAM);
+    }
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let tag = &bytes[pos..pos + 4];
+        let length = u32::from_be_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        pos += 8;
+        if tag == b"ElpH" {
+            let hash = bytes
+                .get(pos..pos + 16)
+                .filter(|_| length == 16)
+                .ok_or(Error::InvalidBEAM)?;
+            return Ok(Some(hash.try_into().unwrap()));
+        }
+        let end = pos
+            .checked_add(length)
+            .filter(|end| *end <= bytes.len())
+            .ok_or(Error::InvalidBEAM)?;
+        // Chunks are padded to a multiple of 4 bytes
+        pos = end.next_multiple_of(4);
+    }
+    if pos < bytes.len() {
+        // The header of a chunk was cut short
+        return Err(Error::InvalidBEAM);
+    }
+    Ok(None)
+}
+
 pub fn type_ids(ast: &AST) -> BTreeMap<Id, Visibility> {
     let mut type_ids = BTreeMap::default();
     for form in &ast.forms {
@@ -22,6 +22,7 @@ use elp_types_db::eqwalizer::invalid_diagnostics::Invalid;
 use serde::Serialize;
 
 use super::Id;
+use super::contractivity::ContractivityError;
 
 #[derive(Serialize, Debug, Clone, PartialEq, Eq, Default)]
 pub struct ModuleStub {
@@ -53,6 +54,7 @@ pub struct VStub {
     stub: Arc<ModuleStub>,
     pub invalids: Vec<Invalid>,
     pub invalid_ids: BTreeSet<Id>,
+    pub contractivity_errors: Vec<ContractivityError>,
 }
 
 impl VStub {
@@ -61,6 +63,7 @@ impl VStub {
             stub,
             invalids: vec![],
             invalid_ids: BTreeSet::default(),
+            contractivity_errors: vec![],
         }
     }
 
@@ -107,4 +110,28 @@ impl ModuleStub {
     pub fn to_bytes(&self) -> Vec<u8> {
         serde_json::to_vec(self).unwrap()
     }
+
+    /// The length of `to_bytes`, plus that of the serialized declarations
+    /// it leaves out, as an estimate of the memory used by the stub.
+    pub fn serialized_size(&self) -> usize {
+        fn len<T: Serialize + ?Sized>(value: &T) -> usize {
+            serde_json::to_vec(value).map_or(0, |bytes| bytes.len())
+        }
+        self.to_bytes().len()
+            + len(&self.export_types)
+            + self.types.values().map(|decl| len(&**decl)).sum::<usize>()
+            + self.specs.values().map(|spec| len(&**spec)).sum::<usize>()
+            + self
+                .overloaded_specs
+                .values()
+                .map(|spec| len(&**spec))
+                .sum::<usize>()
+            + self
+                .records
+                .values()
+                .map(|decl| len(&**decl))
+                .sum::<usize>()
+            + len(&*self.callbacks)
+            + len(&*self.optional_callbacks)
+    }
 }
@@ -327,9 +327,26 @@ impl TransitiveChecker<'_> {
         }
     }
 
+    /// Compute the stubs of the modules `v_stub` refers to in parallel,
+    /// so that the sequential checks below find them in the db. Only
+    /// these are fetched: the checks only follow the types they use, so
+    /// the stubs further down are left to them.
+    fn prefetch_dependencies(&self, v_stub: &VStub) {
+        let project_id = self.project_id;
+        let mut modules = BTreeSet::default();
+        referenced_modules(&mut modules, v_stub);
+        modules.remove(&self.module);
+        let modules: Vec<StringId> = modules.into_iter().collect();
+        salsa::par_map(self.db, modules, move |db, module| {
+            let _ = db.contractive_stub(project_id, ModuleName::new(module.as_str()));
+        })
+    }
+
     pub fn check(&mut self, v_stub: &VStub) -> ModuleStub {
         let mut stub_result = v_stub.into_normalized_stub();
 
+        self.prefetch_dependencies(v_stub);
+
         for decl in v_stub.types() {
             self.check_type_decl(&mut stub_result, decl)
         }
@@ -364,3 +381,41 @@ impl TransitiveChecker<'_> {
         Ok(ty)
     }
 }
+
+fn referenced_modules(modules: &mut BTreeSet<StringId>, v_stub: &VStub) {
+    for decl in v_stub.types() {
+        collect_modules(modules, &decl.body);
+    }
+    for decl in v_stub.records() {
+        for field in decl.fields.iter() {
+            collect_modules(modules, &field.tp);
+        }
+    }
+    let fun_types = v_stub
+        .specs()
+        .map(|spec| &spec.ty)
+        .chain(v_stub.overloaded_specs().flat_map(|spec| spec.tys.iter()));
+    for ft in fun_types {
+        collect_modules(modules, &ft.res_ty);
+        for arg in ft.arg_tys.iter() {
+            collect_modules(modules, arg);
+        }
+    }
+}
+
+fn collect_modules(modules: &mut BTreeSet<StringId>, ty: &Type) {
+    match ty {
+        Type::RemoteType(rt) => {
+            modules.insert(rt.id.module);
+            for arg in rt.arg_tys.iter() {
+                collect_modules(modules, arg);
+            }
+        }
+        ty => {
+            let _ = ty.walk::<()>(&mut |ty| {
+                collect_modules(modules, ty);
+                Ok(())
+            });
+        }
+    }
+}
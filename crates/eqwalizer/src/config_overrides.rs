@@ -0,0 +1,185 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Per-module overrides of `EqwalizerConfig`, given by a comment
+//! directive in the module source:
+//!
+//! ```erlang
+//! % eqwalizer:config #{enable_custom_overrides => false, max_type_expansion_depth => 100}
+//! ```
+//!
+//! Only the settings ELP applies itself while building stubs can be
+//! overridden:
+//! - `enable_custom_overrides` (`true` or `false`), which can only turn
+//!   the overrides off for a module when they are enabled globally
+//! - `max_type_expansion_depth` (a positive integer)
+//! - `stale_beam` (`ignore`, `fall_back_to_source` or `error`)
+//!
+//! The other settings are passed to the eqWAlizer process through its
+//! environment, and apply to all the modules it checks. Unknown settings
+//! and invalid values are ignored, and reported as diagnostics of the
+//! module.
+
+use elp_syntax::TextRange;
+use elp_syntax::TextSize;
+
+use crate::EqwalizerConfig;
+use crate::StaleBeam;
+
+const DIRECTIVE: &str = "eqwalizer:config";
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EqwalizerConfigOverrides {
+    pub enable_custom_overrides: Option<bool>,
+    pub max_type_expansion_depth: Option<usize>,
+    pub stale_beam: Option<StaleBeam>,
+}
+
+/// The `eqwalizer:config` directives of a module.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ConfigDirectives {
+    /// The overrides of all the directives, later ones taking
+    /// precedence, or `None` if there are none.
+    pub overrides: Option<EqwalizerConfigOverrides>,
+    /// The settings that were ignored.
+    pub invalid: Vec<InvalidSetting>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidSetting {
+    pub range: TextRange,
+    pub message: String,
+}
+
+impl ConfigDirectives {
+    /// Collect the directives among `comments`, given with their range
+    /// and their text, including the leading `%`.
+    pub fn from_comments<'a>(
+        comments: impl Iterator<Item = (TextRange, &'a str)>,
+    ) -> ConfigDirectives {
+        let mut directives = ConfigDirectives::default();
+        for (range, comment) in comments {
+            let body = comment.trim_start_matches('%').trim_start();
+            let Some(map) = body.strip_prefix(DIRECTIVE) else {
+                continue;
+            };
+            let overrides = directives.overrides.get_or_insert_with(Default::default);
+            let offset = comment.len() - map.len();
+            let Some(entries) = map
+                .trim()
+                .strip_prefix("#{")
+                .and_then(|map| map.strip_suffix('}'))
+            else {
+                directives.invalid.push(InvalidSetting {
+                    range,
+                    message: format!(
+                        "Malformed {DIRECTIVE} directive, expected a map such as #{{stale_beam => error}}"
+                    ),
+                });
+                continue;
+            };
+            let offset = offset + map.find("#{").unwrap_or(0) + 2;
+            for (start, entry) in split_entries(entries) {
+                let trimmed = entry.trim();
+                if trimmed.is_empty() {
+                    continue;
+                }
+                if let Err(message) = overrides.set(trimmed) {
+                    let start = offset + start + (entry.len() - entry.trim_start().len());
+                    let start = range.start() + TextSize::from(start as u32);
+                    directives.invalid.push(InvalidSetting {
+                        range: TextRange::at(start, TextSize::of(trimmed)),
+                        message,
+                    });
+                }
+            }
+        }
+        directives
+    }
+}
+
+/// Split the entries of a map on the commas that are not nested in
+/// brackets or quotes, returning each with its offset in `entries`.
+fn split_entries(entries: &str) -> Vec<(usize, &str)> {
+    let mut res = Vec::new();
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (idx, c) in entries.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '\'' | '"' => quote = Some(c),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    res.push((start, &entries[start..idx]));
+                    start = idx + 1;
+                }
+                _ => {}
+            },
+        }
+    }
+    res.push((start, &entries[start..]));
+    res
+}
+
+impl EqwalizerConfigOverrides {
+    fn set(&mut self, entry: &str) -> Result<(), String> {
+        let Some((key, value)) = entry.split_once("=>") else {
+            return Err(format!(
+                "Invalid {DIRECTIVE} setting {entry}, expected `key => value`"
+            ));
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let invalid_value =
+            |expected: &str| format!("Invalid value {value} for {key}, expected {expected}");
+        match key {
+            "enable_custom_overrides" => match value {
+                "true" => self.enable_custom_overrides = Some(true),
+                "false" => self.enable_custom_overrides = Some(false),
+                _ => return Err(invalid_value("true or false")),
+            },
+            "max_type_expansion_depth" => match value.parse() {
+                Ok(depth) if depth > 0 => self.max_type_expansion_depth = Some(depth),
+                _ => return Err(invalid_value("a positive integer")),
+            },
+            "stale_beam" => match value {
+                "ignore" => self.stale_beam = Some(StaleBeam::Ignore),
+                "fall_back_to_source" => self.stale_beam = Some(StaleBeam::FallBackToSource),
+                "error" => self.stale_beam = Some(StaleBeam::Error),
+                _ => return Err(invalid_value("ignore, fall_back_to_source or error")),
+            },
+            _ => {
+                return Err(format!(
+                    "Unknown {DIRECTIVE} setting {key}, expected one of enable_custom_overrides, max_type_expansion_depth or stale_beam"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, config: &EqwalizerConfig) -> EqwalizerConfig {
+        EqwalizerConfig {
+            enable_custom_overrides: self
+                .enable_custom_overrides
+                .unwrap_or(config.enable_custom_overrides),
+            max_type_expansion_depth: self
+                .max_type_expansion_depth
+                .unwrap_or(config.max_type_expansion_depth),
+            stale_beam: self.stale_beam.unwrap_or(config.stale_beam),
+            ..config.clone()
+        }
+    }
+}
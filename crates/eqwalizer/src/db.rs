@@ -10,16 +10,27 @@
 
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::collections::btree_map;
+use std::num::NonZeroUsize;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 use std::sync::LazyLock;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 use std::time::Instant;
 
 use elp_base_db::AbsPathBuf;
+use elp_base_db::AppData;
+use elp_base_db::AppDataId;
 use elp_base_db::AppType;
 use elp_base_db::FileId;
 use elp_base_db::ModuleName;
 use elp_base_db::ProjectId;
 use elp_base_db::RootQueryDb;
+use elp_syntax::AstNode;
+use elp_syntax::SyntaxKind;
+use elp_syntax::ast as syntax;
 use elp_types_db::StringId;
 use elp_types_db::eqwalizer::AST;
 use elp_types_db::eqwalizer::Id;
@@ -29,20 +40,30 @@ use elp_types_db::eqwalizer::form::FunSpec;
 use elp_types_db::eqwalizer::form::OverloadedFunSpec;
 use elp_types_db::eqwalizer::form::RecDecl;
 use elp_types_db::eqwalizer::form::TypeDecl;
+use fxhash::FxHashMap;
+use fxhash::FxHashSet;
 use parking_lot::Mutex;
+use salsa::Cancelled;
 
 use crate::EqwalizerConfig;
+use crate::EqwalizerConfigError;
+use crate::EqwalizerDiagnostic;
 use crate::EqwalizerDiagnostics;
+use crate::StaleBeam;
 use crate::ast;
 use crate::ast::Error;
 use crate::ast::Visibility;
+use crate::ast::contractivity::ContractivityError;
 use crate::ast::contractivity::StubContractivityChecker;
+use crate::ast::expand::ExpansionStats;
 use crate::ast::expand::StubExpander;
 use crate::ast::stub::ModuleStub;
 use crate::ast::stub::VStub;
 use crate::ast::trans_valid::TransitiveChecker;
+use crate::config_overrides::ConfigDirectives;
 use crate::get_module_diagnostics;
 use crate::ipc::IpcHandle;
+use crate::progress::EqwalizerProgress;
 
 pub trait EqwalizerErlASTStorage {
     fn erl_ast_bytes(
@@ -69,50 +90,222 @@ pub trait EqwalizerErlASTStorage {
     }
 }
 
+/// Set `eqwalizer_config`, once `EqwalizerConfig::validate` accepts
+/// it, so that misconfiguration is reported up front rather than by the
+/// queries using it.
+pub fn set_eqwalizer_config_validated(
+    db: &mut dyn EqwalizerDiagnosticsDatabase,
+    config: EqwalizerConfig,
+) -> Result<(), EqwalizerConfigError> {
+    config.validate()?;
+    db.set_eqwalizer_config(Arc::new(config));
+    refresh_ebin_beam_files(db);
+    Ok(())
+}
+
+/// Set `ebin_beam_files` from the directories `from_beam_path` looks
+/// in, so that finding the BEAM file of a module doesn't probe the file
+/// system from within a query. Needed again when the config or the apps
+/// change, or BEAM files are built or removed.
+pub fn refresh_ebin_beam_files(db: &mut dyn EqwalizerDiagnosticsDatabase) {
+    let config = db.eqwalizer_config();
+    let mut beam_files = FxHashSet::default();
+    // Otherwise only the `ebin` directory of OTP apps is used, where
+    // every module has a BEAM file
+    if !config.ebin_dirs.is_empty() || config.beam_for_all_apps {
+        let app_data_ids: BTreeSet<AppDataId> = db.app_index().map.values().copied().collect();
+        for app_data_id in app_data_ids {
+            let app_data = match db.app_data_by_id(app_data_id).app_data(db) {
+                Some(app_data) => app_data,
+                None => continue,
+            };
+            for ebin in ebin_dirs(&app_data, &config) {
+                let entries = match std::fs::read_dir(&ebin) {
+                    Ok(entries) => entries,
+                    Err(_) => continue,
+                };
+                for entry in entries.flatten() {
+                    if let Some(name) = entry.file_name().to_str() {
+                        if name.ends_with(".beam") {
+                            beam_files.insert(ebin.join(name));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if beam_files != *db.ebin_beam_files() {
+        db.set_ebin_beam_files(Arc::new(beam_files));
+    }
+}
+
 pub trait ELPDbApi {
     fn eqwalizing_start(&self, module: String);
     fn eqwalizing_done(&self, module: String);
+    /// Report aggregate progress of a project-wide run to `progress`
+    /// as modules complete, until reset with `None`.
+    fn set_eqwalizing_progress(&self, progress: Option<Arc<EqwalizerProgress>>);
     fn set_module_ipc_handle(&self, module: ModuleName, handle: Option<Arc<Mutex<IpcHandle>>>);
     fn module_ipc_handle(&self, module: ModuleName) -> Option<Arc<Mutex<IpcHandle>>>;
+    /// Whether `module` is marked as emitted by a code generator.
+    fn is_generated_module(&self, project_id: ProjectId, module: ModuleName) -> bool;
+    /// Check `module` from scratch, starting eqWAlizer as needed, as
+    /// `module_diagnostics_deadline` does in the background.
+    fn eqwalize_module(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Arc<EqwalizerDiagnostics>;
+    /// The callers of `module_diagnostics_deadline` waiting on a module
+    /// checked in the background, shared by all snapshots.
+    fn deadline_waiters(&self) -> Arc<DeadlineWaiters>;
 }
 
+/// See `ELPDbApi::deadline_waiters`. A module has an entry for as long
+/// as it is checked in the background, so that it is only checked once
+/// however many callers ask for it.
+pub type DeadlineWaiters =
+    Mutex<FxHashMap<(ProjectId, ModuleName), Vec<mpsc::Sender<Arc<EqwalizerDiagnostics>>>>>;
+
 #[ra_ap_query_group_macro::query_group]
 pub trait EqwalizerDiagnosticsDatabase: EqwalizerErlASTStorage + RootQueryDb + ELPDbApi {
     #[salsa::input]
     fn eqwalizer_config(&self) -> Arc<EqwalizerConfig>;
 
+    /// The BEAM files in the ebin directories of the apps, as found by
+    /// `refresh_ebin_beam_files`.
+    #[salsa::input]
+    fn ebin_beam_files(&self) -> Arc<FxHashSet<AbsPathBuf>>;
+
+    /// The `% eqwalizer:config` directives in the comments of a file.
+    /// It only changes when the directives do, so that editing the rest
+    /// of the file doesn't invalidate `module_eqwalizer_config`.
+    fn config_directives(&self, file_id: FileId) -> Arc<ConfigDirectives>;
+
+    /// `eqwalizer_config`, with the overrides given by the
+    /// `% eqwalizer:config` directive of `module`, if any.
+    fn module_eqwalizer_config(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Arc<EqwalizerConfig>;
+
+    /// Whether `module` is never eqWAlized, see
+    /// `EqwalizerConfig::skip_modules`.
+    fn is_module_skipped(&self, module: ModuleName) -> bool;
+
     fn module_diagnostics(
         &self,
         project_id: ProjectId,
         module: String,
     ) -> (Arc<EqwalizerDiagnostics>, Instant);
 
+    /// The subset of `modules` with eqWAlizer errors.
+    fn failing_modules(
+        &self,
+        project_id: ProjectId,
+        modules: Vec<ModuleName>,
+    ) -> Arc<Vec<ModuleName>>;
+
     fn converted_stub(&self, project_id: ProjectId, module: ModuleName) -> Result<Arc<AST>, Error>;
 
+    /// The module named after the file whose `-module` attribute is
+    /// `module`, when they differ, see
+    /// `EqwalizerConfig::infer_module_from_file_stem`.
+    fn module_by_declared_name(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Option<ModuleName>;
+
+    /// The modules of the project whose `-module` attribute differs from
+    /// their file name, by declared name. Used by `module_by_declared_name`
+    /// so that the files are only parsed once per project.
+    fn declared_modules(&self, project_id: ProjectId) -> Arc<BTreeMap<ModuleName, ModuleName>>;
+
+    fn converted_stub_bytes(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Result<Arc<Vec<u8>>, Error>;
+
     fn type_ids(
         &self,
         project_id: ProjectId,
         module: ModuleName,
     ) -> Result<Arc<BTreeMap<Id, Visibility>>, Error>;
 
+    /// The functions listed in the `-export` attributes of `module`.
+    fn exported_functions(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Result<Arc<BTreeSet<Id>>, Error>;
+
+    /// The types listed in the `-export_type` attributes of `module`.
+    fn exported_types(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Result<Arc<BTreeSet<Id>>, Error>;
+
+    /// The behaviours declared by `module`, in declaration order.
+    fn declared_behaviours(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Result<Arc<Vec<ModuleName>>, Error>;
+
+    /// The project modules declaring `behaviour`, sorted by name. Modules
+    /// that can't be converted are skipped.
+    fn behaviour_implementors(
+        &self,
+        project_id: ProjectId,
+        behaviour: ModuleName,
+    ) -> Arc<Vec<ModuleName>>;
+
+    /// Project modules defined by more than one file, with those files
+    /// sorted. `file_for_module` picks one of them arbitrarily.
+    fn duplicate_modules(&self, project_id: ProjectId) -> Arc<BTreeMap<ModuleName, Vec<FileId>>>;
+
     fn expanded_stub(
         &self,
         project_id: ProjectId,
         module: ModuleName,
     ) -> Result<Arc<ModuleStub>, Error>;
 
+    fn stub_expansion_stats(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Result<Arc<ExpansionStats>, Error>;
+
     fn contractive_stub(
         &self,
         project_id: ProjectId,
         module: ModuleName,
     ) -> Result<Arc<VStub>, Error>;
 
+    fn contractivity_errors(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Result<Arc<Vec<ContractivityError>>, Error>;
+
     fn transitive_stub(
         &self,
         project_id: ProjectId,
         module: ModuleName,
     ) -> Result<Arc<ModuleStub>, Error>;
 
+    /// An estimate of the memory used by the stub of `module`, see
+    /// `ModuleStub::serialized_size`.
+    fn transitive_stub_size(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Result<usize, Error>;
+
     fn transitive_stub_bytes(
         &self,
         project_id: ProjectId,
@@ -138,6 +331,23 @@ pub trait EqwalizerDiagnosticsDatabase: EqwalizerErlASTStorage + RootQueryDb + E
         id: Id,
     ) -> Result<Option<Arc<Vec<u8>>>, Error>;
 
+    /// Whether `id` is a type of `module` declared with `-opaque`, false
+    /// if there is no such type.
+    fn is_opaque_type(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+        id: Id,
+    ) -> Result<bool, Error>;
+
+    /// The bytes of every type declaration of `module`, as given by
+    /// `type_decl_bytes` for each of them, serialized in one go.
+    fn all_type_decl_bytes(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Result<Arc<BTreeMap<Id, Vec<u8>>>, Error>;
+
     fn rec_decl(
         &self,
         project_id: ProjectId,
@@ -180,6 +390,14 @@ pub trait EqwalizerDiagnosticsDatabase: EqwalizerErlASTStorage + RootQueryDb + E
         id: Id,
     ) -> Result<Option<Arc<Vec<u8>>>, Error>;
 
+    /// The functions of `module` with a spec, overloaded or not, i.e.
+    /// those `fun_spec` or `overloaded_fun_spec` return one for.
+    fn specced_functions(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Result<Arc<BTreeSet<Id>>, Error>;
+
     fn custom_fun_specs(
         &self,
         project_id: ProjectId,
@@ -196,6 +414,13 @@ pub trait EqwalizerDiagnosticsDatabase: EqwalizerErlASTStorage + RootQueryDb + E
         module: ModuleName,
     ) -> Result<(Arc<Vec<Callback>>, Arc<BTreeSet<Id>>), Error>;
 
+    /// The optional callbacks of `module`, without its callbacks.
+    fn optional_callbacks(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Result<Arc<BTreeSet<Id>>, Error>;
+
     fn callbacks_bytes(
         &self,
         project_id: ProjectId,
@@ -203,6 +428,70 @@ pub trait EqwalizerDiagnosticsDatabase: EqwalizerErlASTStorage + RootQueryDb + E
     ) -> Result<Option<Arc<Vec<u8>>>, Error>;
 }
 
+fn module_eqwalizer_config(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Arc<EqwalizerConfig> {
+    let config = db.eqwalizer_config();
+    let Some(file_id) = db.module_index(project_id).file_for_module(&module) else {
+        return config;
+    };
+    match &db.config_directives(file_id).overrides {
+        Some(overrides) => Arc::new(overrides.apply(&config)),
+        None => config,
+    }
+}
+
+fn config_directives(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    file_id: FileId,
+) -> Arc<ConfigDirectives> {
+    let source_file = db.parse(file_id).tree();
+    let comments = source_file
+        .syntax()
+        .descendants_with_tokens()
+        .filter_map(|elem| elem.into_token())
+        .filter(|token| token.kind() == SyntaxKind::COMMENT)
+        .collect::<Vec<_>>();
+    Arc::new(ConfigDirectives::from_comments(
+        comments
+            .iter()
+            .map(|token| (token.text_range(), token.text())),
+    ))
+}
+
+const INVALID_CONFIG_DIRECTIVE: &str = "invalid_config_directive";
+
+/// The `eqwalizer:config` settings of `module` that were ignored, as
+/// diagnostics.
+fn invalid_config_diagnostics(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: &ModuleName,
+) -> Vec<EqwalizerDiagnostic> {
+    let Some(file_id) = db.module_index(project_id).file_for_module(module) else {
+        return vec![];
+    };
+    db.config_directives(file_id)
+        .invalid
+        .iter()
+        .map(|invalid| EqwalizerDiagnostic {
+            range: invalid.range,
+            message: invalid.message.clone(),
+            uri: format!("https://fb.me/eqwalizer_errors#{INVALID_CONFIG_DIRECTIVE}"),
+            code: INVALID_CONFIG_DIRECTIVE.to_string(),
+            expression: None,
+            explanation: None,
+            diagnostic: None,
+        })
+        .collect()
+}
+
+fn is_module_skipped(db: &dyn EqwalizerDiagnosticsDatabase, module: ModuleName) -> bool {
+    db.eqwalizer_config().skips_module(module.as_str())
+}
+
 fn module_diagnostics(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
@@ -216,8 +505,34 @@ fn module_diagnostics(
     // Ideally, the config should be passed per module to eqWAlizer instead
     // of being set in the command's environment
     let _ = db.eqwalizer_config();
+    let module_name = ModuleName::new(&module);
+    if db.is_module_skipped(module_name.clone()) {
+        return (Arc::new(EqwalizerDiagnostics::default()), timestamp);
+    }
+    let invalid_config = invalid_config_diagnostics(db, project_id, &module_name);
+    let add_invalid_config = |mut diag: EqwalizerDiagnostics| {
+        if let (EqwalizerDiagnostics::Diagnostics { errors, .. }, false) =
+            (&mut diag, invalid_config.is_empty())
+        {
+            errors
+                .entry(module.clone())
+                .or_default()
+                .extend(invalid_config.iter().cloned());
+        }
+        Arc::new(diag)
+    };
+    if db
+        .module_eqwalizer_config(project_id, module_name.clone())
+        .skip_generated
+        && db.is_generated_module(project_id, module_name)
+    {
+        return (
+            add_invalid_config(EqwalizerDiagnostics::default()),
+            timestamp,
+        );
+    }
     match get_module_diagnostics(db, project_id, module.clone()) {
-        Ok(diag) => (Arc::new(diag), timestamp),
+        Ok(diag) => (add_invalid_config(diag), timestamp),
         Err(err) => (
             Arc::new(EqwalizerDiagnostics::Error(format!(
                 "eqWAlizing module {module}:\n{err}"
@@ -227,6 +542,149 @@ fn module_diagnostics(
     }
 }
 
+fn failing_modules(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    modules: Vec<ModuleName>,
+) -> Arc<Vec<ModuleName>> {
+    let failing = modules
+        .into_iter()
+        .filter(|module| {
+            let (diagnostics, _) = db.module_diagnostics(project_id, module.as_str().to_string());
+            match &*diagnostics {
+                EqwalizerDiagnostics::Diagnostics { errors, .. } => {
+                    errors.values().any(|errors| !errors.is_empty())
+                }
+                EqwalizerDiagnostics::Error(_) => true,
+                // Missing ASTs are reported by the parse diagnostics
+                EqwalizerDiagnostics::NoAst { .. } => false,
+            }
+        })
+        .collect();
+    Arc::new(failing)
+}
+
+/// Result of [`module_diagnostics_deadline`].
+#[derive(Debug, Clone)]
+pub enum DeadlineDiagnostics {
+    Ready(Arc<EqwalizerDiagnostics>),
+    /// The diagnostics are still being computed in the background.
+    Pending,
+}
+
+/// How long we wait for a memoized result when the deadline has
+/// already passed. Fetching a memo never takes this long.
+const MEMO_LOOKUP_GRACE: Duration = Duration::from_millis(5);
+
+/// Like `module_diagnostics`, but gives up waiting at `deadline`.
+///
+/// The module is checked with `ELPDbApi::eqwalize_module` on a snapshot
+/// of `db` in a background thread, so a memoized result comes back
+/// straight away, while a check that would exceed the deadline yields
+/// `Pending` and keeps running. Its result is memoized by Salsa, and
+/// picked up by a later call. Callers asking for a module already being
+/// checked wait on that check rather than starting another.
+pub fn module_diagnostics_deadline<DB>(
+    db: &DB,
+    project_id: ProjectId,
+    module: String,
+    deadline: Instant,
+) -> DeadlineDiagnostics
+where
+    DB: EqwalizerDiagnosticsDatabase + Clone + Send + 'static,
+{
+    let module = ModuleName::new(&module);
+    diagnostics_deadline(db, project_id, module.clone(), deadline, move |db| {
+        db.eqwalize_module(project_id, module)
+    })
+}
+
+/// `module_diagnostics_deadline`, with `check` computing the
+/// diagnostics of `module` in the background.
+pub fn diagnostics_deadline<DB>(
+    db: &DB,
+    project_id: ProjectId,
+    module: ModuleName,
+    deadline: Instant,
+    check: impl FnOnce(&DB) -> Arc<EqwalizerDiagnostics> + Send + 'static,
+) -> DeadlineDiagnostics
+where
+    DB: EqwalizerDiagnosticsDatabase + Clone + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    let waiters = db.deadline_waiters();
+    let key = (project_id, module);
+    let in_flight = {
+        let mut waiters = waiters.lock();
+        let senders = waiters.entry(key.clone()).or_default();
+        senders.push(sender);
+        senders.len() > 1
+    };
+    if !in_flight {
+        let snapshot = AssertUnwindSafe(db.clone());
+        thread::spawn(move || {
+            let snapshot = snapshot;
+            let result = Cancelled::catch(AssertUnwindSafe(|| check(&snapshot.0)));
+            // While the snapshot is alive, so that no write, and no
+            // caller in a later revision, can come in between.
+            let senders = waiters.lock().remove(&key).unwrap_or_default();
+            // A pending write cancels the snapshot, in which case there
+            // is no one left interested in the result.
+            if let Ok(diagnostics) = result {
+                for sender in senders {
+                    let _ = sender.send(diagnostics.clone());
+                }
+            }
+        });
+    }
+    let timeout = deadline
+        .saturating_duration_since(Instant::now())
+        .max(MEMO_LOOKUP_GRACE);
+    match receiver.recv_timeout(timeout) {
+        Ok(diagnostics) => DeadlineDiagnostics::Ready(diagnostics),
+        Err(_) => DeadlineDiagnostics::Pending,
+    }
+}
+
+/// Read `erl_ast_bytes` of `modules` in parallel, on snapshots of `db`,
+/// so that converting their stubs afterwards doesn't wait for the
+/// Erlang service one module at a time. Returns what was read, which
+/// later reads in the same revision share. Modules not read before a
+/// pending write cancels the snapshots are left out.
+pub fn prefetch_erl_ast<DB>(
+    db: &DB,
+    project_id: ProjectId,
+    modules: &[ModuleName],
+) -> BTreeMap<ModuleName, Result<Arc<Vec<u8>>, Error>>
+where
+    DB: EqwalizerDiagnosticsDatabase + Clone + Send,
+{
+    let workers = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(modules.len());
+    if workers == 0 {
+        return BTreeMap::new();
+    }
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|s| {
+        for chunk in modules.chunks(modules.len().div_ceil(workers)) {
+            let snapshot = AssertUnwindSafe(db.clone());
+            let sender = sender.clone();
+            s.spawn(move || {
+                let snapshot = snapshot;
+                let _ = Cancelled::catch(|| {
+                    for module in chunk {
+                        let bytes = snapshot.erl_ast_bytes(project_id, module.clone());
+                        let _ = sender.send((module.clone(), bytes));
+                    }
+                });
+            });
+        }
+    });
+    drop(sender);
+    receiver.into_iter().collect()
+}
+
 fn is_non_stub_form(form: &&ExternalForm) -> bool {
     match form {
         ExternalForm::Module(_) => true,
@@ -245,35 +703,186 @@ fn converted_stub(
     project_id: ProjectId,
     module: ModuleName,
 ) -> Result<Arc<AST>, Error> {
-    if let Some(file_id) = db.module_index(project_id).file_for_module(&module) {
-        if let Some(beam_path) = from_beam_path(db, file_id, &module) {
-            if let Ok(beam_contents) = std::fs::read(&beam_path) {
-                ast::from_beam(&beam_contents).map(Arc::new)
-            } else {
-                Err(Error::BEAMNotFound(beam_path.into()))
+    let module_index = db.module_index(project_id);
+    if module_index.file_for_module(&module).is_none()
+        && db.eqwalizer_config().infer_module_from_file_stem
+    {
+        if let Some(stem) = db.module_by_declared_name(project_id, module.clone()) {
+            log::warn!(
+                "Module {} is declared in {}.erl, using the file name as module name",
+                module.as_str(),
+                stem.as_str()
+            );
+            return db.converted_stub(project_id, stem);
+        }
+    }
+    if let Some(file_id) = module_index.file_for_module(&module) {
+        match stub_file(db, file_id, &module) {
+            Some(StubFile::Beam(beam_path)) => {
+                if let Ok(beam_contents) = std::fs::read(&beam_path) {
+                    let stale_beam = db
+                        .module_eqwalizer_config(project_id, module.clone())
+                        .stale_beam;
+                    if stale_beam != StaleBeam::Ignore
+                        && is_stale_beam(db, file_id, &beam_contents)?
+                    {
+                        if stale_beam == StaleBeam::Error {
+                            return Err(Error::StaleBEAM(beam_path.into()));
+                        }
+                        log::warn!("Ignoring stale BEAM file {beam_path}");
+                        let ast = db.erl_ast_bytes(project_id, module)?;
+                        return ast::from_bytes(&ast, true).map(Arc::new);
+                    }
+                    ast::from_beam(&beam_contents).map(Arc::new)
+                } else {
+                    Err(Error::BEAMNotFound(beam_path.into()))
+                }
+            }
+            Some(StubFile::Json(json_file_id)) => {
+                let json = db.file_text(json_file_id).text(db);
+                ast::from_json(json.as_bytes(), true).map(Arc::new)
+            }
+            None => {
+                let ast = db.erl_ast_bytes(project_id, module)?;
+                ast::from_bytes(&ast, true).map(Arc::new)
             }
-        } else {
-            let ast = db.erl_ast_bytes(project_id, module)?;
-            ast::from_bytes(&ast, true).map(Arc::new)
         }
     } else {
         Err(Error::ModuleNotFound(module.as_str().into()))
     }
 }
 
+fn module_by_declared_name(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Option<ModuleName> {
+    db.declared_modules(project_id).get(&module).cloned()
+}
+
+fn declared_modules(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+) -> Arc<BTreeMap<ModuleName, ModuleName>> {
+    let module_index = db.module_index(project_id);
+    let mut names: BTreeMap<ModuleName, ModuleName> = BTreeMap::new();
+    for (stem, _, file_id) in module_index.iter_own() {
+        let declared = db
+            .parse(file_id)
+            .tree()
+            .forms()
+            .find_map(|form| match form {
+                syntax::Form::ModuleAttribute(attr) => attr.name()?.text(),
+                _ => None,
+            });
+        let Some(declared) = declared else {
+            continue;
+        };
+        if declared == stem.as_str() {
+            continue;
+        }
+        // Several files may declare the same module, pick one consistently
+        names
+            .entry(ModuleName::new(&declared))
+            .and_modify(|current| {
+                if *stem < *current {
+                    *current = stem.clone()
+                }
+            })
+            .or_insert_with(|| stem.clone());
+    }
+    Arc::new(names)
+}
+
+fn converted_stub_bytes(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Result<Arc<Vec<u8>>, Error> {
+    db.converted_stub(project_id, module)
+        .map(|ast| Arc::new(ast::to_bytes(&ast.forms.iter().collect())))
+}
+
+fn is_stale_beam(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    file_id: FileId,
+    beam_contents: &[u8],
+) -> Result<bool, Error> {
+    let Some(hash) = ast::beam_source_hash(beam_contents)? else {
+        return Ok(false);
+    };
+    let text = db.file_text(file_id).text(db);
+    Ok(hash != ast::source_hash(&text))
+}
+
+/// Where to load the stub of a module from, other than the AST
+/// produced by the Erlang service.
+enum StubFile {
+    Beam(AbsPathBuf),
+    /// See `ast::from_json`.
+    Json(FileId),
+}
+
+fn stub_file(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    file_id: FileId,
+    module: &ModuleName,
+) -> Option<StubFile> {
+    from_beam_path(db, file_id, module)
+        .map(StubFile::Beam)
+        .or_else(|| json_sidecar(db, file_id).map(StubFile::Json))
+}
+
+/// A JSON-encoded AST that interop tools may put next to a source
+/// file, e.g. `foo.ast.json` for `foo.erl`. Sidecars are loaded in the
+/// VFS with the sources of their app, so adding, changing or removing
+/// one invalidates the stub.
+fn json_sidecar(db: &dyn EqwalizerDiagnosticsDatabase, file_id: FileId) -> Option<FileId> {
+    let source_root_id = db.file_source_root(file_id).source_root_id(db);
+    let source_root = db.source_root(source_root_id).source_root(db);
+    let path = source_root.path_for_file(&file_id)?;
+    let (name, _) = path.name_and_extension()?;
+    let sidecar = path.parent()?.join(&format!("{name}.ast.json"))?;
+    source_root.file_for_path(&sidecar)
+}
+
 fn from_beam_path(
     db: &dyn EqwalizerDiagnosticsDatabase,
     file_id: FileId,
     module: &ModuleName,
 ) -> Option<AbsPathBuf> {
     let app_data = db.file_app_data(file_id)?;
-    if app_data.app_type != AppType::Otp {
-        // Only OTP modules are loaded from BEAM
+    let config = db.eqwalizer_config();
+    let is_otp = app_data.app_type == AppType::Otp;
+    if !is_otp && !config.beam_for_all_apps {
+        // Only OTP modules are loaded from BEAM by default
         return None;
     }
-    let ebin = app_data.ebin_path.as_ref()?;
     let filename = format!("{}.beam", module.as_str());
-    Some(ebin.join(filename))
+    let candidates: Vec<AbsPathBuf> = ebin_dirs(&app_data, &config)
+        .map(|ebin| ebin.join(&filename))
+        .collect();
+    let beam_files = db.ebin_beam_files();
+    match candidates.iter().find(|path| beam_files.contains(*path)) {
+        Some(path) => Some(path.clone()),
+        // OTP modules are expected to have a BEAM file, report the
+        // missing one rather than falling back to the source
+        None if is_otp => candidates.into_iter().next(),
+        None => None,
+    }
+}
+
+/// The directories the BEAM files of an app are looked for in, in
+/// order: its own `ebin` directory, then `EqwalizerConfig::ebin_dirs`.
+fn ebin_dirs<'a>(
+    app_data: &'a AppData,
+    config: &'a EqwalizerConfig,
+) -> impl Iterator<Item = AbsPathBuf> + 'a {
+    app_data
+        .ebin_path
+        .iter()
+        .cloned()
+        .chain(config.ebin_dirs.iter().map(|dir| app_data.dir.join(dir)))
 }
 
 fn type_ids(
@@ -285,6 +894,89 @@ fn type_ids(
         .map(|ast| Arc::new(ast::type_ids(&ast)))
 }
 
+fn exported_functions(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Result<Arc<BTreeSet<Id>>, Error> {
+    let ast = db.converted_stub(project_id, module)?;
+    let mut exports = BTreeSet::new();
+    for form in &ast.forms {
+        if let ExternalForm::Export(attr) = form {
+            exports.extend(attr.funs.iter().cloned());
+        }
+    }
+    Ok(Arc::new(exports))
+}
+
+fn exported_types(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Result<Arc<BTreeSet<Id>>, Error> {
+    let ast = db.converted_stub(project_id, module)?;
+    let mut exports = BTreeSet::new();
+    for form in &ast.forms {
+        if let ExternalForm::ExportType(attr) = form {
+            exports.extend(attr.types.iter().cloned());
+        }
+    }
+    Ok(Arc::new(exports))
+}
+
+fn declared_behaviours(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Result<Arc<Vec<ModuleName>>, Error> {
+    // The conversion maps both `-behaviour` and `-behavior` to `Behaviour`
+    let ast = db.converted_stub(project_id, module)?;
+    let mut behaviours: Vec<ModuleName> = vec![];
+    for form in &ast.forms {
+        if let ExternalForm::Behaviour(attr) = form {
+            let behaviour = ModuleName::new(attr.name.as_str());
+            if !behaviours.contains(&behaviour) {
+                behaviours.push(behaviour);
+            }
+        }
+    }
+    Ok(Arc::new(behaviours))
+}
+
+fn behaviour_implementors(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    behaviour: ModuleName,
+) -> Arc<Vec<ModuleName>> {
+    let module_index = db.module_index(project_id);
+    let mut implementors: Vec<ModuleName> = module_index
+        .iter_own()
+        .map(|(module, _, _)| module.clone())
+        .filter(|module| {
+            db.declared_behaviours(project_id, module.clone())
+                .is_ok_and(|behaviours| behaviours.contains(&behaviour))
+        })
+        .collect();
+    implementors.sort();
+    Arc::new(implementors)
+}
+
+fn duplicate_modules(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+) -> Arc<BTreeMap<ModuleName, Vec<FileId>>> {
+    let module_index = db.module_index(project_id);
+    let duplicates = module_index
+        .iter_duplicates()
+        .map(|(module, file_ids)| {
+            let mut file_ids: Vec<FileId> = file_ids.iter().copied().collect();
+            file_ids.sort();
+            (module.clone(), file_ids)
+        })
+        .collect();
+    Arc::new(duplicates)
+}
+
 fn expanded_stub(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
@@ -298,6 +990,21 @@ fn expanded_stub(
         .map_err(Error::TypeConversionError)
 }
 
+fn stub_expansion_stats(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Result<Arc<ExpansionStats>, Error> {
+    // Expand again rather than slowing down `expanded_stub`, which
+    // doesn't collect stats
+    let ast = db.converted_stub(project_id, module.clone())?;
+    let mut expander = StubExpander::new(db, project_id, module.as_str().into(), &ast).with_stats();
+    expander
+        .expand(&ast.forms)
+        .map_err(Error::TypeConversionError)?;
+    Ok(Arc::new(expander.stats().cloned().unwrap_or_default()))
+}
+
 fn contractive_stub(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
@@ -308,6 +1015,15 @@ fn contractive_stub(
     Ok(Arc::new(checker.check(stub)))
 }
 
+fn contractivity_errors(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Result<Arc<Vec<ContractivityError>>, Error> {
+    db.contractive_stub(project_id, module)
+        .map(|v_stub| Arc::new(v_stub.contractivity_errors.clone()))
+}
+
 fn transitive_stub(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
@@ -318,6 +1034,15 @@ fn transitive_stub(
     Ok(Arc::new(checker.check(&v_stub)))
 }
 
+fn transitive_stub_size(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Result<usize, Error> {
+    db.transitive_stub(project_id, module)
+        .map(|stub| stub.serialized_size())
+}
+
 fn transitive_stub_bytes(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
@@ -333,42 +1058,112 @@ fn custom_types(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
 ) -> Result<Arc<BTreeMap<ModuleName, BTreeMap<Id, Arc<TypeDecl>>>>, Error> {
+    if !db.eqwalizer_config().enable_custom_overrides {
+        return Ok(Arc::new(BTreeMap::new()));
+    }
     match db.transitive_stub(project_id, EQWALIZER_TYPES.clone()) {
-        Ok(stub) => {
-            let mut result: BTreeMap<ModuleName, BTreeMap<Id, Arc<TypeDecl>>> = BTreeMap::new();
-            for (id, type_decl) in stub.types.iter() {
-                let (module_name, ty_name) = id.name.split_once(':').unwrap();
-                let module = ModuleName::new(module_name);
-                let id = Id {
-                    name: StringId::from(ty_name),
-                    arity: id.arity,
-                };
-                result
-                    .entry(module)
-                    .or_default()
-                    .insert(id, type_decl.clone());
-            }
-            Ok(Arc::new(result))
-        }
+        Ok(stub) => Ok(Arc::new(group_by_module(&stub.types, split_module_id))),
         // if there is no eqwalizer_types module, return empty map
         Err(Error::ModuleNotFound(_)) => Ok(Arc::new(BTreeMap::new())),
         Err(err) => Err(err),
     }
 }
 
+/// Split the `'module:name'` ids of the `eqwalizer_types` or
+/// `eqwalizer_specs` overrides by module. The ids of a module are
+/// contiguous in `entries`, so they are collected in order first, and
+/// each inner map is then built in one go rather than entry by entry.
+fn group_by_module<T>(
+    entries: &BTreeMap<Id, Arc<T>>,
+    split: fn(&str) -> (&str, &str),
+) -> BTreeMap<ModuleName, BTreeMap<Id, Arc<T>>> {
+    let mut groups: Vec<(&str, Vec<(Id, Arc<T>)>)> = Vec::new();
+    for (id, value) in entries {
+        let (module_name, name) = split(&id.name);
+        let id = Id {
+            name: StringId::from(name),
+            arity: id.arity,
+        };
+        match groups.last_mut() {
+            Some((module, ids)) if *module == module_name => ids.push((id, value.clone())),
+            _ => groups.push((module_name, vec![(id, value.clone())])),
+        }
+    }
+    let mut result = BTreeMap::new();
+    for (module_name, ids) in groups {
+        match result.entry(ModuleName::new(module_name)) {
+            btree_map::Entry::Vacant(entry) => {
+                entry.insert(ids.into_iter().collect());
+            }
+            // Only if the ids of a module were not contiguous after all
+            btree_map::Entry::Occupied(mut entry) => entry.get_mut().extend(ids),
+        }
+    }
+    result
+}
+
+/// `'module:name'` as `("module", "name")`.
+fn split_module_id(id: &str) -> (&str, &str) {
+    id.split_once(':').unwrap()
+}
+
+/// Like `split_module_id`, but the name of an overloaded spec has always
+/// stopped at the next `:`, if any.
+fn split_overloaded_module_id(id: &str) -> (&str, &str) {
+    let (module_name, name) = split_module_id(id);
+    (module_name, name.split(':').next().unwrap())
+}
+
+/// Whether the `eqwalizer_types` and `eqwalizer_specs` overrides apply
+/// to the types and specs of `module`.
+fn uses_custom_overrides(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: &ModuleName,
+) -> bool {
+    db.module_eqwalizer_config(project_id, module.clone())
+        .enable_custom_overrides
+}
+
 fn type_decl(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
     module: ModuleName,
     id: Id,
 ) -> Result<Option<Arc<TypeDecl>>, Error> {
-    let custom_types = db.custom_types(project_id)?;
-    // return custom type if it exists
-    if let Some(t) = custom_types.get(&module).and_then(|m| m.get(&id)) {
-        return Ok(Some(t.clone()));
+    type_decl_traced(db, project_id, module, id).map(|(decl, _)| decl)
+}
+
+/// Where `type_decl` found a type declaration, see `type_decl_traced`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeDeclSource {
+    /// The `eqwalizer_types` module, overriding the module's own.
+    Custom,
+    /// The transitive stub of the module.
+    Stub,
+    NotFound,
+}
+
+/// Like `type_decl`, telling where the declaration comes from, e.g. to
+/// explain why a custom type did or didn't apply.
+pub fn type_decl_traced(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+    id: Id,
+) -> Result<(Option<Arc<TypeDecl>>, TypeDeclSource), Error> {
+    if uses_custom_overrides(db, project_id, &module) {
+        let custom_types = db.custom_types(project_id)?;
+        // return custom type if it exists
+        if let Some(t) = custom_types.get(&module).and_then(|m| m.get(&id)) {
+            return Ok((Some(t.clone()), TypeDeclSource::Custom));
+        }
     }
     let stub = db.transitive_stub(project_id, module)?;
-    Ok(stub.types.get(&id).cloned())
+    Ok(match stub.types.get(&id) {
+        Some(t) => (Some(t.clone()), TypeDeclSource::Stub),
+        None => (None, TypeDeclSource::NotFound),
+    })
 }
 
 fn type_decl_bytes(
@@ -381,6 +1176,40 @@ fn type_decl_bytes(
         .map(|t| t.map(|t| Arc::new(t.to_bytes())))
 }
 
+fn is_opaque_type(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+    id: Id,
+) -> Result<bool, Error> {
+    db.type_decl(project_id, module, id)
+        .map(|decl| decl.is_some_and(|decl| decl.opaque))
+}
+
+fn all_type_decl_bytes(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Result<Arc<BTreeMap<Id, Vec<u8>>>, Error> {
+    let custom_types = if uses_custom_overrides(db, project_id, &module) {
+        db.custom_types(project_id)?
+    } else {
+        Arc::new(BTreeMap::new())
+    };
+    let stub = db.transitive_stub(project_id, module.clone())?;
+    let mut types: BTreeMap<&Id, &Arc<TypeDecl>> = stub.types.iter().collect();
+    // Custom types take precedence, as in `type_decl`
+    if let Some(custom) = custom_types.get(&module) {
+        types.extend(custom.iter());
+    }
+    Ok(Arc::new(
+        types
+            .into_iter()
+            .map(|(id, decl)| (id.clone(), decl.to_bytes()))
+            .collect(),
+    ))
+}
+
 fn rec_decl(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
@@ -407,17 +1236,19 @@ fn fun_spec(
     module: ModuleName,
     id: Id,
 ) -> Result<Option<Arc<FunSpec>>, Error> {
-    let custom_overloaded_fun_specs = db.custom_overloaded_fun_specs(project_id)?;
-    if custom_overloaded_fun_specs
-        .get(&module)
-        .and_then(|m| m.get(&id))
-        .is_some()
-    {
-        return Ok(None);
-    }
-    let custom_fun_specs = db.custom_fun_specs(project_id)?;
-    if let Some(fun_spec) = custom_fun_specs.get(&module).and_then(|m| m.get(&id)) {
-        return Ok(Some(fun_spec.clone()));
+    if uses_custom_overrides(db, project_id, &module) {
+        let custom_overloaded_fun_specs = db.custom_overloaded_fun_specs(project_id)?;
+        if custom_overloaded_fun_specs
+            .get(&module)
+            .and_then(|m| m.get(&id))
+            .is_some()
+        {
+            return Ok(None);
+        }
+        let custom_fun_specs = db.custom_fun_specs(project_id)?;
+        if let Some(fun_spec) = custom_fun_specs.get(&module).and_then(|m| m.get(&id)) {
+            return Ok(Some(fun_spec.clone()));
+        }
     }
     let stub = db.transitive_stub(project_id, module)?;
     Ok(stub.specs.get(&id).cloned())
@@ -439,25 +1270,55 @@ fn overloaded_fun_spec(
     module: ModuleName,
     id: Id,
 ) -> Result<Option<Arc<OverloadedFunSpec>>, Error> {
-    let custom_fun_specs = db.custom_fun_specs(project_id)?;
-    if custom_fun_specs
-        .get(&module)
-        .and_then(|m| m.get(&id))
-        .is_some()
-    {
-        return Ok(None);
-    }
-    let custom_overloaded_fun_specs = db.custom_overloaded_fun_specs(project_id)?;
-    if let Some(overloaded_fun_spec) = custom_overloaded_fun_specs
-        .get(&module)
-        .and_then(|m| m.get(&id))
-    {
-        return Ok(Some(overloaded_fun_spec.clone()));
+    if uses_custom_overrides(db, project_id, &module) {
+        let custom_fun_specs = db.custom_fun_specs(project_id)?;
+        if custom_fun_specs
+            .get(&module)
+            .and_then(|m| m.get(&id))
+            .is_some()
+        {
+            return Ok(None);
+        }
+        let custom_overloaded_fun_specs = db.custom_overloaded_fun_specs(project_id)?;
+        if let Some(overloaded_fun_spec) = custom_overloaded_fun_specs
+            .get(&module)
+            .and_then(|m| m.get(&id))
+        {
+            return Ok(Some(overloaded_fun_spec.clone()));
+        }
     }
     let stub = db.transitive_stub(project_id, module)?;
     Ok(stub.overloaded_specs.get(&id).cloned())
 }
 
+fn specced_functions(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Result<Arc<BTreeSet<Id>>, Error> {
+    let mut ids = BTreeSet::new();
+    if uses_custom_overrides(db, project_id, &module) {
+        let custom_fun_specs = db.custom_fun_specs(project_id)?;
+        ids.extend(
+            custom_fun_specs
+                .get(&module)
+                .into_iter()
+                .flat_map(|m| m.keys().cloned()),
+        );
+        let custom_overloaded_fun_specs = db.custom_overloaded_fun_specs(project_id)?;
+        ids.extend(
+            custom_overloaded_fun_specs
+                .get(&module)
+                .into_iter()
+                .flat_map(|m| m.keys().cloned()),
+        );
+    }
+    let stub = db.transitive_stub(project_id, module)?;
+    ids.extend(stub.specs.keys().cloned());
+    ids.extend(stub.overloaded_specs.keys().cloned());
+    Ok(Arc::new(ids))
+}
+
 fn overloaded_fun_spec_bytes(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
@@ -474,23 +1335,11 @@ fn custom_fun_specs(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
 ) -> Result<Arc<BTreeMap<ModuleName, BTreeMap<Id, Arc<FunSpec>>>>, Error> {
+    if !db.eqwalizer_config().enable_custom_overrides {
+        return Ok(Arc::new(BTreeMap::new()));
+    }
     match db.transitive_stub(project_id, EQWALIZER_SPECS.clone()) {
-        Ok(stub) => {
-            let mut result: BTreeMap<ModuleName, BTreeMap<Id, Arc<FunSpec>>> = BTreeMap::new();
-            for (id, fun_spec) in stub.specs.iter() {
-                let (module_name, ty_name) = id.name.split_once(':').unwrap();
-                let module = ModuleName::new(module_name);
-                let id = Id {
-                    name: StringId::from(ty_name),
-                    arity: id.arity,
-                };
-                result
-                    .entry(module)
-                    .or_default()
-                    .insert(id, fun_spec.clone());
-            }
-            Ok(Arc::new(result))
-        }
+        Ok(stub) => Ok(Arc::new(group_by_module(&stub.specs, split_module_id))),
         // if there is no eqwalizer_specs module, return an empty map
         Err(Error::ModuleNotFound(_)) => Ok(Arc::new(BTreeMap::new())),
         Err(err) => Err(err),
@@ -501,25 +1350,14 @@ fn custom_overloaded_fun_specs(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
 ) -> Result<Arc<BTreeMap<ModuleName, BTreeMap<Id, Arc<OverloadedFunSpec>>>>, Error> {
+    if !db.eqwalizer_config().enable_custom_overrides {
+        return Ok(Arc::new(BTreeMap::new()));
+    }
     match db.transitive_stub(project_id, EQWALIZER_SPECS.clone()) {
-        Ok(stub) => {
-            let mut result: BTreeMap<ModuleName, BTreeMap<Id, Arc<OverloadedFunSpec>>> =
-                BTreeMap::new();
-            for (id, overloaded_fun_spec) in stub.overloaded_specs.iter() {
-                let parts: Vec<&str> = id.name.split(":").collect();
-                let module = ModuleName::new(parts[0]);
-                let ty_name = parts[1];
-                let id = Id {
-                    name: ty_name.to_string().into(),
-                    arity: id.arity,
-                };
-                result
-                    .entry(module)
-                    .or_default()
-                    .insert(id, overloaded_fun_spec.clone());
-            }
-            Ok(Arc::new(result))
-        }
+        Ok(stub) => Ok(Arc::new(group_by_module(
+            &stub.overloaded_specs,
+            split_overloaded_module_id,
+        ))),
         // if there is no eqwalizer_specs module, return empty map
         Err(Error::ModuleNotFound(_)) => Ok(Arc::new(BTreeMap::new())),
         Err(err) => Err(err),
@@ -535,6 +1373,15 @@ fn callbacks(
     Ok((stub.callbacks.clone(), stub.optional_callbacks.clone()))
 }
 
+fn optional_callbacks(
+    db: &dyn EqwalizerDiagnosticsDatabase,
+    project_id: ProjectId,
+    module: ModuleName,
+) -> Result<Arc<BTreeSet<Id>>, Error> {
+    let stub = db.transitive_stub(project_id, module)?;
+    Ok(stub.optional_callbacks.clone())
+}
+
 fn callbacks_bytes(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
@@ -21,6 +21,7 @@ use std::process::Command;
 use std::process::Stdio;
 use std::thread;
 use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::Context;
 use anyhow::Result;
@@ -157,6 +158,8 @@ pub struct IpcHandle {
 
 const WRITE_TIMEOUT: Duration = Duration::from_secs(240);
 const READ_TIMEOUT: Duration = Duration::from_secs(240);
+/// How often a blocked read checks whether the request was cancelled.
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 impl IpcHandle {
     fn spawn_cmd(cmd: &mut Command) -> Result<Child> {
@@ -205,7 +208,7 @@ impl IpcHandle {
 
         let _child_for_drop = JodChild(child);
         let writer = BufWriter::new(TimeoutWriter::new(stdin, WRITE_TIMEOUT));
-        let reader = BufReader::new(TimeoutReader::new(stdout, READ_TIMEOUT));
+        let reader = BufReader::new(TimeoutReader::new(stdout, READ_POLL_INTERVAL));
 
         Ok(Self {
             writer,
@@ -215,14 +218,21 @@ impl IpcHandle {
     }
 
     pub fn receive(&mut self) -> Result<MsgFromEqWAlizer> {
-        let buf = self.receive_line().context("receiving message")?;
+        self.receive_cancellable(|| ())
+    }
+
+    /// Like `receive`, but calls `unwind` periodically while waiting
+    /// for eqWAlizer, so that a cancelled request can abort mid-flight
+    /// by panicking from it, as `unwind_if_revision_cancelled` does.
+    pub fn receive_cancellable(&mut self, unwind: impl Fn()) -> Result<MsgFromEqWAlizer> {
+        let buf = self.receive_line(&unwind).context("receiving message")?;
         let deserialized = serde_json::from_str(&buf)
             .with_context(|| format!("parsing for eqwalizer: {buf:?}"))?;
         Ok(deserialized)
     }
 
     pub fn receive_newline(&mut self) -> Result<()> {
-        let _ = self.receive_line().context("receiving newline")?;
+        let _ = self.receive_line(&|| ()).context("receiving newline")?;
         Ok(())
     }
 
@@ -250,11 +260,20 @@ impl IpcHandle {
         Ok(())
     }
 
-    fn receive_line(&mut self) -> Result<String> {
-        let mut buf = String::new();
-        self.reader
-            .read_line(&mut buf)
-            .context("failed read_line from eqwalizer stdout")?;
-        Ok(buf)
+    fn receive_line(&mut self, unwind: &dyn Fn()) -> Result<String> {
+        // Read raw bytes, a timed out read keeps what it consumed so far,
+        // even if it ends in the middle of a UTF-8 character.
+        let mut buf = Vec::new();
+        let start = Instant::now();
+        loop {
+            match self.reader.read_until(b'\n', &mut buf) {
+                Ok(_) => break,
+                Err(err) if err.kind() == ErrorKind::TimedOut && start.elapsed() < READ_TIMEOUT => {
+                    unwind()
+                }
+                Err(err) => return Err(err).context("failed read_line from eqwalizer stdout"),
+            }
+        }
+        String::from_utf8(buf).context("eqwalizer stdout is not valid UTF-8")
     }
 }
@@ -8,8 +8,11 @@
  * above-listed licenses.
  */
 
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::env;
 use std::ffi::OsString;
+use std::fmt;
 use std::fs;
 use std::io::Write;
 #[cfg(unix)]
@@ -29,13 +32,16 @@ use elp_base_db::limit_logged_string;
 pub use elp_types_db::eqwalizer::EqwalizerDiagnostic;
 use elp_types_db::eqwalizer::types::Type;
 use fxhash::FxHashMap;
+use fxhash::FxHashSet;
 use parking_lot::Mutex;
 use tempfile::Builder;
 use tempfile::TempPath;
 
+pub mod config_overrides;
 pub mod db;
 
 pub mod ipc;
+pub mod progress;
 use ipc::IpcHandle;
 use ipc::MsgFromEqWAlizer;
 use ipc::MsgToEqWAlizer;
@@ -65,11 +71,72 @@ impl Mode {
     }
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct EqwalizerConfig {
     pub overloaded_spec_dynamic_result: Option<bool>,
     pub report_dynamic_lambdas: Option<bool>,
+    /// Whether types and specs from the `eqwalizer_types` and
+    /// `eqwalizer_specs` modules override those of the modules they name.
+    pub enable_custom_overrides: bool,
+    /// How deeply nested a type may be before stub expansion gives up
+    /// on it, rather than overflowing the stack.
+    pub max_type_expansion_depth: usize,
+    /// What to do with a BEAM stub whose recorded source hash doesn't
+    /// match the current source of its module.
+    pub stale_beam: StaleBeam,
+    /// More directories to look for the BEAM files of an app in, after
+    /// its own `ebin` directory. Relative ones are resolved against the
+    /// app directory.
+    pub ebin_dirs: Vec<String>,
+    /// Whether the types of modules outside of OTP are loaded from their
+    /// BEAM file as well, when there is one in the ebin directories.
+    pub beam_for_all_apps: bool,
+    /// Whether modules marked as generated, with a `% elp:generated`
+    /// comment, are left unchecked.
+    pub skip_generated: bool,
+    /// Whether a module no file is named after is looked up by its
+    /// `-module` attribute, and analysed under the name of its file,
+    /// rather than reported as not found.
+    pub infer_module_from_file_stem: bool,
+    /// Modules never eqWAlized, such as vendored ones, by name or by a
+    /// pattern where `*` matches any sequence of characters, such as
+    /// `thrift_*`.
+    pub skip_modules: Vec<String>,
 }
+
+pub const DEFAULT_MAX_TYPE_EXPANSION_DEPTH: usize = 500;
+
+/// Handling of BEAM files built from an older version of their source,
+/// detected through the hash stored in their `ElpH` chunk, see
+/// `ast::beam_source_hash`. BEAM files without this chunk are trusted.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum StaleBeam {
+    /// Use the BEAM file anyway.
+    Ignore,
+    /// Build the stub from the source instead.
+    #[default]
+    FallBackToSource,
+    /// Fail with `ast::Error::StaleBEAM`.
+    Error,
+}
+
+impl Default for EqwalizerConfig {
+    fn default() -> Self {
+        EqwalizerConfig {
+            overloaded_spec_dynamic_result: None,
+            report_dynamic_lambdas: None,
+            enable_custom_overrides: true,
+            max_type_expansion_depth: DEFAULT_MAX_TYPE_EXPANSION_DEPTH,
+            stale_beam: StaleBeam::default(),
+            ebin_dirs: vec![],
+            beam_for_all_apps: false,
+            skip_generated: false,
+            infer_module_from_file_stem: false,
+            skip_modules: vec![],
+        }
+    }
+}
+
 impl EqwalizerConfig {
     fn set_cmd_env(&self, cmd: &mut Command) {
         self.overloaded_spec_dynamic_result
@@ -82,10 +149,86 @@ impl EqwalizerConfig {
         EqwalizerConfig {
             overloaded_spec_dynamic_result: Some(false),
             report_dynamic_lambdas: Some(false),
+            enable_custom_overrides: true,
+            max_type_expansion_depth: DEFAULT_MAX_TYPE_EXPANSION_DEPTH,
+            stale_beam: StaleBeam::default(),
+            ebin_dirs: vec![],
+            beam_for_all_apps: false,
+            skip_generated: false,
+            infer_module_from_file_stem: false,
+            skip_modules: vec![],
+        }
+    }
+
+    /// Whether `module` matches one of `skip_modules`.
+    pub fn skips_module(&self, module: &str) -> bool {
+        self.skip_modules
+            .iter()
+            .any(|pattern| matches_pattern(pattern.as_bytes(), module.as_bytes()))
+    }
+
+    /// Check the settings that would only fail once queries use them.
+    pub fn validate(&self) -> Result<(), EqwalizerConfigError> {
+        if self.max_type_expansion_depth == 0 {
+            return Err(EqwalizerConfigError::ZeroMaxTypeExpansionDepth);
         }
+        if let Some(idx) = self.ebin_dirs.iter().position(|dir| dir.trim().is_empty()) {
+            return Err(EqwalizerConfigError::EmptyEbinDir(idx));
+        }
+        Ok(())
     }
 }
 
+/// Whether `name` matches `pattern`, in which `*` matches any sequence
+/// of characters.
+fn matches_pattern(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*`: its position in `pattern`, and
+    // the position in `name` it matches up to.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, n));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == name[n] {
+            p += 1;
+            n += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// A setting of `EqwalizerConfig` rejected by `EqwalizerConfig::validate`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EqwalizerConfigError {
+    /// No type could be expanded at all.
+    ZeroMaxTypeExpansionDepth,
+    /// The ebin directory at this index is empty, so it would resolve
+    /// to the app directory itself.
+    EmptyEbinDir(usize),
+}
+
+impl fmt::Display for EqwalizerConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EqwalizerConfigError::ZeroMaxTypeExpansionDepth => {
+                write!(f, "max_type_expansion_depth must be positive")
+            }
+            EqwalizerConfigError::EmptyEbinDir(idx) => {
+                write!(f, "ebin directory #{idx} is empty")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EqwalizerConfigError {}
+
 // Bundle file with command to make sure it's not removed too early
 #[derive(Clone)]
 pub struct Eqwalizer {
@@ -153,6 +296,68 @@ impl EqwalizerDiagnostics {
             },
         }
     }
+
+    /// Compare the errors of two runs, e.g. with different configs.
+    /// Diagnostics are the same when they have the same code and range.
+    pub fn diff(&self, other: &EqwalizerDiagnostics) -> DiagnosticsDiff {
+        let (
+            EqwalizerDiagnostics::Diagnostics { errors, .. },
+            EqwalizerDiagnostics::Diagnostics {
+                errors: other_errors,
+                ..
+            },
+        ) = (self, other)
+        else {
+            return DiagnosticsDiff::Incomparable;
+        };
+        let mut added: BTreeMap<String, Vec<EqwalizerDiagnostic>> = BTreeMap::new();
+        let mut removed: BTreeMap<String, Vec<EqwalizerDiagnostic>> = BTreeMap::new();
+        let mut common: BTreeMap<String, Vec<EqwalizerDiagnostic>> = BTreeMap::new();
+        let modules: BTreeSet<&String> = errors.keys().chain(other_errors.keys()).collect();
+        for module in modules {
+            let ours = errors.get(module).map(Vec::as_slice).unwrap_or_default();
+            let theirs = other_errors
+                .get(module)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let key = |d: &EqwalizerDiagnostic| (d.code.clone(), d.range);
+            let our_keys: FxHashSet<_> = ours.iter().map(key).collect();
+            let their_keys: FxHashSet<_> = theirs.iter().map(key).collect();
+            for d in ours {
+                let side = if their_keys.contains(&key(d)) {
+                    &mut common
+                } else {
+                    &mut removed
+                };
+                side.entry(module.clone()).or_default().push(d.clone());
+            }
+            for d in theirs {
+                if !our_keys.contains(&key(d)) {
+                    added.entry(module.clone()).or_default().push(d.clone());
+                }
+            }
+        }
+        DiagnosticsDiff::Diff {
+            added,
+            removed,
+            common,
+        }
+    }
+}
+
+/// The result of `EqwalizerDiagnostics::diff`, by module.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DiagnosticsDiff {
+    Diff {
+        /// Only reported by the other run.
+        added: BTreeMap<String, Vec<EqwalizerDiagnostic>>,
+        /// Only reported by this run.
+        removed: BTreeMap<String, Vec<EqwalizerDiagnostic>>,
+        /// Reported by both runs, as reported by this one.
+        common: BTreeMap<String, Vec<EqwalizerDiagnostic>>,
+    },
+    /// One of the runs failed, or had no AST to check.
+    Incomparable,
 }
 
 impl Default for Eqwalizer {
@@ -285,7 +490,9 @@ fn do_typecheck(
     let mut diagnostics = EqwalizerDiagnostics::default();
     loop {
         db.unwind_if_revision_cancelled();
-        let msg = handle.lock().receive()?;
+        let msg = handle
+            .lock()
+            .receive_cancellable(|| db.unwind_if_revision_cancelled())?;
         match msg {
             MsgFromEqWAlizer::EnteringModule { module } => {
                 let module_name = ModuleName::new(&module);
@@ -314,6 +521,21 @@ fn do_typecheck(
     }
 }
 
+/// Clears the stored IPC handle of a module when eqWAlizing it is
+/// cancelled, so the eqWAlizer process is not kept alive by the db.
+struct ReleaseHandleOnCancel<'a> {
+    db: &'a dyn EqwalizerDiagnosticsDatabase,
+    module: ModuleName,
+}
+
+impl Drop for ReleaseHandleOnCancel<'_> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.db.set_module_ipc_handle(self.module.clone(), None);
+        }
+    }
+}
+
 fn get_module_diagnostics(
     db: &dyn EqwalizerDiagnosticsDatabase,
     project_id: ProjectId,
@@ -324,11 +546,15 @@ fn get_module_diagnostics(
         .ok_or(anyhow::Error::msg(format!(
             "no eqWAlizer handle for module {module}"
         )))?;
+    let _release = ReleaseHandleOnCancel {
+        db,
+        module: ModuleName::new(&module),
+    };
     let mut handle = handle_mutex.lock();
     handle.send(&MsgToEqWAlizer::ELPEnteringModule)?;
     loop {
         db.unwind_if_revision_cancelled();
-        match handle.receive()? {
+        match handle.receive_cancellable(|| db.unwind_if_revision_cancelled())? {
             MsgFromEqWAlizer::GetAstBytes { module, format } => {
                 log::debug!(
                     "received from eqwalizer: GetAstBytes for module {module} (format = {format:?})"
@@ -0,0 +1,62 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Aggregate progress of a project-wide eqWAlizer run, suitable for an
+//! LSP `$/progress` bar.
+
+use std::fmt;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use parking_lot::Mutex;
+
+/// Turns per-module `eqwalizing_done` notifications into percentage
+/// updates. Modules may complete on several threads at once, each
+/// percentage is reported at most once and in increasing order.
+pub struct EqwalizerProgress {
+    total: usize,
+    done: AtomicUsize,
+    reported: Mutex<u32>,
+    on_progress: Box<dyn Fn(u32) + Send + Sync>,
+}
+
+impl EqwalizerProgress {
+    pub fn new(total: usize, on_progress: impl Fn(u32) + Send + Sync + 'static) -> Self {
+        EqwalizerProgress {
+            total,
+            done: AtomicUsize::new(0),
+            reported: Mutex::new(0),
+            on_progress: Box::new(on_progress),
+        }
+    }
+
+    pub fn module_done(&self) {
+        let done = self.done.fetch_add(1, Ordering::SeqCst) + 1;
+        let percentage = if self.total == 0 {
+            100
+        } else {
+            (done.min(self.total) * 100 / self.total) as u32
+        };
+        let mut reported = self.reported.lock();
+        if percentage > *reported {
+            *reported = percentage;
+            (self.on_progress)(percentage)
+        }
+    }
+}
+
+impl fmt::Debug for EqwalizerProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EqwalizerProgress")
+            .field("total", &self.total)
+            .field("done", &self.done)
+            .finish()
+    }
+}
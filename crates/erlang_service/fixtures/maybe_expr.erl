@@ -0,0 +1,11 @@
+-module(maybe_expr).
+
+-export([main/1]).
+
+main(X) ->
+    maybe
+        {ok, A} ?= X,
+        A
+    else
+        error -> undefined
+    end.
@@ -0,0 +1,12 @@
+-module(maybe_expr_in_file).
+-feature(maybe_expr, enable).
+
+-export([main/1]).
+
+main(X) ->
+    maybe
+        {ok, A} ?= X,
+        A
+    else
+        error -> undefined
+    end.
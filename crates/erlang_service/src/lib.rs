@@ -54,6 +54,7 @@ use text_size::TextRange;
 use text_size::TextSize;
 
 pub mod common_test;
+pub mod pretty_etf;
 
 lazy_static! {
     pub static ref ESCRIPT: RwLock<String> = RwLock::new("escript".to_string());
@@ -94,8 +95,16 @@ pub enum CompileOption {
     Macros(Vec<eetf::Term>),
     ParseTransforms(Vec<eetf::Term>),
     ElpMetadata(eetf::Term),
+    /// `-feature(Name, enable | disable)`, applied to the whole module.
+    Feature {
+        name: String,
+        enable: bool,
+    },
 }
 
+/// The language features understood by the erlang service.
+pub const KNOWN_FEATURES: &[&str] = &["maybe_expr"];
+
 impl From<CompileOption> for eetf::Term {
     fn from(val: CompileOption) -> Self {
         match val {
@@ -112,6 +121,16 @@ impl From<CompileOption> for eetf::Term {
                 let label = eetf::Atom::from("elp_metadata");
                 eetf::Tuple::from(vec![label.into(), elp_metadata]).into()
             }
+            CompileOption::Feature { name, enable } => {
+                let label = eetf::Atom::from("feature");
+                let toggle = eetf::Atom::from(if enable { "enable" } else { "disable" });
+                eetf::Tuple::from(vec![
+                    label.into(),
+                    eetf::Atom::from(name).into(),
+                    toggle.into(),
+                ])
+                .into()
+            }
         }
     }
 }
@@ -169,6 +188,66 @@ pub struct ParseError {
     pub code: String,
 }
 
+/// The `L0xxx` codes of the `ParseError`s reported by ELP itself,
+/// rather than by the Erlang compiler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseErrorCode {
+    /// An included file has errors, reported by the Erlang service.
+    IssueInIncludedFile,
+    /// The Erlang service response could not be decoded.
+    CouldNotParse,
+    /// The file doesn't belong to any known application.
+    UnknownApplication,
+    /// The project configuration enables a feature that doesn't exist.
+    UnknownFeature,
+    /// The file declares an encoding the Erlang compiler doesn't support.
+    UnsupportedEncoding,
+    /// Resolving an include of the file panicked.
+    IncludeResolutionPanicked,
+    /// The project defines a macro ELP defines for the Erlang service.
+    ElpMacroOverridden,
+}
+
+impl ParseErrorCode {
+    /// The code used in `ParseError::code` and shown to users.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseErrorCode::IssueInIncludedFile => "L0000",
+            ParseErrorCode::CouldNotParse => "L0002",
+            ParseErrorCode::UnknownApplication => "L0003",
+            ParseErrorCode::UnknownFeature => "L0004",
+            ParseErrorCode::UnsupportedEncoding => "L0005",
+            ParseErrorCode::IncludeResolutionPanicked => "L0006",
+            ParseErrorCode::ElpMacroOverridden => "L0007",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<ParseErrorCode> {
+        match code {
+            "L0000" => Some(ParseErrorCode::IssueInIncludedFile),
+            "L0002" => Some(ParseErrorCode::CouldNotParse),
+            "L0003" => Some(ParseErrorCode::UnknownApplication),
+            "L0004" => Some(ParseErrorCode::UnknownFeature),
+            "L0005" => Some(ParseErrorCode::UnsupportedEncoding),
+            "L0006" => Some(ParseErrorCode::IncludeResolutionPanicked),
+            "L0007" => Some(ParseErrorCode::ElpMacroOverridden),
+            _ => None,
+        }
+    }
+}
+
+impl ParseError {
+    /// An error reported by ELP, not attached to a location in the file.
+    pub fn elp(path: PathBuf, code: ParseErrorCode, msg: String) -> ParseError {
+        ParseError {
+            path,
+            location: None,
+            msg,
+            code: code.code().to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DocDiagnostic {
     pub code: String,
@@ -433,12 +512,11 @@ impl Connection {
                 })
             })
             .unwrap_or_else(|error| {
-                ParseResult::error(ParseError {
+                ParseResult::error(ParseError::elp(
                     path,
-                    location: None,
-                    msg: format!("Could not parse, error: {error}"),
-                    code: "L0002".to_string(),
-                })
+                    ParseErrorCode::CouldNotParse,
+                    format!("Could not parse, error: {error}"),
+                ))
             })
     }
 
@@ -882,6 +960,24 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn parse_error_codes() {
+        let codes = [
+            (ParseErrorCode::IssueInIncludedFile, "L0000"),
+            (ParseErrorCode::CouldNotParse, "L0002"),
+            (ParseErrorCode::UnknownApplication, "L0003"),
+            (ParseErrorCode::UnknownFeature, "L0004"),
+            (ParseErrorCode::UnsupportedEncoding, "L0005"),
+            (ParseErrorCode::IncludeResolutionPanicked, "L0006"),
+            (ParseErrorCode::ElpMacroOverridden, "L0007"),
+        ];
+        for (code, legacy) in codes {
+            assert_eq!(code.code(), legacy);
+            assert_eq!(ParseErrorCode::from_code(legacy), Some(code));
+        }
+        assert_eq!(ParseErrorCode::from_code("L0001"), None);
+    }
+
     #[test]
     fn regular_module() {
         expect_module(
@@ -1041,6 +1137,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn maybe_expr_feature() {
+        lazy_static! {
+            static ref CONN: Connection = Connection::start().unwrap();
+        }
+        let parse = |fixture: &str, enable| {
+            let path: PathBuf = format!("fixtures/{fixture}").into();
+            let file_text = Arc::from(
+                fs::read_to_string(path.clone()).expect("Should have been able to read the file"),
+            );
+            let request = ParseRequest {
+                options: vec![CompileOption::Feature {
+                    name: "maybe_expr".to_string(),
+                    enable,
+                }],
+                file_id: FileId::from_raw(0),
+                path,
+                file_text,
+                format: Format::Text,
+            };
+            CONN.request_parse(request, || (), &|_, _, _| None)
+        };
+        let enabled = parse("maybe_expr.erl", true);
+        assert!(enabled.errors.is_empty(), "{:#?}", enabled.errors);
+        let disabled = parse("maybe_expr.erl", false);
+        assert!(!disabled.errors.is_empty());
+        // The module's own `-feature(maybe_expr, enable).` wins
+        let enabled_in_file = parse("maybe_expr_in_file.erl", false);
+        assert!(
+            enabled_in_file.errors.is_empty(),
+            "{:#?}",
+            enabled_in_file.errors
+        );
+    }
+
     fn expect_module_with_opts(path: PathBuf, expected: ExpectFile, options: Vec<CompileOption>) {
         lazy_static! {
             static ref CONN: Connection = Connection::start().unwrap();
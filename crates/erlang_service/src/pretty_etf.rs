@@ -0,0 +1,364 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Render the ASTs returned for `Format::OffsetEtf` as indented Erlang
+//! terms, for debugging. Terms fitting in `WIDTH` columns are printed on
+//! one line, others have one element per line.
+
+use std::fmt::Write;
+use std::io::Cursor;
+
+use anyhow::Context;
+use anyhow::Result;
+use eetf::Term;
+
+const WIDTH: usize = 80;
+
+const RESERVED_WORDS: &[&str] = &[
+    "after", "and", "andalso", "band", "begin", "bnot", "bor", "bsl", "bsr", "bxor", "case",
+    "catch", "cond", "div", "else", "end", "fun", "if", "let", "maybe", "not", "of", "or",
+    "orelse", "receive", "rem", "try", "when", "xor",
+];
+
+/// Decode an ETF AST and render it. The `{ok, Forms, Warnings}` returned
+/// by the erlang service is rendered as its forms, each followed by a
+/// full stop, like `Format::Text`.
+pub fn pretty_print(ast: &[u8]) -> Result<String> {
+    let term = Term::decode(Cursor::new(ast)).context("decoding ETF AST")?;
+    let mut out = String::new();
+    match &term {
+        Term::Tuple(tuple) => match tuple.elements.as_slice() {
+            [Term::Atom(ok), Term::List(forms), _] if ok.name == "ok" => {
+                for form in &forms.elements {
+                    write_term(form, 0, &mut out);
+                    out.push_str(".\n");
+                }
+            }
+            _ => {
+                write_term(&term, 0, &mut out);
+                out.push('\n');
+            }
+        },
+        _ => {
+            write_term(&term, 0, &mut out);
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+fn write_term(term: &Term, indent: usize, out: &mut String) {
+    let flat = flat_term(term);
+    if indent + flat.len() <= WIDTH {
+        out.push_str(&flat);
+        return;
+    }
+    match term {
+        Term::Tuple(tuple) => write_elements("{", &tuple.elements, None, "}", indent, out),
+        Term::List(list) => write_elements("[", &list.elements, None, "]", indent, out),
+        Term::ImproperList(list) => {
+            write_elements("[", &list.elements, Some(&list.last), "]", indent, out)
+        }
+        Term::Map(map) => {
+            out.push_str("#{\n");
+            let entries = sorted_entries(map);
+            for (idx, (key, value)) in entries.iter().enumerate() {
+                let key = flat_term(key);
+                push_indent(indent + 2, out);
+                out.push_str(&key);
+                out.push_str(" => ");
+                write_term(value, indent + 2 + key.len() + 4, out);
+                if idx + 1 < entries.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            push_indent(indent, out);
+            out.push('}');
+        }
+        _ => out.push_str(&flat),
+    }
+}
+
+fn write_elements(
+    open: &str,
+    elements: &[Term],
+    tail: Option<&Term>,
+    close: &str,
+    indent: usize,
+    out: &mut String,
+) {
+    out.push_str(open);
+    out.push('\n');
+    for (idx, element) in elements.iter().enumerate() {
+        push_indent(indent + 2, out);
+        write_term(element, indent + 2, out);
+        if idx + 1 < elements.len() {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    if let Some(tail) = tail {
+        push_indent(indent + 2, out);
+        out.push_str("| ");
+        write_term(tail, indent + 4, out);
+        out.push('\n');
+    }
+    push_indent(indent, out);
+    out.push_str(close);
+}
+
+fn push_indent(indent: usize, out: &mut String) {
+    out.extend(std::iter::repeat_n(' ', indent));
+}
+
+fn flat_term(term: &Term) -> String {
+    let mut out = String::new();
+    write_flat(term, &mut out);
+    out
+}
+
+fn write_flat(term: &Term, out: &mut String) {
+    match term {
+        Term::Atom(atom) => write_atom(&atom.name, out),
+        Term::ByteList(list) => write_string(&list.bytes, out),
+        Term::Binary(binary) => {
+            out.push_str("<<");
+            if is_printable(&binary.bytes) {
+                write_string(&binary.bytes, out);
+            } else {
+                write_separated(binary.bytes.iter(), out, |byte, out| {
+                    let _ = write!(out, "{byte}");
+                });
+            }
+            out.push_str(">>");
+        }
+        Term::Tuple(tuple) => {
+            out.push('{');
+            write_separated(tuple.elements.iter(), out, write_flat);
+            out.push('}');
+        }
+        Term::List(list) => {
+            out.push('[');
+            write_separated(list.elements.iter(), out, write_flat);
+            out.push(']');
+        }
+        Term::ImproperList(list) => {
+            out.push('[');
+            write_separated(list.elements.iter(), out, write_flat);
+            out.push('|');
+            write_flat(&list.last, out);
+            out.push(']');
+        }
+        Term::Map(map) => {
+            out.push_str("#{");
+            write_separated(sorted_entries(map).into_iter(), out, |(key, value), out| {
+                write_flat(key, out);
+                out.push_str(" => ");
+                write_flat(value, out);
+            });
+            out.push('}');
+        }
+        _ => {
+            let _ = write!(out, "{term}");
+        }
+    }
+}
+
+fn write_separated<T>(
+    items: impl Iterator<Item = T>,
+    out: &mut String,
+    mut write_item: impl FnMut(T, &mut String),
+) {
+    for (idx, item) in items.enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+        write_item(item, out);
+    }
+}
+
+/// Map entries in a stable order, as the decoded map has none.
+fn sorted_entries(map: &eetf::Map) -> Vec<(&Term, &Term)> {
+    let mut entries: Vec<(&Term, &Term)> = map.map.iter().collect();
+    entries.sort_by_cached_key(|(key, _)| flat_term(key));
+    entries
+}
+
+fn write_atom(name: &str, out: &mut String) {
+    let mut chars = name.chars();
+    let bare = chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '@')
+        && !RESERVED_WORDS.contains(&name);
+    if bare {
+        out.push_str(name);
+    } else {
+        out.push('\'');
+        for c in name.chars() {
+            match c {
+                '\'' | '\\' => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                _ => write_char(c, out),
+            }
+        }
+        out.push('\'');
+    }
+}
+
+/// Strings are lists of small integers in ETF, rendered as a string
+/// when they are printable.
+fn write_string(bytes: &[u8], out: &mut String) {
+    if !is_printable(bytes) {
+        out.push('[');
+        write_separated(bytes.iter(), out, |byte, out| {
+            let _ = write!(out, "{byte}");
+        });
+        out.push(']');
+        return;
+    }
+    out.push('"');
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            _ => write_char(byte as char, out),
+        }
+    }
+    out.push('"');
+}
+
+fn write_char(c: char, out: &mut String) {
+    match c {
+        '\n' => out.push_str("\\n"),
+        '\t' => out.push_str("\\t"),
+        '\r' => out.push_str("\\r"),
+        _ => out.push(c),
+    }
+}
+
+fn is_printable(bytes: &[u8]) -> bool {
+    bytes
+        .iter()
+        .all(|byte| byte.is_ascii_graphic() || b" \n\t\r".contains(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use eetf::Atom;
+    use eetf::ByteList;
+    use eetf::FixInteger;
+    use eetf::List;
+    use eetf::Tuple;
+    use expect_test::expect_file;
+
+    use super::*;
+
+    fn atom(name: &str) -> Term {
+        Atom::from(name).into()
+    }
+
+    fn int(value: i32) -> Term {
+        FixInteger::from(value).into()
+    }
+
+    fn tuple(elements: Vec<Term>) -> Term {
+        Tuple::from(elements).into()
+    }
+
+    fn list(elements: Vec<Term>) -> Term {
+        List::from(elements).into()
+    }
+
+    fn string(s: &str) -> Term {
+        ByteList {
+            bytes: s.as_bytes().to_vec(),
+        }
+        .into()
+    }
+
+    fn pos(start: i32, end: i32) -> Term {
+        tuple(vec![int(start), int(end)])
+    }
+
+    #[test]
+    fn tiny_module() {
+        // The forms of
+        //   -module(tiny).
+        //   -export([f/1]).
+        //   f(X) -> {ok, X}.
+        let forms = vec![
+            tuple(vec![
+                atom("attribute"),
+                pos(0, 0),
+                atom("file"),
+                tuple(vec![string("tiny.erl"), int(0)]),
+            ]),
+            tuple(vec![
+                atom("attribute"),
+                pos(0, 13),
+                atom("module"),
+                atom("tiny"),
+            ]),
+            tuple(vec![
+                atom("attribute"),
+                pos(15, 29),
+                atom("export"),
+                list(vec![tuple(vec![atom("f"), int(1)])]),
+            ]),
+            tuple(vec![
+                atom("function"),
+                pos(31, 46),
+                atom("f"),
+                int(1),
+                list(vec![tuple(vec![
+                    atom("clause"),
+                    pos(31, 46),
+                    list(vec![tuple(vec![atom("var"), pos(33, 34), atom("X")])]),
+                    list(vec![]),
+                    list(vec![tuple(vec![
+                        atom("tuple"),
+                        pos(39, 46),
+                        list(vec![
+                            tuple(vec![atom("atom"), pos(40, 42), atom("ok")]),
+                            tuple(vec![atom("var"), pos(44, 45), atom("X")]),
+                        ]),
+                    ])]),
+                ])]),
+            ]),
+            tuple(vec![atom("eof"), pos(48, 48)]),
+        ];
+        let response = tuple(vec![atom("ok"), list(forms), list(vec![])]);
+        let mut ast = Vec::new();
+        response.encode(&mut ast).unwrap();
+
+        let actual = pretty_print(&ast).unwrap();
+        expect_file!["../fixtures/pretty_etf.expected"].assert_eq(&actual);
+    }
+
+    #[test]
+    fn quoting() {
+        let term = list(vec![
+            atom("Var"),
+            atom("end"),
+            atom("it's"),
+            ByteList { bytes: vec![1, 2] }.into(),
+        ]);
+        let mut ast = Vec::new();
+        term.encode(&mut ast).unwrap();
+        assert_eq!(
+            pretty_print(&ast).unwrap(),
+            "['Var','end','it\\'s',[1,2]]\n"
+        );
+    }
+}
@@ -11,6 +11,7 @@
 use std::sync::Arc;
 
 use elp_base_db::FileId;
+use elp_base_db::ProjectId;
 use elp_base_db::RootQueryDb;
 use elp_base_db::Upcast;
 use elp_base_db::salsa;
@@ -142,6 +143,25 @@ pub trait DefDatabase:
     #[salsa::invoke(include::resolve)]
     fn resolve_include(&self, include_id: InFile<IncludeAttributeId>) -> Option<FileId>;
 
+    /// The files `file_id` includes directly, in order.
+    #[salsa::invoke(include::file_includes_query)]
+    fn file_includes(&self, file_id: FileId) -> Arc<Vec<FileId>>;
+
+    /// The include cycle closed by `include_id`, if any, as the chain of
+    /// files from the including file back to itself.
+    #[salsa::invoke(include::include_cycle_query)]
+    fn include_cycle(&self, include_id: InFile<IncludeAttributeId>) -> Option<Arc<Vec<FileId>>>;
+
+    /// For each file included in `project_id`, the files of the project
+    /// including it directly.
+    #[salsa::invoke(include::include_graph_query)]
+    fn include_graph(&self, project_id: ProjectId) -> Arc<FxHashMap<FileId, Vec<FileId>>>;
+
+    /// The modules of the project of `header` including it, directly or
+    /// through other headers, sorted.
+    #[salsa::invoke(include::modules_including_query)]
+    fn modules_including(&self, header: FileId) -> Arc<Vec<FileId>>;
+
     #[salsa::invoke(macro_exp::resolve_query)]
     fn resolve_macro(&self, file_id: FileId, name: MacroName) -> Option<ResolvedMacro>;
 
@@ -8,8 +8,15 @@
  * above-listed licenses.
  */
 
+use std::collections::VecDeque;
+use std::sync::Arc;
+
 use elp_base_db::FileId;
+use elp_base_db::FileKind;
 use elp_base_db::IncludeCtx;
+use elp_base_db::ProjectId;
+use fxhash::FxHashMap;
+use fxhash::FxHashSet;
 
 use crate::InFile;
 use crate::IncludeAttribute;
@@ -37,8 +44,97 @@ pub(crate) fn resolve(
     file_id
 }
 
+pub(crate) fn file_includes_query(db: &dyn DefDatabase, file_id: FileId) -> Arc<Vec<FileId>> {
+    let form_list = db.file_form_list(file_id);
+    let mut includes: Vec<FileId> = Vec::new();
+    for (idx, _) in form_list.includes() {
+        if let Some(included) = db.resolve_include(InFile::new(file_id, idx)) {
+            if !includes.contains(&included) {
+                includes.push(included);
+            }
+        }
+    }
+    Arc::new(includes)
+}
+
+pub(crate) fn include_cycle_query(
+    db: &dyn DefDatabase,
+    include_id: InFile<IncludeAttributeId>,
+) -> Option<Arc<Vec<FileId>>> {
+    let start = include_id.file_id;
+    let target = db.resolve_include(include_id)?;
+    // Breadth-first, so the reported cycle is a shortest one.
+    let mut parents: FxHashMap<FileId, FileId> = FxHashMap::default();
+    let mut pending = VecDeque::from([target]);
+    let mut seen = FxHashSet::from_iter([target]);
+    while let Some(file_id) = pending.pop_front() {
+        if file_id == start {
+            let mut cycle = vec![start];
+            let mut current = start;
+            while current != target {
+                current = parents[&current];
+                cycle.push(current);
+            }
+            cycle.push(start);
+            cycle.reverse();
+            return Some(Arc::new(cycle));
+        }
+        for &included in db.file_includes(file_id).iter() {
+            if included == start || seen.insert(included) {
+                parents.entry(included).or_insert(file_id);
+                pending.push_back(included);
+            }
+        }
+    }
+    None
+}
+
+pub(crate) fn include_graph_query(
+    db: &dyn DefDatabase,
+    project_id: ProjectId,
+) -> Arc<FxHashMap<FileId, Vec<FileId>>> {
+    let mut graph: FxHashMap<FileId, Vec<FileId>> = FxHashMap::default();
+    for &source_root_id in &db.project_data(project_id).project_data(db).source_roots {
+        for file_id in db.source_root(source_root_id).source_root(db).iter() {
+            let kind = db.file_kind(file_id);
+            if !(kind.is_module() || kind == FileKind::Header) {
+                continue;
+            }
+            for &included in db.file_includes(file_id).iter() {
+                graph.entry(included).or_default().push(file_id);
+            }
+        }
+    }
+    Arc::new(graph)
+}
+
+pub(crate) fn modules_including_query(db: &dyn DefDatabase, header: FileId) -> Arc<Vec<FileId>> {
+    let Some(project_id) = db.file_project_id(header) else {
+        return Arc::default();
+    };
+    let graph = db.include_graph(project_id);
+    let mut seen = FxHashSet::default();
+    let mut pending = vec![header];
+    let mut modules = Vec::new();
+    while let Some(file_id) = pending.pop() {
+        for &includer in graph.get(&file_id).into_iter().flatten() {
+            if seen.insert(includer) {
+                if db.file_kind(includer).is_module() {
+                    modules.push(includer);
+                }
+                pending.push(includer);
+            }
+        }
+    }
+    modules.sort();
+    Arc::new(modules)
+}
+
 #[cfg(test)]
 mod tests {
+    use elp_base_db::AbsPathBuf;
+    use elp_base_db::IncludeKind;
+    use elp_base_db::RootQueryDb;
     use elp_base_db::SourceDatabase;
     use elp_base_db::fixture::WithFixture;
     use expect_test::Expect;
@@ -124,4 +220,100 @@ mod tests {
             "#]],
         )
     }
+
+    #[test]
+    fn otp_lib_in_custom_root() {
+        check(
+            r#"
+//- /main/src/module.erl app:main
+-include_lib("kernel/include/file.hrl").
+//- /custom/otp/lib/kernel-9.0/include/file.hrl otp_app:/custom/otp/lib/kernel-9.0
+"#,
+            expect![[r#"
+                -include_lib("kernel/include/file.hrl"). % => /custom/otp/lib/kernel-9.0/include/file.hrl
+            "#]],
+        )
+    }
+
+    #[test]
+    fn include_search_paths() {
+        let (db, files, _) = TestDB::with_many_files(
+            r#"
+//- /app_a/src/a.erl app:app_a include_path:/app_a/include
+-module(a).
+-include("missing.hrl").
+-include_lib("app_b/include/missing.hrl").
+//- /app_b/src/b.erl app:app_b include_path:/app_b/include
+-module(b).
+//- /app_c/src/c.erl app:app_c include_path:/app_c/include
+-module(c).
+"#,
+        );
+        let ctx = IncludeCtx::new(&db, files[0]);
+        let app_a = db.file_app_data(files[0]).unwrap();
+        let app_b = db.file_app_data(files[1]).unwrap();
+        let mut expected = vec![app_a.dir.join("src")];
+        let push = |expected: &mut Vec<AbsPathBuf>, dir: &AbsPathBuf| {
+            if !expected.contains(dir) {
+                expected.push(dir.clone());
+            }
+        };
+        for dir in &app_a.include_path {
+            push(&mut expected, dir);
+        }
+
+        assert_eq!(ctx.resolve_include("missing.hrl"), None);
+        let paths = db.include_search_paths(files[0], "missing.hrl".into(), IncludeKind::Include);
+        assert_eq!(*paths, expected);
+
+        assert_eq!(ctx.resolve_include_lib("app_b/include/missing.hrl"), None);
+        push(&mut expected, &app_b.dir);
+        for dir in &app_b.include_path {
+            push(&mut expected, dir);
+        }
+        let paths = db.include_search_paths(
+            files[0],
+            "app_b/include/missing.hrl".into(),
+            IncludeKind::IncludeLib,
+        );
+        assert_eq!(*paths, expected);
+        // Only the app the path starts with is searched
+        assert!(
+            !paths.iter().any(|path| path.as_str().starts_with("/app_c")),
+            "{paths:?}"
+        );
+    }
+
+    #[test]
+    fn modules_including() {
+        let (mut db, files, _) = TestDB::with_many_files(
+            r#"
+//- /src/one.erl
+-module(one).
+-include("common.hrl").
+//- /src/two.erl
+-module(two).
+-include("nested.hrl").
+//- /src/three.erl
+-module(three).
+//- /src/nested.hrl
+-include("common.hrl").
+//- /src/common.hrl
+-define(COMMON, common).
+"#,
+        );
+        let [one, two, three, _nested, common] = files[..] else {
+            panic!("unexpected files: {files:?}");
+        };
+        assert_eq!(*db.modules_including(common), vec![one, two]);
+
+        // Invalidated when an includer changes
+        db.set_file_text(
+            three,
+            Arc::from("-module(three).\n-include(\"common.hrl\").\n"),
+        );
+        assert_eq!(*db.modules_including(common), vec![one, two, three]);
+        db.set_file_text(one, Arc::from("-module(one).\n"));
+        assert_eq!(*db.modules_including(common), vec![two, three]);
+    }
 }
@@ -32,6 +32,7 @@ use elp_base_db::SourceRoot;
 use elp_base_db::SourceRootId;
 use elp_base_db::SourceRootInput;
 use elp_base_db::Upcast;
+use elp_base_db::VfsPath;
 use elp_base_db::salsa;
 use elp_types_db::TypedSemantic;
 use elp_types_db::eqwalizer;
@@ -88,13 +89,22 @@ impl SourceDatabase for TestDB {
         files.set_source_root(self, source_root_id, source_root);
     }
 
+    fn has_source_root(&self, source_root_id: SourceRootId) -> bool {
+        self.files.has_source_root(source_root_id)
+    }
+
     fn file_source_root(&self, id: FileId) -> FileSourceRootInput {
         self.files.file_source_root(id)
     }
 
-    fn set_file_source_root(&mut self, id: FileId, source_root_id: SourceRootId) {
+    fn set_file_source_root(
+        &mut self,
+        id: FileId,
+        source_root_id: SourceRootId,
+        path: Option<VfsPath>,
+    ) {
         let files = self.files.clone();
-        files.set_file_source_root(self, id, source_root_id);
+        files.set_file_source_root(self, id, source_root_id, path);
     }
 
     fn app_data_by_id(&self, id: AppDataId) -> AppDataInput {
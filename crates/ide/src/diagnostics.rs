@@ -11,10 +11,14 @@
 use std::collections::BTreeSet;
 use std::fmt;
 use std::sync::Arc;
+use std::task::Poll;
+use std::time::Instant;
 
 use anyhow::Result;
 use anyhow::bail;
 use elp_eqwalizer::EqwalizerDiagnostic;
+use elp_eqwalizer::db::DeadlineDiagnostics;
+use elp_eqwalizer::db::module_diagnostics_deadline;
 use elp_ide_assists::AssistConfig;
 use elp_ide_assists::AssistId;
 use elp_ide_assists::AssistKind;
@@ -38,6 +42,7 @@ use elp_ide_db::elp_base_db::FileKind;
 use elp_ide_db::elp_base_db::FileRange;
 use elp_ide_db::elp_base_db::ProjectId;
 use elp_ide_db::elp_base_db::RootQueryDb;
+use elp_ide_db::eqwalizer::eqwalizer_errors;
 use elp_ide_db::erlang_service::DiagnosticLocation;
 use elp_ide_db::erlang_service::ParseError;
 use elp_ide_db::metadata::Kind;
@@ -76,6 +81,7 @@ use serde::Serialize;
 use crate::RootDatabase;
 use crate::common_test;
 
+mod ambiguous_include_lib;
 mod application_env;
 mod atoms_exhaustion;
 mod binary_string_to_sigil;
@@ -93,6 +99,7 @@ mod expression_can_be_simplified;
 mod from_config;
 mod head_mismatch;
 mod helpers;
+mod include_cycle;
 mod inefficient_enumerate;
 mod inefficient_flatlength;
 mod inefficient_last;
@@ -126,6 +133,7 @@ mod undocumented_module;
 mod unnecessary_fold_to_build_map;
 mod unnecessary_map_from_list_around_comprehension;
 mod unnecessary_map_to_list_in_comprehension;
+mod unresolved_otp_include_lib;
 mod unspecific_include;
 mod unused_function_args;
 mod unused_include;
@@ -758,30 +766,21 @@ pub fn eqwalizer_to_diagnostic(
     d: &EqwalizerDiagnostic,
     eqwalizer_enabled: bool,
 ) -> Diagnostic {
-    let range = d.range;
+    // The range is computed from the file eqWAlizer last read, so may
+    // extend beyond the end of the file.
+    let range = sema.db.file_line_index(file_id).clamp(d.range);
     let severity = if eqwalizer_enabled {
         Severity::Error
     } else {
         Severity::Information
     };
-    let explanation = match &d.explanation {
-        Some(s) => format!("\n\n{s}"),
-        None => "".to_string(),
-    };
-    let message = format!(
-        "{}{}{}\n        See {}",
-        d.expr_string(),
-        d.message,
-        explanation,
-        d.uri
-    );
     let mut diagnostic = Diagnostic {
         range,
         severity,
         cli_severity: None,
         tag: DiagnosticTag::None,
         code: DiagnosticCode::Eqwalizer(d.code.clone()),
-        message,
+        message: d.full_message(),
         categories: FxHashSet::default(),
         fixes: None,
         related_info: None,
@@ -921,6 +920,9 @@ pub fn diagnostics_descriptors<'a>() -> Vec<&'a DiagnosticDescriptor<'a>> {
         &binary_string_to_sigil::DESCRIPTOR,
         &no_catch::DESCRIPTOR,
         &no_error_logger::DESCRIPTOR,
+        &ambiguous_include_lib::DESCRIPTOR,
+        &include_cycle::DESCRIPTOR,
+        &unresolved_otp_include_lib::DESCRIPTOR,
     ]
 }
 
@@ -1387,6 +1389,41 @@ pub fn eqwalizer_diagnostics(db: &RootDatabase, file_id: FileId) -> Option<Vec<D
     )
 }
 
+/// Like `eqwalizer_diagnostics`, but `Poll::Pending` when the module
+/// is not checked by `deadline`, see `module_diagnostics_deadline`.
+pub fn eqwalizer_diagnostics_by_deadline(
+    db: &RootDatabase,
+    file_id: FileId,
+    deadline: Instant,
+) -> Poll<Option<Vec<Diagnostic>>> {
+    let Some(app_data) = db.file_app_data(file_id) else {
+        return Poll::Ready(None);
+    };
+    let project_id = app_data.project_id;
+    let Some(module) = db
+        .module_index(project_id)
+        .module_for_file(file_id)
+        .cloned()
+    else {
+        return Poll::Ready(None);
+    };
+    if !db.is_eqwalizer_enabled(file_id, false) {
+        return Poll::Ready(Some(vec![]));
+    }
+    match module_diagnostics_deadline(db, project_id, module.as_str().to_string(), deadline) {
+        DeadlineDiagnostics::Ready(diagnostics) => {
+            let sema = Semantic::new(db);
+            Poll::Ready(Some(
+                eqwalizer_errors(&module, &diagnostics)
+                    .iter()
+                    .map(|d| eqwalizer_to_diagnostic(&sema, file_id, d, true))
+                    .collect(),
+            ))
+        }
+        DeadlineDiagnostics::Pending => Poll::Pending,
+    }
+}
+
 pub fn to_standard_diagnostics(
     db: &RootDatabase,
     project_id: ProjectId,
@@ -1447,13 +1484,12 @@ pub fn edoc_diagnostics(
 
     // If the file cannot be parsed, it does not really make sense to run EDoc,
     // so let's return early.
-    let ast = db.module_ast(file_id);
+    let (ast, line_index) = db.module_ast_with_lines(file_id);
     if !ast.is_ok() {
         return vec![];
     };
 
     let res = db.file_doc(file_id);
-    let line_index = db.file_line_index(file_id);
 
     res.diagnostics.iter().for_each(|d| {
         // While line number in EDoc diagnostics are 1 based,
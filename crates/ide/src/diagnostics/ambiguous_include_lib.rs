@@ -0,0 +1,110 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+// Diagnostic: ambiguous-include-lib
+//
+// Report an `-include_lib` naming an application that is available in
+// several versions, none of which the including application is built
+// against, so that one had to be picked.
+
+use elp_ide_db::DiagnosticCode;
+use elp_ide_db::elp_base_db::FileId;
+use elp_ide_db::elp_base_db::IncludeCtx;
+use hir::IncludeAttribute;
+use hir::Semantic;
+
+use super::Diagnostic;
+use super::DiagnosticConditions;
+use super::DiagnosticDescriptor;
+use super::Severity;
+
+pub(crate) static DESCRIPTOR: DiagnosticDescriptor = DiagnosticDescriptor {
+    conditions: DiagnosticConditions {
+        experimental: false,
+        include_generated: false,
+        include_tests: true,
+        default_disabled: false,
+    },
+    checker: &|diags, sema, file_id, _file_kind| {
+        check_include_libs(diags, sema, file_id);
+    },
+};
+
+fn check_include_libs(acc: &mut Vec<Diagnostic>, sema: &Semantic, file_id: FileId) {
+    let form_list = sema.form_list(file_id);
+    let ctx = IncludeCtx::new(sema.db.upcast(), file_id);
+    for (_idx, inc) in form_list.includes() {
+        if let IncludeAttribute::IncludeLib { path, form_id, .. } = inc {
+            let Some(app_dir) = ctx.guessed_include_lib_app(path) else {
+                continue;
+            };
+            let source_file = sema.parse(file_id);
+            let Some(range) = form_id.get(&source_file.value).include_range() else {
+                continue;
+            };
+            let app_name = path.split('/').next().unwrap_or_default();
+            let message = format!(
+                "Several versions of application '{app_name}' are available, using the one in {app_dir}."
+            );
+            acc.push(
+                Diagnostic::new(DiagnosticCode::AmbiguousIncludeLib, message, range)
+                    .with_severity(Severity::Information),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elp_ide_db::DiagnosticCode;
+
+    use crate::diagnostics::Diagnostic;
+    use crate::tests;
+
+    fn filter(d: &Diagnostic) -> bool {
+        d.code == DiagnosticCode::AmbiguousIncludeLib
+    }
+
+    #[track_caller]
+    fn check_diagnostics(fixture: &str) {
+        tests::check_filtered_diagnostics(fixture, &filter)
+    }
+
+    #[test]
+    fn guesses_latest_version() {
+        check_diagnostics(
+            r#"
+         //- /src/main.erl
+           -module(main).
+           -include_lib("foo/include/foo.hrl").
+           %%           ^^^^^^^^^^^^^^^^^^^^^ information: Several versions of application 'foo' are available, using the one in /opt/lib/foo-2.0.
+         //- /opt/lib/foo-1.0/include/foo.hrl otp_app:/opt/lib/foo-1.0
+           -define(FOO, 1).
+         //- /opt/lib/foo-2.0/include/foo.hrl otp_app:/opt/lib/foo-2.0
+           -define(FOO, 2).
+            "#,
+        )
+    }
+
+    #[test]
+    fn uses_pinned_version() {
+        check_diagnostics(
+            r#"
+         //- /src/main.erl include_path:/opt/lib/foo-1.0/include
+           -module(main).
+           -include_lib("foo/include/foo.hrl").
+         //- /opt/lib/foo-1.0/include/foo.hrl otp_app:/opt/lib/foo-1.0
+           -define(FOO, 1).
+         //- /opt/lib/foo-2.0/include/foo.hrl otp_app:/opt/lib/foo-2.0
+           -define(FOO, 2).
+            "#,
+        )
+    }
+}
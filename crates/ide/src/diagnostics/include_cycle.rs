@@ -0,0 +1,126 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+// Diagnostic: include-cycle
+//
+// Report an `-include` or `-include_lib` of a file that, directly or through
+// other headers, includes the file containing the attribute.
+
+use elp_ide_db::DiagnosticCode;
+use elp_ide_db::elp_base_db::FileId;
+use hir::InFile;
+use hir::IncludeAttribute;
+use hir::Semantic;
+use itertools::Itertools;
+
+use super::Diagnostic;
+use super::DiagnosticConditions;
+use super::DiagnosticDescriptor;
+
+pub(crate) static DESCRIPTOR: DiagnosticDescriptor = DiagnosticDescriptor {
+    conditions: DiagnosticConditions {
+        experimental: false,
+        include_generated: false,
+        include_tests: true,
+        default_disabled: false,
+    },
+    checker: &|diags, sema, file_id, _file_kind| {
+        check_includes(diags, sema, file_id);
+    },
+};
+
+fn check_includes(acc: &mut Vec<Diagnostic>, sema: &Semantic, file_id: FileId) {
+    let form_list = sema.form_list(file_id);
+    for (idx, inc) in form_list.includes() {
+        let Some(cycle) = sema.db.include_cycle(InFile::new(file_id, idx)) else {
+            continue;
+        };
+        let source_file = sema.parse(file_id);
+        let range = match inc {
+            IncludeAttribute::Include { form_id, .. } => {
+                form_id.get(&source_file.value).include_range()
+            }
+            IncludeAttribute::IncludeLib { form_id, .. } => {
+                form_id.get(&source_file.value).include_range()
+            }
+        };
+        let Some(range) = range else {
+            continue;
+        };
+        let path = cycle
+            .iter()
+            .map(|&file_id| file_name(sema, file_id))
+            .join(" -> ");
+        acc.push(Diagnostic::new(
+            DiagnosticCode::IncludeCycle,
+            format!("Include cycle: {path}"),
+            range,
+        ));
+    }
+}
+
+fn file_name(sema: &Semantic, file_id: FileId) -> String {
+    let root_id = sema.db.file_source_root(file_id).source_root_id(sema.db);
+    let root = sema.db.source_root(root_id).source_root(sema.db);
+    match root
+        .path_for_file(&file_id)
+        .and_then(|path| path.name_and_extension())
+    {
+        Some((name, Some(ext))) => format!("{name}.{ext}"),
+        Some((name, None)) => name.to_string(),
+        None => format!("{file_id:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elp_ide_db::DiagnosticCode;
+
+    use crate::diagnostics::Diagnostic;
+    use crate::tests;
+
+    fn filter(d: &Diagnostic) -> bool {
+        d.code == DiagnosticCode::IncludeCycle
+    }
+
+    #[track_caller]
+    fn check_diagnostics(fixture: &str) {
+        tests::check_filtered_diagnostics(fixture, &filter)
+    }
+
+    #[test]
+    fn two_header_cycle() {
+        check_diagnostics(
+            r#"
+         //- /include/a.hrl
+           -include("b.hrl").
+           %%       ^^^^^^^ error: Include cycle: a.hrl -> b.hrl -> a.hrl
+           -define(A, 1).
+         //- /include/b.hrl
+           -include("a.hrl").
+           %%       ^^^^^^^ error: Include cycle: b.hrl -> a.hrl -> b.hrl
+           -define(B, 2).
+            "#,
+        )
+    }
+
+    #[test]
+    fn no_cycle() {
+        check_diagnostics(
+            r#"
+         //- /include/a.hrl
+           -include("b.hrl").
+           -define(A, 1).
+         //- /include/b.hrl
+           -define(B, 2).
+            "#,
+        )
+    }
+}
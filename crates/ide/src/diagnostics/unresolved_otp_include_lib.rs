@@ -0,0 +1,103 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+// Diagnostic: unresolved-otp-include-lib
+//
+// Report an `-include_lib` of a header of an OTP application that the
+// OTP installation in use, e.g. the one configured as `otp_root`, does
+// not have.
+
+use elp_ide_db::DiagnosticCode;
+use elp_ide_db::elp_base_db::FileId;
+use elp_ide_db::elp_base_db::IncludeCtx;
+use hir::IncludeAttribute;
+use hir::Semantic;
+
+use super::Diagnostic;
+use super::DiagnosticConditions;
+use super::DiagnosticDescriptor;
+use super::Severity;
+
+pub(crate) static DESCRIPTOR: DiagnosticDescriptor = DiagnosticDescriptor {
+    conditions: DiagnosticConditions {
+        experimental: false,
+        include_generated: false,
+        include_tests: true,
+        default_disabled: false,
+    },
+    checker: &|diags, sema, file_id, _file_kind| {
+        check_include_libs(diags, sema, file_id);
+    },
+};
+
+fn check_include_libs(acc: &mut Vec<Diagnostic>, sema: &Semantic, file_id: FileId) {
+    let form_list = sema.form_list(file_id);
+    let ctx = IncludeCtx::new(sema.db.upcast(), file_id);
+    for (_idx, inc) in form_list.includes() {
+        if let IncludeAttribute::IncludeLib { path, form_id, .. } = inc {
+            let Some(app_dir) = ctx.unresolved_otp_include_lib(path) else {
+                continue;
+            };
+            let source_file = sema.parse(file_id);
+            let Some(range) = form_id.get(&source_file.value).include_range() else {
+                continue;
+            };
+            let message = format!("Can't find '{path}' in the OTP application in {app_dir}.");
+            acc.push(
+                Diagnostic::new(DiagnosticCode::UnresolvedOtpIncludeLib, message, range)
+                    .with_severity(Severity::Warning),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elp_ide_db::DiagnosticCode;
+
+    use crate::diagnostics::Diagnostic;
+    use crate::tests;
+
+    fn filter(d: &Diagnostic) -> bool {
+        d.code == DiagnosticCode::UnresolvedOtpIncludeLib
+    }
+
+    #[track_caller]
+    fn check_diagnostics(fixture: &str) {
+        tests::check_filtered_diagnostics(fixture, &filter)
+    }
+
+    #[test]
+    fn missing_header_in_custom_otp_root() {
+        check_diagnostics(
+            r#"
+         //- /src/main.erl
+           -module(main).
+           -include_lib("kernel/include/file.hrl").
+           -include_lib("kernel/include/logger.hrl").
+           %%           ^^^^^^^^^^^^^^^^^^^^^^^^^^^ warning: Can't find 'kernel/include/logger.hrl' in the OTP application in /custom/otp/lib/kernel-9.0.
+         //- /custom/otp/lib/kernel-9.0/include/file.hrl otp_app:/custom/otp/lib/kernel-9.0
+           -record(file_info, {size}).
+            "#,
+        )
+    }
+
+    #[test]
+    fn non_otp_app_not_reported() {
+        check_diagnostics(
+            r#"
+         //- /main/src/main.erl app:main
+           -module(main).
+           -include_lib("another/include/missing.hrl").
+         //- /another-app/include/header.hrl app:another
+            "#,
+        )
+    }
+}
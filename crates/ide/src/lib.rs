@@ -10,6 +10,8 @@
 
 use std::error::Error;
 use std::sync::Arc;
+use std::task::Poll;
+use std::time::Instant;
 
 use anyhow::Result;
 use call_hierarchy::CallItem;
@@ -20,6 +22,9 @@ use diagnostics::LabeledDiagnostics;
 use diagnostics::RemoveElpReported;
 use diagnostics_collection::DiagnosticCollection;
 use elp_eqwalizer::ast::Pos;
+use elp_eqwalizer::db::ELPDbApi;
+use elp_eqwalizer::db::prefetch_erl_ast;
+use elp_eqwalizer::progress::EqwalizerProgress;
 use elp_ide_assists::Assist;
 use elp_ide_assists::AssistConfig;
 use elp_ide_assists::AssistId;
@@ -57,6 +62,7 @@ use elp_ide_db::rename::RenameError;
 use elp_ide_db::source_change::SourceChange;
 use elp_project_model::AppName;
 use elp_project_model::AppType;
+use elp_project_model::EqwalizerConfig;
 use elp_syntax::AstNode;
 use elp_syntax::SmolStr;
 use elp_syntax::algo::ancestors_at_offset;
@@ -65,6 +71,7 @@ use elp_syntax::label::Label;
 use elp_types_db::eqwalizer;
 use elp_types_db::eqwalizer::types::Type;
 use expand_macro::ExpandedMacro;
+use fxhash::FxHashMap;
 use handlers::get_docs;
 use handlers::goto_definition;
 use handlers::goto_type_definition;
@@ -75,7 +82,11 @@ use hir::FormList;
 use hir::Module;
 use hir::Semantic;
 use hir::db::DefDatabase;
+use lazy_static::lazy_static;
 use navigation_target::ToNav;
+use parking_lot::Mutex;
+use rayon::ThreadPool;
+use rayon::ThreadPoolBuilder;
 use rayon::prelude::*;
 
 mod annotations;
@@ -246,38 +257,53 @@ impl Analysis {
         self.with_db(|db| diagnostics::eqwalizer_diagnostics(db, file_id))
     }
 
+    /// Like `eqwalizer_diagnostics_for_file`, but `Poll::Pending` when
+    /// not computed by `deadline`, in which case the computation goes on
+    /// in the background for a later call to pick up.
+    pub fn eqwalizer_diagnostics_for_file_by_deadline(
+        &self,
+        file_id: FileId,
+        deadline: Instant,
+    ) -> Cancellable<Poll<Option<Vec<Diagnostic>>>> {
+        self.with_db(|db| diagnostics::eqwalizer_diagnostics_by_deadline(db, file_id, deadline))
+    }
+
     /// Computes the set of eqwalizer diagnostics for the given project and files,
     /// including checking for disabled. Returns standard diagnostics.
+    /// The percentage of the files checked is reported to `on_progress`.
     #[allow(clippy::type_complexity)]
     pub fn eqwalizer_diagnostics_by_project(
         &self,
         project_id: ProjectId,
         file_ids: Vec<FileId>,
         max_tasks: usize,
+        on_progress: impl Fn(u32) + Send + Sync + 'static,
     ) -> Cancellable<Option<Vec<(FileId, Vec<Diagnostic>)>>> {
-        self.with_db(|db| {
-            let files_count = file_ids.len();
-            let chunk_size = files_count.div_ceil(max_tasks);
-            if chunk_size == 0 {
-                // The chunks function panics if the chunk size is 0, so we return an empty array
-                return Some(Vec::new());
-            }
-            let diagnostics = file_ids
-                .chunks(chunk_size)
-                .par_bridge()
-                .map_with(self.clone(), move |analysis, file_ids| {
-                    analysis
-                        .eqwalizer_diagnostics(project_id, file_ids.to_vec())
-                        .unwrap_or(Arc::new(EqwalizerDiagnostics::default()))
-                })
-                .fold(EqwalizerDiagnostics::default, |acc, output| {
-                    acc.combine((*output).clone())
-                })
-                .reduce(EqwalizerDiagnostics::default, |acc, other| {
-                    acc.combine(other)
-                });
-            diagnostics::to_standard_diagnostics(db, project_id, diagnostics)
-        })
+        let files_count = file_ids.len();
+        let chunk_size = files_count.div_ceil(max_tasks);
+        if chunk_size == 0 {
+            // The chunks function panics if the chunk size is 0, so we return an empty array
+            return Ok(Some(Vec::new()));
+        }
+        let eqwalizer_config = self.with_db(|db| {
+            db.project_data(project_id)
+                .project_data(db)
+                .eqwalizer_config
+                .clone()
+        })?;
+        let diagnostics = eqwalize_in_parallel(
+            &eqwalizer_config,
+            self.clone(),
+            &file_ids,
+            chunk_size,
+            on_progress,
+            |analysis, file_ids| {
+                analysis
+                    .eqwalizer_diagnostics(project_id, file_ids.to_vec())
+                    .unwrap_or(Arc::new(EqwalizerDiagnostics::default()))
+            },
+        );
+        self.with_db(|db| diagnostics::to_standard_diagnostics(db, project_id, diagnostics))
     }
 
     /// Computes the set of eqwalizer diagnostics for the given files,
@@ -292,6 +318,31 @@ impl Analysis {
         })
     }
 
+    /// Read the ASTs of the modules in `file_ids` in parallel, see
+    /// `prefetch_erl_ast`.
+    pub fn prefetch_erl_ast(&self, file_ids: &[FileId]) -> Cancellable<()> {
+        self.with_db(|db| {
+            let mut modules: FxHashMap<ProjectId, Vec<ModuleName>> = FxHashMap::default();
+            for &file_id in file_ids {
+                let Some(app_data) = db.file_app_data(file_id) else {
+                    continue;
+                };
+                if let Some(module) = db
+                    .module_index(app_data.project_id)
+                    .module_for_file(file_id)
+                {
+                    modules
+                        .entry(app_data.project_id)
+                        .or_default()
+                        .push(module.clone());
+                }
+            }
+            for (project_id, modules) in modules {
+                prefetch_erl_ast(db, project_id, &modules);
+            }
+        })
+    }
+
     pub fn eqwalizer_stats(
         &self,
         project_id: ProjectId,
@@ -795,6 +846,82 @@ impl Clone for Analysis {
     }
 }
 
+/// Check `file_ids` with `check`, in chunks of `chunk_size`, in parallel
+/// on the eqWAlizer thread pool, so that no more than
+/// `config.check_parallelism()` chunks are checked at the same time.
+/// The ASTs of the modules are all read first, see `prefetch_erl_ast`.
+/// The percentage of the modules checked so far is reported to
+/// `on_progress`, see `EqwalizerProgress`.
+pub fn eqwalize_in_parallel(
+    config: &EqwalizerConfig,
+    analysis: Analysis,
+    file_ids: &[FileId],
+    chunk_size: usize,
+    on_progress: impl Fn(u32) + Send + Sync + 'static,
+    check: impl Fn(&Analysis, &[FileId]) -> Arc<EqwalizerDiagnostics> + Send + Sync,
+) -> EqwalizerDiagnostics {
+    // If cancelled, so are the checks below
+    let _ = analysis.prefetch_erl_ast(file_ids);
+    let progress = EqwalizerProgress::new(file_ids.len(), on_progress);
+    analysis
+        .db
+        .set_eqwalizing_progress(Some(Arc::new(progress)));
+    // Reset even if a check panics, e.g. when cancelled
+    let _reset = ResetEqwalizingProgress(analysis.clone());
+    let run = || {
+        file_ids
+            .chunks(chunk_size)
+            .par_bridge()
+            .map_with(analysis, |analysis, file_ids| check(analysis, file_ids))
+            .fold(EqwalizerDiagnostics::default, |acc, output| {
+                acc.combine((*output).clone())
+            })
+            .reduce(EqwalizerDiagnostics::default, |acc, other| {
+                acc.combine(other)
+            })
+    };
+    match check_pool(config.check_parallelism()) {
+        Some(pool) => pool.install(run),
+        None => run(),
+    }
+}
+
+lazy_static! {
+    /// The pools used by `eqwalize_in_parallel`, by number of threads,
+    /// so that each is only built once.
+    static ref CHECK_POOLS: Mutex<FxHashMap<usize, Arc<ThreadPool>>> = Mutex::default();
+}
+
+struct ResetEqwalizingProgress(Analysis);
+
+impl Drop for ResetEqwalizingProgress {
+    fn drop(&mut self) {
+        self.0.db.set_eqwalizing_progress(None)
+    }
+}
+
+fn check_pool(num_threads: usize) -> Option<Arc<ThreadPool>> {
+    let mut pools = CHECK_POOLS.lock();
+    if let Some(pool) = pools.get(&num_threads) {
+        return Some(pool.clone());
+    }
+    match ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .thread_name(|idx| format!("eqwalizer-{idx}"))
+        .build()
+    {
+        Ok(pool) => {
+            let pool = Arc::new(pool);
+            pools.insert(num_threads, pool.clone());
+            Some(pool)
+        }
+        Err(err) => {
+            log::warn!("Could not create the eqWAlizer thread pool: {err}");
+            None
+        }
+    }
+}
+
 pub fn is_cancelled(e: &(dyn Error + 'static)) -> bool {
     e.downcast_ref::<salsa::Cancelled>().is_some()
 }
@@ -19,6 +19,8 @@ use elp_ide_db::SymbolKind;
 use elp_ide_db::elp_base_db::FileId;
 use elp_syntax::AstNode;
 use elp_syntax::NodeOrToken;
+use elp_syntax::SyntaxKind;
+use elp_syntax::SyntaxNode;
 use elp_syntax::TextRange;
 use elp_syntax::ast;
 use elp_types_db::eqwalizer::types::Type;
@@ -54,7 +56,9 @@ pub struct HighlightConfig {}
 //
 // ELP highlights some code semantically.
 //
-// Initially this is just used for bound variables in patterns
+// Initially this is just used for bound variables in patterns. Quoted
+// atoms, the names of macro calls and keyword operators such as `band`
+// and `bsl` are highlighted too.
 
 pub(crate) fn highlight(
     db: &RootDatabase,
@@ -82,6 +86,9 @@ pub(crate) fn highlight(
     };
 
     let mut hl = highlights::Highlights::new(root.text_range());
+    // Added first, so that the semantic highlights of the same ranges
+    // nest inside, and take precedence over, these.
+    tokens_highlight(&root, range_to_highlight, &mut hl);
     bound_vars_in_pattern_highlight(&sema, file_id, range_to_highlight, &mut hl);
     functions_highlight(&sema, file_id, range_to_highlight, &mut hl);
     types_highlight(&sema, file_id, range_to_highlight, &mut hl);
@@ -90,6 +97,59 @@ pub(crate) fn highlight(
     hl.to_vec()
 }
 
+/// Highlight quoted atoms, macro calls and keyword operators, which need
+/// no semantic information.
+fn tokens_highlight(root: &SyntaxNode, range_to_highlight: TextRange, hl: &mut Highlights) {
+    for element in root.descendants_with_tokens() {
+        let mut range = element.text_range();
+        if range_to_highlight.intersect(range).is_none() {
+            continue;
+        }
+        let tag = match element {
+            NodeOrToken::Node(node) => {
+                if let Some(call) = ast::MacroCallExpr::cast(node.clone()) {
+                    // Only `?NAME`: the arguments are highlighted as
+                    // any other code.
+                    let Some(name) = call.name() else {
+                        continue;
+                    };
+                    range = TextRange::new(range.start(), name.syntax().text_range().end());
+                    HlTag::Symbol(SymbolKind::Define)
+                } else {
+                    match ast::Atom::cast(node) {
+                        Some(atom) if atom.syntax().text().char_at(0.into()) == Some('\'') => {
+                            HlTag::QuotedAtom
+                        }
+                        _ => continue,
+                    }
+                }
+            }
+            NodeOrToken::Token(token) => match token.kind() {
+                SyntaxKind::ANON_BAND
+                | SyntaxKind::ANON_BOR
+                | SyntaxKind::ANON_BXOR
+                | SyntaxKind::ANON_BNOT
+                | SyntaxKind::ANON_BSL
+                | SyntaxKind::ANON_BSR
+                | SyntaxKind::ANON_DIV
+                | SyntaxKind::ANON_REM
+                | SyntaxKind::ANON_AND
+                | SyntaxKind::ANON_OR
+                | SyntaxKind::ANON_XOR
+                | SyntaxKind::ANON_NOT
+                | SyntaxKind::ANON_ANDALSO
+                | SyntaxKind::ANON_ORELSE => HlTag::Operator,
+                _ => continue,
+            },
+        };
+        hl.add(HlRange {
+            range,
+            highlight: tag.into(),
+            binding_hash: None,
+        });
+    }
+}
+
 fn bound_vars_in_pattern_highlight(
     sema: &Semantic,
     file_id: FileId,
@@ -225,45 +285,37 @@ fn functions_highlight(
 ) {
     let def_map = sema.def_map_local(file_id);
     for (_, def) in def_map.get_functions() {
-        if def.exported || def.deprecated {
-            let fun_decl_ast = def.source(sema.db.upcast());
-
-            fun_decl_ast
-                .iter()
-                .for_each(|fun_clause| match fun_clause.clause() {
-                    Some(ast::FunctionOrMacroClause::FunctionClause(clause)) => {
-                        if let Some(n) = clause.name() {
-                            let range = n.syntax().text_range();
-
-                            let highlight = match (def.exported, def.deprecated) {
-                                (true, true) => {
-                                    HlTag::Symbol(SymbolKind::Function)
-                                        | HlMod::ExportedFunction
-                                        | HlMod::DeprecatedFunction
-                                }
-                                (false, true) => {
-                                    HlTag::Symbol(SymbolKind::Function) | HlMod::DeprecatedFunction
-                                }
-                                (true, false) => {
-                                    HlTag::Symbol(SymbolKind::Function) | HlMod::ExportedFunction
-                                }
-                                (false, false) => unreachable!("checked above"),
-                            };
+        let fun_decl_ast = def.source(sema.db.upcast());
 
-                            // Element inside the viewport, need to highlight
-                            if range_to_highlight.intersect(range).is_some() {
-                                hl.add(HlRange {
-                                    range,
-                                    highlight,
-                                    binding_hash: None,
-                                })
-                            }
-                        };
-                    }
-                    Some(ast::FunctionOrMacroClause::MacroCallExpr(_)) => {}
-                    None => {}
-                })
+        // The name in every clause head is a definition, calls are not.
+        let mut highlight = HlTag::Symbol(SymbolKind::Function) | HlMod::Definition;
+        if def.exported {
+            highlight |= HlMod::ExportedFunction;
         }
+        if def.deprecated {
+            highlight |= HlMod::DeprecatedFunction;
+        }
+
+        fun_decl_ast
+            .iter()
+            .for_each(|fun_clause| match fun_clause.clause() {
+                Some(ast::FunctionOrMacroClause::FunctionClause(clause)) => {
+                    if let Some(n) = clause.name() {
+                        let range = n.syntax().text_range();
+
+                        // Element inside the viewport, need to highlight
+                        if range_to_highlight.intersect(range).is_some() {
+                            hl.add(HlRange {
+                                range,
+                                highlight,
+                                binding_hash: None,
+                            })
+                        }
+                    };
+                }
+                Some(ast::FunctionOrMacroClause::MacroCallExpr(_)) => {}
+                None => {}
+            })
     }
 }
 
@@ -275,28 +327,29 @@ fn types_highlight(
 ) {
     let def_map = sema.def_map_local(file_id);
     for def in def_map.get_types().values() {
-        if def.exported {
-            let type_alias_source = def.source(sema.db.upcast());
+        let type_alias_source = def.source(sema.db.upcast());
 
-            if let Some(type_name) = type_alias_source.type_name() {
-                if let Some(name) = type_name.name() {
-                    let range = name.syntax().text_range();
+        if let Some(type_name) = type_alias_source.type_name() {
+            if let Some(name) = type_name.name() {
+                let range = name.syntax().text_range();
 
-                    // The base highlighing currently uses SymbolKind::Function.
-                    // We cannot set a modifier only. so must repeat it here.
-                    let highlight = HlTag::Symbol(SymbolKind::Function) | HlMod::ExportedType;
+                // The base highlighing currently uses SymbolKind::Function.
+                // We cannot set a modifier only. so must repeat it here.
+                let mut highlight = HlTag::Symbol(SymbolKind::Function) | HlMod::Definition;
+                if def.exported {
+                    highlight |= HlMod::ExportedType;
+                }
 
-                    // Element inside the viewport, need to highlight
-                    if range_to_highlight.intersect(range).is_some() {
-                        hl.add(HlRange {
-                            range,
-                            highlight,
-                            binding_hash: None,
-                        })
-                    }
+                // Element inside the viewport, need to highlight
+                if range_to_highlight.intersect(range).is_some() {
+                    hl.add(HlRange {
+                        range,
+                        highlight,
+                        binding_hash: None,
+                    })
                 }
-            };
-        }
+            }
+        };
     }
 }
 
@@ -388,7 +441,11 @@ mod tests {
             .filter(|h| h.highlight != HlTag::None.into()) // Means with no modifiers either
             .map(|h| {
                 let mods: Vec<_> = h.highlight.mods.iter().map(|m| format!("{m}")).collect();
-                (h.range, mods.join(","))
+                if mods.is_empty() {
+                    (h.range, h.highlight.tag.to_string())
+                } else {
+                    (h.range, mods.join(","))
+                }
             })
             .sorted_by(|a, b| a.0.start().cmp(&b.0.start()))
             .collect();
@@ -400,6 +457,7 @@ mod tests {
         check_highlights(
             r#"
               f(Var1) ->
+           %% ^definition
                 Var1 = 1.
            %%   ^^^^bound "#,
         )
@@ -411,7 +469,7 @@ mod tests {
             r#"
               -export([f/1]).
               f(Var1) ->
-           %% ^exported_function
+           %% ^exported_function,definition
                 Var1 = 1.
            %%   ^^^^bound "#,
         )
@@ -424,11 +482,11 @@ mod tests {
               -deprecated([{f, 1}, {g, 1}]).
               -export([g/1]).
               f(1) -> 1;
-           %% ^deprecated_function
+           %% ^deprecated_function,definition
               f(2) -> 2.
-           %% ^deprecated_function
+           %% ^deprecated_function,definition
               g(3) -> 3.
-           %% ^exported_function,deprecated_function"#,
+           %% ^exported_function,deprecated_function,definition"#,
         )
     }
 
@@ -440,8 +498,9 @@ mod tests {
               -module(deprecated_highlight).
               -deprecated([{f, 1}]).
               f(1) -> 1.
-           %% ^deprecated_function
+           %% ^deprecated_function,definition
               ga(Num) -> f(Num).
+           %% ^^definition
            %%            ^deprecated_function"#,
         )
     }
@@ -475,20 +534,84 @@ mod tests {
               -module(a_file).  
               -spec f(dynamic()) -> ok.
               f(AAA) -> ok.
+            %%^definition
             %%  ^^^type_dynamic
               "#,
             )
         }
     }
 
+    #[test]
+    fn eqwalizer_dynamic_highlight_without_types() {
+        // No type info for files eqWAlizer doesn't check, so no modifier
+        check_highlights_eqwalizer(
+            r#"
+            //- /app_a/src/a_file.erl
+              -module(a_file).
+              -spec f(dynamic()) -> ok.
+              f(AAA) -> ok.
+            %%^definition
+              "#,
+        )
+    }
+
     #[test]
     fn exported_type() {
         check_highlights(
             r#"
               -export_type([foo/0]).
               -type foo() :: integer().
-           %%       ^^^exported_type
+           %%       ^^^exported_type,definition
               -type bar() :: integer().
+           %%       ^^^definition
+              "#,
+        )
+    }
+
+    #[test]
+    fn definition_not_call() {
+        check_highlights(
+            r#"
+              f() -> g().
+           %% ^definition
+              g() -> ok.
+           %% ^definition
+              "#,
+        )
+    }
+
+    #[test]
+    fn quoted_atom() {
+        check_highlights(
+            r#"
+              f() -> {'Foo bar', foo}.
+           %% ^definition
+           %%         ^^^^^^^^^quoted_atom
+              "#,
+        )
+    }
+
+    #[test]
+    fn bit_shift_operator() {
+        check_highlights(
+            r#"
+              f(X) -> (X bsl 2) band 16#ff.
+           %% ^definition
+           %%            ^^^operator
+           %%                   ^^^^operator
+              "#,
+        )
+    }
+
+    #[test]
+    fn macro_call() {
+        check_highlights(
+            r#"
+              f(X) -> ?ASSERT(X =:= 1), ?FOO(X bsl 1).
+           %% ^definition
+           %%         ^^^^^^^constant
+           %%                           ^^^^constant
+           %%                                  ^^^operator
               "#,
         )
     }
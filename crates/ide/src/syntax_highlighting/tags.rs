@@ -29,6 +29,10 @@ pub struct HlMods(u32);
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum HlTag {
     Symbol(SymbolKind),
+    /// An atom written in quotes, such as `'Foo bar'`.
+    QuotedAtom,
+    /// An operator written as a keyword, such as `band` or `bsl`.
+    Operator,
 
     // For things which don't have a specific highlight. This is the
     // default for anything we do not specifically set, and maps to VS Code `generic` type
@@ -52,6 +56,9 @@ pub enum HlMod {
     DeprecatedFunction,
     // Eqwalizer Dynamic type
     TypeDynamic,
+    /// Name of a function or type where it is defined, rather than used.
+    /// Combined with `ExportedFunction` or `ExportedType` if exported.
+    Definition,
 }
 
 impl HlTag {
@@ -69,6 +76,8 @@ impl HlTag {
                 SymbolKind::Variable => "variable",
                 SymbolKind::Callback => "function",
             },
+            HlTag::QuotedAtom => "quoted_atom",
+            HlTag::Operator => "operator",
             HlTag::None => "none",
         }
     }
@@ -81,12 +90,13 @@ impl fmt::Display for HlTag {
 }
 
 impl HlMod {
-    const ALL: &'static [HlMod; 5] = &[
+    const ALL: &'static [HlMod; 6] = &[
         HlMod::Bound,
         HlMod::ExportedFunction,
         HlMod::ExportedType,
         HlMod::DeprecatedFunction,
         HlMod::TypeDynamic,
+        HlMod::Definition,
     ];
 
     fn as_str(self) -> &'static str {
@@ -96,6 +106,7 @@ impl HlMod {
             HlMod::ExportedType => "exported_type",
             HlMod::DeprecatedFunction => "deprecated_function",
             HlMod::TypeDynamic => "type_dynamic",
+            HlMod::Definition => "definition",
         }
     }
 
@@ -670,10 +670,23 @@ fn check_call_hierarchy_outgoing_calls(fixture: &str) {
 
 #[cfg(test)]
 mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::time::Duration;
+
+    use elp_eqwalizer::db::ELPDbApi;
     use elp_ide_db::DiagnosticCode;
+    use elp_ide_db::EqwalizerDiagnostics;
+    use elp_ide_db::elp_base_db::FileId;
+    use elp_project_model::EqwalizerConfig;
+    use parking_lot::Mutex;
 
     use super::check_filtered_diagnostics;
     use crate::diagnostics::Diagnostic;
+    use crate::eqwalize_in_parallel;
+    use crate::fixture;
 
     fn filter(d: &Diagnostic) -> bool {
         // Only allows through diagnostics where it returns true
@@ -693,4 +706,64 @@ mod test {
             &filter,
         )
     }
+
+    #[test]
+    fn check_parallelism_is_bounded() {
+        let config = EqwalizerConfig {
+            max_check_parallelism: Some(2),
+            ..EqwalizerConfig::default()
+        };
+        let analysis = fixture::multi_file("//- /src/main.erl\n-module(main).");
+        let file_ids: Vec<FileId> = (0..16).map(FileId::from_raw).collect();
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let checked = AtomicUsize::new(0);
+        eqwalize_in_parallel(
+            &config,
+            analysis,
+            &file_ids,
+            1,
+            |_| {},
+            |_analysis, file_ids| {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(5));
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                checked.fetch_add(file_ids.len(), Ordering::SeqCst);
+                Arc::new(EqwalizerDiagnostics::default())
+            },
+        );
+        assert!(max_in_flight.load(Ordering::SeqCst) <= 2);
+        assert_eq!(checked.load(Ordering::SeqCst), 16);
+    }
+
+    #[test]
+    fn eqwalize_in_parallel_reports_progress() {
+        let analysis = fixture::multi_file("//- /src/main.erl\n-module(main).");
+        let file_ids: Vec<FileId> = (0..4).map(FileId::from_raw).collect();
+        let percentages = Arc::new(Mutex::new(vec![]));
+        let on_progress = {
+            let percentages = percentages.clone();
+            move |percentage| percentages.lock().push(percentage)
+        };
+        eqwalize_in_parallel(
+            &EqwalizerConfig::default(),
+            analysis.clone(),
+            &file_ids,
+            1,
+            on_progress,
+            |analysis, file_ids| {
+                let module = format!("m{}", file_ids[0].index());
+                analysis.db.eqwalizing_start(module.clone());
+                analysis.db.eqwalizing_done(module);
+                Arc::new(EqwalizerDiagnostics::default())
+            },
+        );
+        assert_eq!(*percentages.lock(), vec![25, 50, 75, 100]);
+
+        // The progress is reset once done
+        analysis.db.eqwalizing_start("after".to_string());
+        analysis.db.eqwalizing_done("after".to_string());
+        assert_eq!(percentages.lock().len(), 4);
+    }
 }
@@ -0,0 +1,78 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Splits the overrides of a large `eqwalizer_types` module by the
+//! module they apply to.
+
+use criterion::BatchSize;
+use criterion::Criterion;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use elp_base_db::ModuleName;
+use elp_base_db::RootQueryDb;
+use elp_base_db::fixture::WithFixture;
+use elp_eqwalizer::db::EqwalizerDiagnosticsDatabase;
+use elp_ide_db::RootDatabase;
+
+const MODULES: usize = 100;
+const TYPES_PER_MODULE: usize = 50;
+
+fn eqwalizer_types_fixture() -> String {
+    let ids: Vec<(String, usize)> = (0..MODULES)
+        .flat_map(|m| (0..TYPES_PER_MODULE).map(move |t| (format!("mod_{m}:t_{t}"), t % 3)))
+        .collect();
+    let exports: Vec<String> = ids
+        .iter()
+        .map(|(name, arity)| format!("'{name}'/{arity}"))
+        .collect();
+    let mut fixture = format!(
+        "//- /src/eqwalizer_types.erl\n-module(eqwalizer_types).\n-export_type([{}]).\n",
+        exports.join(", ")
+    );
+    for (name, arity) in &ids {
+        let params: Vec<String> = (0..*arity).map(|i| format!("T{i}")).collect();
+        let params = params.join(", ");
+        let body = if params.is_empty() {
+            "integer()".to_string()
+        } else {
+            format!("{{{params}}}")
+        };
+        fixture.push_str(&format!("-type '{name}'({params}) :: {body}.\n"));
+    }
+    fixture
+}
+
+fn bench_custom_types(c: &mut Criterion) {
+    let fixture = eqwalizer_types_fixture();
+    let mut group = c.benchmark_group("custom_types");
+    group.sample_size(10);
+    group.bench_function("5k_entries", |b| {
+        b.iter_batched(
+            || {
+                let (db, files, _) = RootDatabase::with_many_files(&fixture);
+                let project_id = db.file_project_id(files[0]).unwrap();
+                // Only measure the grouping, not parsing the stub
+                db.transitive_stub(project_id, ModuleName::new("eqwalizer_types"))
+                    .unwrap();
+                (db, project_id)
+            },
+            // Return the db so that dropping it isn't measured
+            |(db, project_id)| {
+                let types = db.custom_types(project_id).unwrap();
+                (db, types)
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_custom_types);
+criterion_main!(benches);
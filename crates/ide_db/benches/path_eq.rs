@@ -0,0 +1,51 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Looks a path up among many others it is not equal to, as when matching
+//! a root against every known project. Only Windows compares paths
+//! case-insensitively; elsewhere both variants are plain comparisons.
+
+use criterion::Criterion;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use elp_base_db::AbsPathExt;
+use paths::AbsPathBuf;
+use paths::Utf8PathBuf;
+
+const PATHS: usize = 1000;
+
+fn abs(path: String) -> AbsPathBuf {
+    let root = if cfg!(windows) { "C:" } else { "" };
+    AbsPathBuf::assert(Utf8PathBuf::from(format!("{root}{path}")))
+}
+
+fn bench_path_eq(c: &mut Criterion) {
+    let paths: Vec<AbsPathBuf> = (0..PATHS)
+        .map(|i| abs(format!("/repo/apps/app_{i}/src/module_{i}.erl")))
+        .collect();
+    let needle = abs("/Repo/Apps/App_X/Src/module_x.erl".to_string());
+    let mut group = c.benchmark_group("path_eq");
+    group.bench_function("eq_ci_1k_non_equal", |b| {
+        b.iter(|| paths.iter().filter(|path| path.eq_ci(&needle)).count())
+    });
+    group.bench_function("to_logical_canonical_1k_non_equal", |b| {
+        b.iter(|| {
+            let needle = needle.to_logical_canonical();
+            paths
+                .iter()
+                .filter(|path| path.to_logical_canonical() == needle)
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_path_eq);
+criterion_main!(benches);
@@ -0,0 +1,74 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Computes the transitive stub of a module referring to types of many
+//! other modules, each with dependencies of its own.
+
+use criterion::BatchSize;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use elp_base_db::ModuleName;
+use elp_base_db::RootQueryDb;
+use elp_base_db::fixture::WithFixture;
+use elp_eqwalizer::db::EqwalizerDiagnosticsDatabase;
+use elp_ide_db::RootDatabase;
+
+fn wide_fixture(fan_out: usize) -> String {
+    let mut fixture = String::new();
+    let refs: Vec<String> = (0..fan_out).map(|i| format!("dep_{i}:t()")).collect();
+    fixture.push_str(&format!(
+        "//- /src/wide.erl\n-module(wide).\n-export_type([t/0]).\n-type t() :: {}.\n",
+        refs.join(" | ")
+    ));
+    for i in 0..fan_out {
+        fixture.push_str(&format!(
+            "//- /src/dep_{i}.erl\n-module(dep_{i}).\n-export_type([t/0]).\n\
+             -type t() :: nil | {{dep_{i}, leaf_{i}:t(), t()}}.\n\
+             //- /src/leaf_{i}.erl\n-module(leaf_{i}).\n-export_type([t/0]).\n\
+             -type t() :: {{leaf_{i}, integer()}}.\n"
+        ));
+    }
+    fixture
+}
+
+fn bench_transitive_stub(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transitive_stub");
+    group.sample_size(10);
+    for fan_out in [10, 50] {
+        let fixture = wide_fixture(fan_out);
+        group.bench_with_input(
+            BenchmarkId::new("fan_out", fan_out),
+            &fixture,
+            |b, fixture| {
+                b.iter_batched(
+                    || {
+                        let (db, files, _) = RootDatabase::with_many_files(fixture);
+                        let project_id = db.file_project_id(files[0]).unwrap();
+                        (db, project_id)
+                    },
+                    // Return the db so that dropping it isn't measured
+                    |(db, project_id)| {
+                        let stub = db
+                            .transitive_stub(project_id, ModuleName::new("wide"))
+                            .unwrap();
+                        (db, stub)
+                    },
+                    BatchSize::PerIteration,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_transitive_stub);
+criterion_main!(benches);
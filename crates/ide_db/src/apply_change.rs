@@ -25,6 +25,8 @@ impl RootDatabase {
         let _p = tracing::info_span!("RootDatabase::apply_change").entered();
         self.request_cancellation();
         log::info!("apply_change {change:?}");
-        change.apply(self, resolve_file_id);
+        if let Err(err) = change.apply(self, resolve_file_id) {
+            log::error!("apply_change failed: {err}");
+        }
     }
 }
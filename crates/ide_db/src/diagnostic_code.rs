@@ -87,6 +87,9 @@ pub enum DiagnosticCode {
     BinaryStringToSigil,
     NoCatch,
     NoErrorLogger,
+    AmbiguousIncludeLib,
+    IncludeCycle,
+    UnresolvedOtpIncludeLib,
 
     // Wrapper for erlang service diagnostic codes
     ErlangService(String),
@@ -234,6 +237,9 @@ impl DiagnosticCode {
             DiagnosticCode::BinaryStringToSigil => "W0051".to_string(),
             DiagnosticCode::NoCatch => "W0052".to_string(),
             DiagnosticCode::NoErrorLogger => "W0053".to_string(),
+            DiagnosticCode::AmbiguousIncludeLib => "W0054".to_string(),
+            DiagnosticCode::IncludeCycle => "W0055".to_string(),
+            DiagnosticCode::UnresolvedOtpIncludeLib => "W0056".to_string(),
 
             DiagnosticCode::ErlangService(c) => c.to_string(),
             DiagnosticCode::Eqwalizer(c) => format!("eqwalizer: {c}"),
@@ -329,6 +335,9 @@ impl DiagnosticCode {
             DiagnosticCode::BinaryStringToSigil => "binary_string_to_sigil".to_string(),
             DiagnosticCode::NoCatch => "no_catch".to_string(),
             DiagnosticCode::NoErrorLogger => "no_error_logger".to_string(),
+            DiagnosticCode::AmbiguousIncludeLib => "ambiguous_include_lib".to_string(),
+            DiagnosticCode::IncludeCycle => "include_cycle".to_string(),
+            DiagnosticCode::UnresolvedOtpIncludeLib => "unresolved_otp_include_lib".to_string(),
 
             DiagnosticCode::ErlangService(c) => c.to_string(),
             DiagnosticCode::Eqwalizer(c) => c.to_string(),
@@ -472,6 +481,9 @@ impl DiagnosticCode {
             DiagnosticCode::NoSize => false,
             DiagnosticCode::NoCatch => false,
             DiagnosticCode::NoErrorLogger => false,
+            DiagnosticCode::AmbiguousIncludeLib => false,
+            DiagnosticCode::IncludeCycle => false,
+            DiagnosticCode::UnresolvedOtpIncludeLib => false,
 
             DiagnosticCode::BinaryStringToSigil => false,
             DiagnosticCode::ErlangService(_) => false,
@@ -18,6 +18,7 @@ use elp_base_db::ProjectId;
 use elp_base_db::RootQueryDb;
 use elp_base_db::SourceDatabase;
 use elp_base_db::VfsPath;
+use elp_base_db::limit_logged_string;
 use elp_base_db::salsa;
 use elp_eqwalizer::EqwalizerDiagnostic;
 use elp_eqwalizer::EqwalizerDiagnostics;
@@ -25,9 +26,11 @@ use elp_eqwalizer::analyses::EqwalizerAnalysesDatabase;
 use elp_eqwalizer::ast::Error;
 use elp_eqwalizer::ast::Pos;
 use elp_eqwalizer::ast::RemoteId;
+use elp_eqwalizer::db::DeadlineWaiters;
 use elp_eqwalizer::db::EqwalizerDiagnosticsDatabase;
 use elp_eqwalizer::db::EqwalizerErlASTStorage;
 use elp_eqwalizer::ipc::IpcHandle;
+use elp_eqwalizer::progress::EqwalizerProgress;
 use elp_project_model::otp::otp_supported_by_eqwalizer;
 use elp_syntax::SmolStr;
 use elp_syntax::ast;
@@ -102,6 +105,29 @@ pub fn eqwalizer_diagnostics_by_project(
     Arc::new(db.typecheck(project_id, file_ids))
 }
 
+/// The errors in the `diagnostics` of `module`. An eqWAlizer failure
+/// is logged, and yields no errors.
+pub fn eqwalizer_errors(
+    module: &ModuleName,
+    diagnostics: &EqwalizerDiagnostics,
+) -> Vec<EqwalizerDiagnostic> {
+    match diagnostics {
+        EqwalizerDiagnostics::Diagnostics { errors, .. } => errors
+            .iter()
+            .flat_map(|(_, diags)| diags.iter().cloned())
+            .collect(),
+        EqwalizerDiagnostics::NoAst { .. } => vec![],
+        EqwalizerDiagnostics::Error(err) => {
+            log::error!(
+                "EqWAlizer failed for {}: {}",
+                module.as_str(),
+                limit_logged_string(err)
+            );
+            vec![]
+        }
+    }
+}
+
 fn eqwalizer_stats(
     db: &dyn EqwalizerDatabase,
     project_id: ProjectId,
@@ -365,12 +391,7 @@ impl EqwalizerErlASTStorage for crate::RootDatabase {
         module: ModuleName,
     ) -> Result<Arc<Vec<u8>>, Error> {
         if let Some(file_id) = self.module_index(project_id).file_for_module(&module) {
-            let result = self.module_ast(file_id);
-            if result.is_ok() {
-                Ok(result.ast.clone())
-            } else {
-                Err(Error::ParseError)
-            }
+            crate::erl_ast::module_ast_etf(self, file_id)
         } else {
             Err(Error::ModuleNotFound(module.as_str().into()))
         }
@@ -379,15 +400,31 @@ impl EqwalizerErlASTStorage for crate::RootDatabase {
 
 impl elp_eqwalizer::db::ELPDbApi for crate::RootDatabase {
     fn eqwalizing_start(&self, module: String) {
+        if !self.eqwalizing_in_flight.lock().insert(module.clone()) {
+            return;
+        }
+        self.save_eqwalizing_in_flight();
         if let Some(reporter) = self.eqwalizer_progress_reporter.lock().as_mut() {
             reporter.start_module(module)
         }
     }
 
     fn eqwalizing_done(&self, module: String) {
+        if !self.eqwalizing_in_flight.lock().remove(&module) {
+            return;
+        }
+        self.save_eqwalizing_in_flight();
         if let Some(reporter) = self.eqwalizer_progress_reporter.lock().as_mut() {
             reporter.done_module(&module);
         }
+        let progress = self.eqwalizing_progress.lock().clone();
+        if let Some(progress) = progress {
+            progress.module_done();
+        }
+    }
+
+    fn set_eqwalizing_progress(&self, progress: Option<Arc<EqwalizerProgress>>) {
+        *self.eqwalizing_progress.lock() = progress
     }
 
     fn set_module_ipc_handle(&self, module: ModuleName, handle: Option<Arc<Mutex<IpcHandle>>>) {
@@ -409,6 +446,30 @@ impl elp_eqwalizer::db::ELPDbApi for crate::RootDatabase {
             .get(module.as_str())
             .map(|v| v.to_owned())
     }
+
+    fn is_generated_module(&self, project_id: ProjectId, module: ModuleName) -> bool {
+        self.module_index(project_id)
+            .file_for_module(&module)
+            .is_some_and(|file_id| self.elp_metadata(file_id).is_generated())
+    }
+
+    fn eqwalize_module(
+        &self,
+        project_id: ProjectId,
+        module: ModuleName,
+    ) -> Arc<EqwalizerDiagnostics> {
+        match self.module_index(project_id).file_for_module(&module) {
+            Some(file_id) => self.eqwalizer_diagnostics_by_project(project_id, vec![file_id]),
+            None => Arc::new(EqwalizerDiagnostics::Error(format!(
+                "eqWAlizing module {}: module not found",
+                module.as_str()
+            ))),
+        }
+    }
+
+    fn deadline_waiters(&self) -> Arc<DeadlineWaiters> {
+        self.deadline_waiters.clone()
+    }
 }
 
 fn has_eqwalizer_atom(expr: ast::Expr) -> bool {
@@ -444,11 +505,63 @@ fn is_ignore_or_fixme_atom(expr: ast::Expr) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+    use std::collections::BTreeSet;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use std::time::Instant;
+
     use elp_base_db::fixture::WithFixture;
+    use elp_eqwalizer::DiagnosticsDiff;
+    use elp_eqwalizer::EqwalizerConfig;
+    use elp_eqwalizer::EqwalizerConfigError;
+    use elp_eqwalizer::StaleBeam;
+    use elp_eqwalizer::ast::TypeConversionError;
+    use elp_eqwalizer::ast::beam_source_hash;
+    use elp_eqwalizer::ast::source_hash;
+    use elp_eqwalizer::config_overrides::EqwalizerConfigOverrides;
+    use elp_eqwalizer::db::DeadlineDiagnostics;
+    use elp_eqwalizer::db::ELPDbApi;
+    use elp_eqwalizer::db::TypeDeclSource;
+    use elp_eqwalizer::db::diagnostics_deadline;
+    use elp_eqwalizer::db::prefetch_erl_ast;
+    use elp_eqwalizer::db::refresh_ebin_beam_files;
+    use elp_eqwalizer::db::set_eqwalizer_config_validated;
+    use elp_eqwalizer::db::type_decl_traced;
+    use elp_syntax::TextRange;
+    use elp_types_db::eqwalizer::form::ExternalForm;
 
     use super::*;
+    use crate::EqwalizerProgressReporter;
     use crate::RootDatabase;
 
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl EqwalizerProgressReporter for Recorder {
+        fn start_module(&mut self, module: String) {
+            self.0.lock().push(format!("start {module}"));
+        }
+
+        fn done_module(&mut self, module: &str) {
+            self.0.lock().push(format!("done {module}"));
+        }
+    }
+
+    /// Wait for the in-flight modules saved in the background to be `expected`.
+    fn assert_saved_in_flight(path: &std::path::Path, expected: &str) {
+        let start = Instant::now();
+        loop {
+            let saved = std::fs::read_to_string(path).unwrap_or_default();
+            if saved == expected || start.elapsed() > Duration::from_secs(10) {
+                assert_eq!(saved, expected);
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
     #[test]
     fn test_has_eqwalizer_module_marker() {
         let (db, file_id) = RootDatabase::with_single_file(
@@ -500,4 +613,1348 @@ mod tests {
 
         assert!(db.has_eqwalizer_ignore_marker(file_id));
     }
+
+    #[test]
+    fn test_module_diagnostics_deadline() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(slow).
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        let module = ModuleName::new("slow");
+        let checks = Arc::new(AtomicUsize::new(0));
+        let (release, released) = mpsc::channel::<()>();
+        // Stands in for a slow eqWAlizer, checking until released.
+        let slow_check = {
+            let checks = checks.clone();
+            move |_: &RootDatabase| {
+                checks.fetch_add(1, Ordering::SeqCst);
+                released.recv().unwrap();
+                Arc::new(EqwalizerDiagnostics::Error("slow".to_string()))
+            }
+        };
+        let other_check = {
+            let checks = checks.clone();
+            move |_: &RootDatabase| {
+                checks.fetch_add(1, Ordering::SeqCst);
+                Arc::new(EqwalizerDiagnostics::default())
+            }
+        };
+
+        assert!(matches!(
+            diagnostics_deadline(&db, project_id, module.clone(), Instant::now(), slow_check),
+            DeadlineDiagnostics::Pending
+        ));
+
+        // A caller waiting on the check in flight rather than starting another
+        let waiter = {
+            let db = db.snapshot();
+            let module = module.clone();
+            std::thread::spawn(move || {
+                let deadline = Instant::now() + Duration::from_secs(3600);
+                diagnostics_deadline(&db, project_id, module, deadline, other_check)
+            })
+        };
+        let key = (project_id, module.clone());
+        while db.deadline_waiters().lock().get(&key).map_or(0, Vec::len) < 2 {
+            std::thread::yield_now();
+        }
+        release.send(()).unwrap();
+
+        match waiter.join().unwrap() {
+            DeadlineDiagnostics::Ready(diagnostics) => assert_eq!(
+                *diagnostics,
+                EqwalizerDiagnostics::Error("slow".to_string())
+            ),
+            DeadlineDiagnostics::Pending => panic!("expected the diagnostics of the slow check"),
+        }
+        assert_eq!(checks.load(Ordering::SeqCst), 1);
+        assert!(db.deadline_waiters().lock().is_empty());
+    }
+
+    #[test]
+    fn test_eqwalizing_progress_reaches_100() {
+        let db = RootDatabase::default();
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let progress = EqwalizerProgress::new(8, {
+            let reported = reported.clone();
+            move |percentage| reported.lock().push(percentage)
+        });
+        db.set_eqwalizing_progress(Some(Arc::new(progress)));
+
+        std::thread::scope(|s| {
+            for idx in 0..8 {
+                let db = db.clone();
+                s.spawn(move || {
+                    db.eqwalizing_start(format!("mod_{idx}"));
+                    db.eqwalizing_done(format!("mod_{idx}"));
+                });
+            }
+        });
+
+        let reported = reported.lock();
+        assert_eq!(reported.last(), Some(&100));
+        assert!(reported.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_duplicate_modules() {
+        let (db, files, _) = RootDatabase::with_many_files(
+            r#"
+//- /src/dup.erl
+-module(dup).
+//- /src/sub/dup.erl
+-module(dup).
+//- /src/unique.erl
+-module(unique).
+"#,
+        );
+        let project_id = db.file_project_id(files[0]).unwrap();
+        let mut dup_files = vec![files[0], files[1]];
+        dup_files.sort();
+
+        let duplicates = db.duplicate_modules(project_id);
+        assert_eq!(
+            *duplicates,
+            BTreeMap::from([(ModuleName::new("dup"), dup_files)])
+        );
+    }
+
+    #[test]
+    fn test_prefetch_erl_ast() {
+        let (db, files, _) = RootDatabase::with_many_files(
+            r#"
+//- /src/a.erl
+-module(a).
+//- /src/b.erl
+-module(b).
+//- /src/c.erl
+-module(c).
+"#,
+        );
+        let project_id = db.file_project_id(files[0]).unwrap();
+        let modules: Vec<ModuleName> = ["a", "b", "c", "missing"]
+            .into_iter()
+            .map(ModuleName::new)
+            .collect();
+
+        let prefetched = prefetch_erl_ast(&db, project_id, &modules);
+        assert_eq!(prefetched.len(), modules.len());
+        for module in &modules[..3] {
+            let bytes = prefetched[module].as_ref().unwrap();
+            // Memoized, so read again without parsing
+            let again = db.erl_ast_bytes(project_id, module.clone()).unwrap();
+            assert!(Arc::ptr_eq(bytes, &again), "{module:?}");
+        }
+        assert!(matches!(
+            prefetched[&ModuleName::new("missing")],
+            Err(Error::ModuleNotFound(_))
+        ));
+        assert!(prefetch_erl_ast(&db, project_id, &[]).is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_cancelled_module_diagnostics_releases_ipc_handle() {
+        let (mut db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(slow).
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        let module = ModuleName::new("slow");
+        // Stands in for an eqWAlizer process that never answers.
+        let handle =
+            IpcHandle::from_command(std::process::Command::new("sleep").arg("30")).unwrap();
+        let handle = Arc::new(Mutex::new(handle));
+        db.set_module_ipc_handle(module.clone(), Some(handle.clone()));
+
+        let start = Instant::now();
+        let snapshot = db.snapshot();
+        let request = std::thread::spawn(move || {
+            salsa::Cancelled::catch(|| snapshot.module_diagnostics(project_id, "slow".to_string()))
+                .is_err()
+        });
+        // The request holds the handle while waiting for eqWAlizer.
+        while !handle.is_locked() {
+            std::thread::yield_now();
+        }
+        // Blocks until the in-flight request has unwound.
+        db.request_cancellation();
+
+        assert!(request.join().unwrap());
+        assert!(db.module_ipc_handle(module).is_none());
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_overlapping_eqwalizing_notifications_are_deduplicated() {
+        let db = RootDatabase::default();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        db.set_eqwalizer_progress_reporter(Some(Box::new(Recorder(events.clone()))));
+
+        db.eqwalizing_start("foo".to_string());
+        db.eqwalizing_start("foo".to_string());
+        db.eqwalizing_done("foo".to_string());
+        db.eqwalizing_done("foo".to_string());
+
+        assert_eq!(*events.lock(), vec!["start foo", "done foo"]);
+    }
+
+    #[test]
+    fn test_dangling_eqwalizing_modules_are_done_after_restart() {
+        let path = std::env::temp_dir().join(format!(
+            "elp_eqwalizing_in_flight_{}.txt",
+            std::process::id()
+        ));
+
+        // A session crashing while eqWAlizing `foo`
+        let db = RootDatabase::default();
+        db.persist_eqwalizing_in_flight(Some(path.clone()));
+        db.eqwalizing_start("foo".to_string());
+        db.eqwalizing_start("bar".to_string());
+        db.eqwalizing_done("bar".to_string());
+        assert_saved_in_flight(&path, "foo\n");
+        drop(db);
+
+        // The next session
+        let db = RootDatabase::default();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        db.set_eqwalizer_progress_reporter(Some(Box::new(Recorder(events.clone()))));
+        db.persist_eqwalizing_in_flight(Some(path.clone()));
+        assert_eq!(*events.lock(), vec!["done foo"]);
+        assert_saved_in_flight(&path, "");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_converted_stub_bytes_round_trip() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(stub).
+-export_type([t/0]).
+-type t() :: integer().
+-spec f(t()) -> t().
+f(X) -> X.
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        let module = ModuleName::new("stub");
+
+        let stub = db.converted_stub(project_id, module.clone()).unwrap();
+        let bytes = db.converted_stub_bytes(project_id, module).unwrap();
+        let forms: Vec<ExternalForm> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(forms, stub.forms);
+    }
+
+    #[test]
+    fn test_transitive_stub_size() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(sized).
+-export([f/1]).
+-export_type([t/0]).
+-type t() :: #{atom() => [integer()]}.
+-record(r, {a :: t(), b :: binary()}).
+-spec f(t()) -> #r{}.
+f(X) -> #r{a = X, b = <<>>}.
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        let module = ModuleName::new("sized");
+
+        let size = db.transitive_stub_size(project_id, module.clone()).unwrap();
+        let bytes = db.transitive_stub_bytes(project_id, module).unwrap();
+        assert!(size > bytes.len(), "{size} <= {}", bytes.len());
+    }
+
+    #[test]
+    fn test_specced_functions() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(specced).
+-export([a/0, b/1, c/0]).
+-spec a() -> ok.
+a() -> ok.
+-spec b(integer()) -> integer().
+b(X) -> X.
+c() -> ok.
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        let specced = db
+            .specced_functions(project_id, ModuleName::new("specced"))
+            .unwrap();
+        let id = |name: &str, arity| eqwalizer::Id {
+            name: name.into(),
+            arity,
+        };
+        assert_eq!(*specced, BTreeSet::from([id("a", 0), id("b", 1)]));
+    }
+
+    #[test]
+    fn test_optional_callbacks() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(behave).
+-callback init(term()) -> ok.
+-callback terminate() -> ok.
+-callback handle(term()) -> ok.
+-optional_callbacks([terminate/0, handle/1]).
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        let optional = db
+            .optional_callbacks(project_id, ModuleName::new("behave"))
+            .unwrap();
+        let id = |name: &str, arity| eqwalizer::Id {
+            name: name.into(),
+            arity,
+        };
+        assert_eq!(
+            *optional,
+            BTreeSet::from([id("terminate", 0), id("handle", 1)])
+        );
+    }
+
+    #[test]
+    fn test_custom_overrides_config() {
+        let fixture = r#"
+//- /src/foo.erl
+-module(foo).
+-export_type([t/0]).
+-type t() :: integer().
+//- /src/eqwalizer_types.erl
+-module(eqwalizer_types).
+-export_type(['foo:t'/0]).
+-type 'foo:t'() :: atom().
+"#;
+        let (mut db, files, _) = RootDatabase::with_many_files(fixture);
+        let project_id = db.file_project_id(files[0]).unwrap();
+        let module = ModuleName::new("foo");
+        let id = eqwalizer::Id {
+            name: "t".into(),
+            arity: 0,
+        };
+        let stub_decl = db
+            .transitive_stub(project_id, module.clone())
+            .unwrap()
+            .types
+            .get(&id)
+            .cloned();
+        assert!(stub_decl.is_some());
+
+        let overridden = db
+            .type_decl(project_id, module.clone(), id.clone())
+            .unwrap();
+        assert!(overridden.is_some());
+        assert_ne!(overridden, stub_decl);
+
+        db.set_eqwalizer_config(Arc::new(EqwalizerConfig {
+            enable_custom_overrides: false,
+            ..EqwalizerConfig::default()
+        }));
+        assert!(db.custom_types(project_id).unwrap().is_empty());
+        assert_eq!(db.type_decl(project_id, module, id).unwrap(), stub_decl);
+    }
+
+    #[test]
+    fn test_skip_generated_module() {
+        let (mut db, file_id) = RootDatabase::with_single_file(
+            r#"
+%% elp:generated by protoc
+-module(generated).
+-spec f() -> ok.
+f() -> not_ok.
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        db.set_eqwalizer_config(Arc::new(EqwalizerConfig {
+            skip_generated: true,
+            ..EqwalizerConfig::default()
+        }));
+
+        let (diagnostics, _) = db.module_diagnostics(project_id, "generated".to_string());
+        assert_eq!(*diagnostics, EqwalizerDiagnostics::default());
+    }
+
+    #[test]
+    fn test_skip_modules() {
+        // All the modules are generated, with an invalid config
+        // directive: that is reported for the modules that get past
+        // `skip_modules`, without running eqWAlizer as
+        // `skip_generated` is set.
+        let (mut db, files, _) = RootDatabase::with_many_files(
+            r#"
+//- /src/thrift_types.erl
+%% elp:generated by thrift
+-module(thrift_types).
+% eqwalizer:config #{unknown => true}
+//- /src/vendored.erl
+%% elp:generated by thrift
+-module(vendored).
+% eqwalizer:config #{unknown => true}
+//- /src/own.erl
+%% elp:generated by thrift
+-module(own).
+% eqwalizer:config #{unknown => true}
+"#,
+        );
+        let project_id = db.file_project_id(files[0]).unwrap();
+        db.set_eqwalizer_config(Arc::new(EqwalizerConfig {
+            skip_modules: vec!["thrift_*".to_string(), "vendored".to_string()],
+            skip_generated: true,
+            ..EqwalizerConfig::default()
+        }));
+
+        for module in ["thrift_types", "vendored"] {
+            assert!(db.is_module_skipped(ModuleName::new(module)), "{module}");
+            let (diagnostics, _) = db.module_diagnostics(project_id, module.to_string());
+            assert_eq!(*diagnostics, EqwalizerDiagnostics::default(), "{module}");
+        }
+        assert!(!db.is_module_skipped(ModuleName::new("own")));
+        let (diagnostics, _) = db.module_diagnostics(project_id, "own".to_string());
+        let EqwalizerDiagnostics::Diagnostics { errors, .. } = &*diagnostics else {
+            panic!("unexpected {diagnostics:?}");
+        };
+        let codes: Vec<&str> = errors["own"].iter().map(|d| d.code.as_str()).collect();
+        assert_eq!(codes, vec!["invalid_config_directive"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_failing_modules() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(clean_a).
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        // Stands in for eqWAlizer reporting no diagnostics.
+        for module in ["clean_a", "clean_b"] {
+            let handle = IpcHandle::from_command(
+                std::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(r#"read line; echo '{"Done":{}}'"#),
+            )
+            .unwrap();
+            db.set_module_ipc_handle(ModuleName::new(module), Some(Arc::new(Mutex::new(handle))));
+        }
+        // Without a handle, eqWAlizing "broken" fails with an error.
+        let modules = vec![
+            ModuleName::new("clean_a"),
+            ModuleName::new("broken"),
+            ModuleName::new("clean_b"),
+        ];
+
+        let failing = db.failing_modules(project_id, modules);
+        assert_eq!(*failing, vec![ModuleName::new("broken")]);
+    }
+
+    #[test]
+    fn test_type_nested_beyond_expansion_depth() {
+        let nested = format!("{}integer(){}", "[".repeat(20), "]".repeat(20));
+        let (mut db, file_id) = RootDatabase::with_single_file(&format!(
+            r#"
+-module(deep).
+-export_type([shallow/0, deep/0]).
+-type shallow() :: [integer()].
+-type deep() :: {nested}.
+"#
+        ));
+        let project_id = db.file_project_id(file_id).unwrap();
+        db.set_eqwalizer_config(Arc::new(EqwalizerConfig {
+            max_type_expansion_depth: 10,
+            ..EqwalizerConfig::default()
+        }));
+
+        assert_eq!(
+            db.expanded_stub(project_id, ModuleName::new("deep"))
+                .map(|_| ()),
+            Err(Error::TypeConversionError(
+                TypeConversionError::ExpansionTooDeep("type deep/0".to_string())
+            ))
+        );
+    }
+
+    /// The encoding of `term` described in `elp_eqwalizer::ast::json`.
+    fn term_to_json(term: &eetf::Term) -> serde_json::Value {
+        use eetf::Term;
+        match term {
+            Term::Atom(atom) => serde_json::json!(atom.name),
+            Term::FixInteger(int) => serde_json::json!(int.value),
+            Term::BigInteger(int) => {
+                serde_json::json!(int.value.to_string().parse::<i64>().unwrap())
+            }
+            Term::Float(float) => serde_json::json!(float.value),
+            Term::List(list) => {
+                serde_json::Value::Array(list.elements.iter().map(term_to_json).collect())
+            }
+            Term::Tuple(tuple) => serde_json::json!({
+                "tuple": tuple.elements.iter().map(term_to_json).collect::<Vec<_>>()
+            }),
+            Term::ByteList(string) => serde_json::json!({
+                "string": String::from_utf8(string.bytes.clone()).unwrap()
+            }),
+            term => panic!("unexpected term in forms: {term}"),
+        }
+    }
+
+    #[test]
+    fn test_json_ast_matches_etf_ast() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(small).
+-export([f/1]).
+-export_type([t/0]).
+-record(r, {a :: t(), b = 1.5 :: float()}).
+-type t() :: integer() | atom() | 0..4294967296.
+-spec f(t()) -> #r{}.
+f(X) -> #r{a = X}.
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        let etf = db
+            .erl_ast_bytes(project_id, ModuleName::new("small"))
+            .unwrap();
+        let etf_ast = elp_eqwalizer::ast::from_bytes(&etf, true).unwrap();
+
+        let forms = match eetf::Term::decode(std::io::Cursor::new(&*etf)).unwrap() {
+            eetf::Term::Tuple(res) => res.elements[1].clone(),
+            term => panic!("unexpected result {term}"),
+        };
+        let json = serde_json::to_vec(&term_to_json(&forms)).unwrap();
+        let json_ast = elp_eqwalizer::ast::from_json(&json, true).unwrap();
+        assert_eq!(json_ast.forms, etf_ast.forms);
+
+        for invalid in [
+            &br#"[{"NotAForm": {}}]"#[..],
+            br#"{"tuple": []}"#,
+            br#"[true]"#,
+            br#"[{"tuple": ["attribute", 1, "module", "small"]}]"#,
+        ] {
+            assert!(
+                matches!(
+                    elp_eqwalizer::ast::from_json(invalid, true),
+                    Err(Error::TypeConversionError(
+                        TypeConversionError::InvalidJson(_)
+                    ))
+                ),
+                "{}",
+                String::from_utf8_lossy(invalid)
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_sidecar_stub() {
+        let sidecar = |name: &str| {
+            format!(
+                r#"[
+  {{"tuple": ["attribute", 1, "file", {{"tuple": [{{"string": "/src/small.erl"}}, 1]}}]}},
+  {{"tuple": ["attribute", 1, "module", "small"]}},
+  {{"tuple": ["attribute", 2, "export_type", [{{"tuple": ["{name}", 0]}}]]}},
+  {{"tuple": ["attribute", 3, "type", {{"tuple": ["{name}", {{"tuple": ["type", 3, "integer", []]}}, []]}}]}}
+]"#
+            )
+        };
+        let (mut db, files, _) = RootDatabase::with_many_files(&format!(
+            r#"
+//- /src/small.erl
+-module(small).
+-export_type([u/0]).
+-type u() :: atom().
+//- /src/small.ast.json
+{}
+"#,
+            sidecar("t")
+        ));
+        let project_id = db.file_project_id(files[0]).unwrap();
+        let exported = |db: &RootDatabase| -> Vec<String> {
+            db.exported_types(project_id, ModuleName::new("small"))
+                .unwrap()
+                .iter()
+                .map(|id| id.to_string())
+                .collect()
+        };
+        assert_eq!(exported(&db), vec!["t/0"]);
+
+        // The sidecar is an input like the source it stands for
+        db.set_file_text(files[1], Arc::from(sidecar("v")));
+        assert_eq!(exported(&db), vec!["v/0"]);
+    }
+
+    #[test]
+    fn test_stub_expansion_stats() {
+        let fixture = r#"
+//- /src/a.erl
+-module(a).
+-export([f/1]).
+-export_type([t/0]).
+-type t() :: b:u() | integer().
+-spec f(t()) -> b:u().
+f(X) -> X.
+//- /src/b.erl
+-module(b).
+-export_type([u/0]).
+-type u() :: atom().
+"#;
+        let (db, files, _) = RootDatabase::with_many_files(fixture);
+        let project_id = db.file_project_id(files[0]).unwrap();
+
+        let stats = db
+            .stub_expansion_stats(project_id, ModuleName::new("a"))
+            .unwrap();
+        assert_eq!(stats.forms_expanded, 2);
+        assert_eq!(stats.remote_refs_followed, 2);
+        assert!(stats.types_resolved >= 3);
+
+        let stats = db
+            .stub_expansion_stats(project_id, ModuleName::new("b"))
+            .unwrap();
+        assert_eq!(stats.forms_expanded, 1);
+        assert_eq!(stats.remote_refs_followed, 0);
+    }
+
+    /// Write a BEAM file with only an `ElpH` chunk: loading a stub from
+    /// it fails with `Error::InvalidBEAM`, which tells it was used.
+    fn write_beam(path: &std::path::Path, source_hash: [u8; 16]) {
+        std::fs::write(path, beam_with_hash(source_hash)).unwrap();
+    }
+
+    fn beam_with_hash(source_hash: [u8; 16]) -> Vec<u8> {
+        let mut beam = b"FOR1".to_vec();
+        beam.extend(28u32.to_be_bytes());
+        beam.extend(b"BEAMElpH");
+        beam.extend(16u32.to_be_bytes());
+        beam.extend(source_hash);
+        beam
+    }
+
+    #[test]
+    fn test_truncated_beam() {
+        let hash = source_hash("-module(comp).\n");
+        let beam = beam_with_hash(hash);
+        assert_eq!(beam_source_hash(&beam), Ok(Some(hash)));
+        assert_eq!(beam_source_hash(&beam[..12]), Ok(None));
+        // Cut in the middle of the chunk or of its header
+        for len in [30, 14] {
+            assert_eq!(
+                beam_source_hash(&beam[..len]),
+                Err(Error::InvalidBEAM),
+                "{len}"
+            );
+        }
+        // A chunk whose length runs past the end of the file
+        let mut beam = b"FOR1".to_vec();
+        beam.extend(16u32.to_be_bytes());
+        beam.extend(b"BEAMAbst");
+        beam.extend(u32::MAX.to_be_bytes());
+        beam.extend(b"abst");
+        assert_eq!(beam_source_hash(&beam), Err(Error::InvalidBEAM));
+    }
+
+    #[test]
+    fn test_stale_beam() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = dir.path().join("comp-1.3");
+        let beam_path = app_dir.join("ebin").join("comp.beam");
+        std::fs::create_dir_all(beam_path.parent().unwrap()).unwrap();
+        let fixture = format!(
+            r#"
+//- {app}/src/comp.erl otp_app:{app}
+-module(comp).
+-export_type([t/0]).
+-type t() :: integer().
+"#,
+            app = app_dir.display()
+        );
+        let (mut db, files, _) = RootDatabase::with_many_files(&fixture);
+        let project_id = db.file_project_id(files[0]).unwrap();
+        let module = ModuleName::new("comp");
+        let source = db.file_text(files[0]).text(&db);
+
+        // The BEAM has no AST chunk, so loading it fails: that tells
+        // whether it was used or not
+        write_beam(&beam_path, source_hash("-module(old).\n"));
+        assert!(db.converted_stub(project_id, module.clone()).is_ok());
+
+        let set_stale_beam = |db: &mut RootDatabase, stale_beam| {
+            db.set_eqwalizer_config(Arc::new(EqwalizerConfig {
+                stale_beam,
+                ..EqwalizerConfig::default()
+            }))
+        };
+        set_stale_beam(&mut db, StaleBeam::Error);
+        assert_eq!(
+            db.converted_stub(project_id, module.clone()).map(|_| ()),
+            Err(Error::StaleBEAM(beam_path.clone()))
+        );
+        set_stale_beam(&mut db, StaleBeam::Ignore);
+        assert_eq!(
+            db.converted_stub(project_id, module.clone()).map(|_| ()),
+            Err(Error::InvalidBEAM)
+        );
+
+        write_beam(&beam_path, source_hash(&source));
+        set_stale_beam(&mut db, StaleBeam::FallBackToSource);
+        assert_eq!(
+            db.converted_stub(project_id, module).map(|_| ()),
+            Err(Error::InvalidBEAM)
+        );
+    }
+
+    #[test]
+    fn test_beam_in_extra_ebin_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("ebin1");
+        let second = dir.path().join("ebin2");
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+        let (mut db, files, _) = RootDatabase::with_many_files(
+            r#"
+//- /src/prebuilt.erl
+-module(prebuilt).
+-export_type([t/0]).
+-type t() :: integer().
+"#,
+        );
+        let project_id = db.file_project_id(files[0]).unwrap();
+        let module = ModuleName::new("prebuilt");
+        let source = db.file_text(files[0]).text(&db);
+        let beam_path = second.join("prebuilt.beam");
+        write_beam(&beam_path, source_hash(&source));
+
+        let set_config = |db: &mut RootDatabase, beam_for_all_apps| {
+            set_eqwalizer_config_validated(
+                db,
+                EqwalizerConfig {
+                    ebin_dirs: vec![first.display().to_string(), second.display().to_string()],
+                    beam_for_all_apps,
+                    ..EqwalizerConfig::default()
+                },
+            )
+            .unwrap()
+        };
+        set_config(&mut db, false);
+        assert!(db.converted_stub(project_id, module.clone()).is_ok());
+
+        set_config(&mut db, true);
+        assert_eq!(
+            db.converted_stub(project_id, module.clone()).map(|_| ()),
+            Err(Error::InvalidBEAM)
+        );
+
+        // The ebin directories are only listed on refresh
+        std::fs::remove_file(&beam_path).unwrap();
+        assert_eq!(
+            db.converted_stub(project_id, module.clone()).map(|_| ()),
+            Err(Error::InvalidBEAM)
+        );
+        refresh_ebin_beam_files(&mut db);
+        assert!(db.converted_stub(project_id, module).is_ok());
+    }
+
+    #[test]
+    fn test_custom_types_grouping() {
+        // Module names sharing prefixes, with several arities
+        let mut exports = vec![];
+        let mut types = String::new();
+        let mut fun_exports = vec![];
+        let mut specs = String::new();
+        for module in ["a", "a_b", "ab", "b"] {
+            for name in ["t", "u", "v"] {
+                exports.push(format!("'{module}:{name}'/0, '{module}:{name}'/1"));
+                types.push_str(&format!("-type '{module}:{name}'() :: {{{module}}}.\n"));
+                types.push_str(&format!("-type '{module}:{name}'(T) :: {{{module}, T}}.\n"));
+            }
+            // `f` with a plain spec, `g` and `g:h` with overloaded ones
+            for name in ["f", "g", "g:h"] {
+                fun_exports.push(format!("'{module}:{name}'/1"));
+                let spec = match name {
+                    "f" => "(integer()) -> integer()",
+                    _ => "(integer()) -> integer(); (atom()) -> atom()",
+                };
+                specs.push_str(&format!("-spec '{module}:{name}'{spec}.\n"));
+                specs.push_str(&format!("'{module}:{name}'(X) -> X.\n"));
+            }
+        }
+        let fixture = format!(
+            "//- /src/eqwalizer_types.erl\n-module(eqwalizer_types).\n-export_type([{}]).\n{types}\
+             //- /src/eqwalizer_specs.erl\n-module(eqwalizer_specs).\n-export([{}]).\n{specs}",
+            exports.join(", "),
+            fun_exports.join(", ")
+        );
+        let (db, files, _) = RootDatabase::with_many_files(&fixture);
+        let project_id = db.file_project_id(files[0]).unwrap();
+
+        // The grouping as done entry by entry before
+        let stub = db
+            .transitive_stub(project_id, ModuleName::new("eqwalizer_types"))
+            .unwrap();
+        let mut expected: BTreeMap<ModuleName, BTreeMap<eqwalizer::Id, _>> = BTreeMap::new();
+        for (id, type_decl) in stub.types.iter() {
+            let (module_name, ty_name) = id.name.split_once(':').unwrap();
+            let id = eqwalizer::Id {
+                name: ty_name.into(),
+                arity: id.arity,
+            };
+            expected
+                .entry(ModuleName::new(module_name))
+                .or_default()
+                .insert(id, type_decl.clone());
+        }
+
+        let custom_types = db.custom_types(project_id).unwrap();
+        assert_eq!(custom_types.len(), 4);
+        assert!(custom_types.values().all(|types| types.len() == 6));
+        assert_eq!(*custom_types, expected);
+
+        let stub = db
+            .transitive_stub(project_id, ModuleName::new("eqwalizer_specs"))
+            .unwrap();
+        let mut expected: BTreeMap<ModuleName, BTreeMap<eqwalizer::Id, _>> = BTreeMap::new();
+        for (id, fun_spec) in stub.specs.iter() {
+            let (module_name, fun_name) = id.name.split_once(':').unwrap();
+            let id = eqwalizer::Id {
+                name: fun_name.into(),
+                arity: id.arity,
+            };
+            expected
+                .entry(ModuleName::new(module_name))
+                .or_default()
+                .insert(id, fun_spec.clone());
+        }
+        let custom_fun_specs = db.custom_fun_specs(project_id).unwrap();
+        assert_eq!(custom_fun_specs.len(), 4);
+        assert!(custom_fun_specs.values().all(|specs| specs.len() == 1));
+        assert_eq!(*custom_fun_specs, expected);
+
+        // Overloaded spec names stop at the next `:`, so `g:h` replaces `g`
+        let mut expected: BTreeMap<ModuleName, BTreeMap<eqwalizer::Id, _>> = BTreeMap::new();
+        for (id, overloaded_fun_spec) in stub.overloaded_specs.iter() {
+            let parts: Vec<&str> = id.name.split(":").collect();
+            let id = eqwalizer::Id {
+                name: parts[1].into(),
+                arity: id.arity,
+            };
+            expected
+                .entry(ModuleName::new(parts[0]))
+                .or_default()
+                .insert(id, overloaded_fun_spec.clone());
+        }
+        let custom_overloaded_fun_specs = db.custom_overloaded_fun_specs(project_id).unwrap();
+        assert_eq!(custom_overloaded_fun_specs.len(), 4);
+        assert!(
+            custom_overloaded_fun_specs
+                .values()
+                .all(|specs| specs.len() == 1)
+        );
+        assert_eq!(*custom_overloaded_fun_specs, expected);
+    }
+
+    #[test]
+    fn test_contractivity_errors() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(rec).
+-export_type([list/0, loop/0]).
+-type list() :: nil | {cons, integer(), list()}.
+-type loop() :: loop() | atom().
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+
+        let errors = db
+            .contractivity_errors(project_id, ModuleName::new("rec"))
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].id.to_string(), "loop/0");
+        assert_eq!(errors[0].to_string(), "type loop/0 is not contractive");
+    }
+
+    #[test]
+    fn test_transitive_stub_is_deterministic() {
+        // `a` and `b` refer to each other, `c` refers to a type that
+        // doesn't exist
+        let fixture = r#"
+//- /src/wide.erl
+-module(wide).
+-export_type([t/0, u/0]).
+-type t() :: a:t() | b:t().
+-type u() :: c:t() | a:t().
+//- /src/a.erl
+-module(a).
+-export_type([t/0]).
+-type t() :: nil | {a, b:t()}.
+//- /src/b.erl
+-module(b).
+-export_type([t/0]).
+-type t() :: nil | {b, a:t()}.
+//- /src/c.erl
+-module(c).
+-export_type([t/0]).
+-type t() :: {c, d:missing()}.
+"#;
+        let transitive_stub = || {
+            let (db, files, _) = RootDatabase::with_many_files(fixture);
+            let project_id = db.file_project_id(files[0]).unwrap();
+            db.transitive_stub(project_id, ModuleName::new("wide"))
+                .unwrap()
+        };
+
+        let stub = transitive_stub();
+        let t = eqwalizer::Id {
+            name: "t".into(),
+            arity: 0,
+        };
+        let u = eqwalizer::Id {
+            name: "u".into(),
+            arity: 0,
+        };
+        assert!(stub.types.contains_key(&t));
+        assert!(!stub.types.contains_key(&u));
+        for _ in 0..5 {
+            assert_eq!(transitive_stub(), stub);
+        }
+    }
+
+    #[test]
+    fn test_diagnostics_diff() {
+        let diagnostic = |code: &str, start: u32| EqwalizerDiagnostic {
+            range: TextRange::new(start.into(), (start + 1).into()),
+            message: format!("{code} at {start}"),
+            uri: String::new(),
+            code: code.to_string(),
+            expression: None,
+            explanation: None,
+            diagnostic: None,
+        };
+        let diagnostics =
+            |errors: Vec<(&str, Vec<EqwalizerDiagnostic>)>| EqwalizerDiagnostics::Diagnostics {
+                errors: errors
+                    .into_iter()
+                    .map(|(module, diagnostics)| (module.to_string(), diagnostics))
+                    .collect(),
+                type_info: Default::default(),
+            };
+        let lenient = diagnostics(vec![
+            ("a", vec![diagnostic("incompatible_types", 0)]),
+            ("b", vec![diagnostic("redundant_fixme", 3)]),
+        ]);
+        let strict = diagnostics(vec![
+            (
+                "a",
+                vec![
+                    diagnostic("incompatible_types", 0),
+                    diagnostic("incompatible_types", 5),
+                ],
+            ),
+            ("c", vec![diagnostic("dynamic_lambda", 1)]),
+        ]);
+
+        assert_eq!(
+            lenient.diff(&strict),
+            DiagnosticsDiff::Diff {
+                added: BTreeMap::from([
+                    ("a".to_string(), vec![diagnostic("incompatible_types", 5)]),
+                    ("c".to_string(), vec![diagnostic("dynamic_lambda", 1)]),
+                ]),
+                removed: BTreeMap::from([(
+                    "b".to_string(),
+                    vec![diagnostic("redundant_fixme", 3)]
+                )]),
+                common: BTreeMap::from([(
+                    "a".to_string(),
+                    vec![diagnostic("incompatible_types", 0)]
+                )]),
+            }
+        );
+        assert_eq!(
+            lenient.diff(&EqwalizerDiagnostics::Error("crashed".to_string())),
+            DiagnosticsDiff::Incomparable
+        );
+    }
+
+    #[test]
+    fn test_set_eqwalizer_config_validated() {
+        let mut db = RootDatabase::default();
+        let config = EqwalizerConfig {
+            max_type_expansion_depth: 10,
+            ebin_dirs: vec!["_build/ebin".to_string()],
+            ..EqwalizerConfig::default()
+        };
+        assert_eq!(
+            set_eqwalizer_config_validated(&mut db, config.clone()),
+            Ok(())
+        );
+        assert_eq!(*db.eqwalizer_config(), config);
+
+        let invalid = EqwalizerConfig {
+            max_type_expansion_depth: 0,
+            ..EqwalizerConfig::default()
+        };
+        assert_eq!(
+            set_eqwalizer_config_validated(&mut db, invalid),
+            Err(EqwalizerConfigError::ZeroMaxTypeExpansionDepth)
+        );
+        let invalid = EqwalizerConfig {
+            ebin_dirs: vec!["ebin".to_string(), " ".to_string()],
+            ..EqwalizerConfig::default()
+        };
+        assert_eq!(
+            set_eqwalizer_config_validated(&mut db, invalid),
+            Err(EqwalizerConfigError::EmptyEbinDir(1))
+        );
+        // The config is left as it was
+        assert_eq!(*db.eqwalizer_config(), config);
+    }
+
+    #[test]
+    fn test_infer_module_from_file_stem() {
+        let (mut db, files, _) = RootDatabase::with_many_files(
+            r#"
+//- /src/bar.erl
+-module(foo).
+-export_type([t/0]).
+-type t() :: integer().
+"#,
+        );
+        let project_id = db.file_project_id(files[0]).unwrap();
+        let foo = ModuleName::new("foo");
+
+        assert!(matches!(
+            db.converted_stub(project_id, foo.clone()),
+            Err(Error::ModuleNotFound(_))
+        ));
+
+        db.set_eqwalizer_config(Arc::new(EqwalizerConfig {
+            infer_module_from_file_stem: true,
+            ..EqwalizerConfig::default()
+        }));
+        assert_eq!(
+            db.module_by_declared_name(project_id, foo.clone()),
+            Some(ModuleName::new("bar"))
+        );
+        assert_eq!(
+            *db.declared_modules(project_id),
+            BTreeMap::from([(foo.clone(), ModuleName::new("bar"))])
+        );
+        let types = db.type_ids(project_id, foo).unwrap();
+        assert!(types.contains_key(&eqwalizer::Id {
+            name: "t".into(),
+            arity: 0
+        }));
+    }
+
+    #[test]
+    fn test_exported_functions() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(exports).
+-export([a/0]).
+-export([b/1]).
+-export_type([t/0]).
+-type t() :: ok.
+-type u() :: ok.
+a() -> ok.
+b(X) -> X.
+c() -> ok.
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        let id = |name: &str, arity| eqwalizer::Id {
+            name: name.into(),
+            arity,
+        };
+
+        let functions = db
+            .exported_functions(project_id, ModuleName::new("exports"))
+            .unwrap();
+        assert_eq!(*functions, BTreeSet::from([id("a", 0), id("b", 1)]));
+        let types = db
+            .exported_types(project_id, ModuleName::new("exports"))
+            .unwrap();
+        assert_eq!(*types, BTreeSet::from([id("t", 0)]));
+    }
+
+    #[test]
+    fn test_declared_behaviours() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(server).
+-behaviour(gen_server).
+-behavior(supervisor).
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+
+        let behaviours = db
+            .declared_behaviours(project_id, ModuleName::new("server"))
+            .unwrap();
+        assert_eq!(
+            *behaviours,
+            vec![ModuleName::new("gen_server"), ModuleName::new("supervisor")]
+        );
+    }
+
+    #[test]
+    fn test_behaviour_implementors() {
+        let (db, files, _) = RootDatabase::with_many_files(
+            r#"
+//- /src/server.erl
+-module(server).
+-behaviour(gen_server).
+//- /src/other_server.erl
+-module(other_server).
+-behavior(gen_server).
+-behaviour(supervisor).
+//- /src/unrelated.erl
+-module(unrelated).
+-behaviour(supervisor).
+"#,
+        );
+        let project_id = db.file_project_id(files[0]).unwrap();
+
+        let implementors = db.behaviour_implementors(project_id, ModuleName::new("gen_server"));
+        assert_eq!(
+            *implementors,
+            vec![ModuleName::new("other_server"), ModuleName::new("server")]
+        );
+        assert!(
+            db.behaviour_implementors(project_id, ModuleName::new("gen_statem"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_type_decl_traced() {
+        let fixture = r#"
+//- /src/foo.erl
+-module(foo).
+-export_type([t/0, u/0]).
+-type t() :: integer().
+-type u() :: binary().
+//- /src/eqwalizer_types.erl
+-module(eqwalizer_types).
+-export_type(['foo:t'/0]).
+-type 'foo:t'() :: atom().
+"#;
+        let (db, files, _) = RootDatabase::with_many_files(fixture);
+        let project_id = db.file_project_id(files[0]).unwrap();
+        let foo = ModuleName::new("foo");
+        let id = |name: &str| eqwalizer::Id {
+            name: name.into(),
+            arity: 0,
+        };
+
+        for (name, source) in [
+            ("t", TypeDeclSource::Custom),
+            ("u", TypeDeclSource::Stub),
+            ("v", TypeDeclSource::NotFound),
+        ] {
+            let (decl, traced) = type_decl_traced(&db, project_id, foo.clone(), id(name)).unwrap();
+            assert_eq!(traced, source, "{name}");
+            assert_eq!(
+                decl,
+                db.type_decl(project_id, foo.clone(), id(name)).unwrap(),
+                "{name}"
+            );
+            assert_eq!(decl.is_some(), source != TypeDeclSource::NotFound);
+        }
+        let custom = db.custom_types(project_id).unwrap();
+        let (decl, _) = type_decl_traced(&db, project_id, foo.clone(), id("t")).unwrap();
+        assert_eq!(decl.as_ref(), custom[&foo].get(&id("t")));
+    }
+
+    #[test]
+    fn test_is_opaque_type() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(foo).
+-export_type([t/0, o/1]).
+-type t() :: integer().
+-opaque o(X) :: [X].
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        let foo = ModuleName::new("foo");
+        let id = |name: &str, arity| eqwalizer::Id {
+            name: name.into(),
+            arity,
+        };
+
+        for (name, arity, opaque) in [("o", 1, true), ("t", 0, false), ("missing", 0, false)] {
+            assert_eq!(
+                db.is_opaque_type(project_id, foo.clone(), id(name, arity)),
+                Ok(opaque),
+                "{name}/{arity}"
+            );
+        }
+        // Opacity is not part of what eqWAlizer is sent
+        let bytes = db
+            .type_decl_bytes(project_id, foo, id("o", 1))
+            .unwrap()
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&bytes).contains("opaque"));
+    }
+
+    #[test]
+    fn test_all_type_decl_bytes() {
+        let fixture = r#"
+//- /src/foo.erl
+-module(foo).
+-export_type([t/0, u/0]).
+-type t() :: integer().
+-type u() :: binary().
+-type v(X) :: [X].
+//- /src/eqwalizer_types.erl
+-module(eqwalizer_types).
+-export_type(['foo:t'/0, 'foo:w'/0]).
+-type 'foo:t'() :: atom().
+-type 'foo:w'() :: pid().
+"#;
+        let (db, files, _) = RootDatabase::with_many_files(fixture);
+        let project_id = db.file_project_id(files[0]).unwrap();
+        let foo = ModuleName::new("foo");
+
+        let all = db.all_type_decl_bytes(project_id, foo.clone()).unwrap();
+        let names: Vec<_> = all.keys().map(|id| id.to_string()).collect();
+        assert_eq!(names, vec!["t/0", "u/0", "v/1", "w/0"]);
+        for (id, bytes) in all.iter() {
+            let single = db
+                .type_decl_bytes(project_id, foo.clone(), id.clone())
+                .unwrap()
+                .unwrap();
+            assert_eq!(bytes, &*single, "{id}");
+        }
+    }
+
+    #[test]
+    fn test_module_config_overrides() {
+        let fixture = r#"
+//- /src/foo.erl
+-module(foo).
+% eqwalizer:config #{enable_custom_overrides => false}
+-export_type([t/0]).
+-type t() :: integer().
+//- /src/bar.erl
+-module(bar).
+-export_type([t/0]).
+-type t() :: integer().
+//- /src/eqwalizer_types.erl
+-module(eqwalizer_types).
+-export_type(['foo:t'/0, 'bar:t'/0]).
+-type 'foo:t'() :: atom().
+-type 'bar:t'() :: atom().
+"#;
+        let (db, files, _) = RootDatabase::with_many_files(fixture);
+        let project_id = db.file_project_id(files[0]).unwrap();
+        let id = eqwalizer::Id {
+            name: "t".into(),
+            arity: 0,
+        };
+
+        let foo = ModuleName::new("foo");
+        assert!(
+            !db.module_eqwalizer_config(project_id, foo.clone())
+                .enable_custom_overrides
+        );
+        let stub_decl = db
+            .transitive_stub(project_id, foo.clone())
+            .unwrap()
+            .types
+            .get(&id)
+            .cloned();
+        assert!(stub_decl.is_some());
+        assert_eq!(
+            db.type_decl(project_id, foo, id.clone()).unwrap(),
+            stub_decl
+        );
+
+        let bar = ModuleName::new("bar");
+        assert!(
+            db.module_eqwalizer_config(project_id, bar.clone())
+                .enable_custom_overrides
+        );
+        let stub_decl = db
+            .transitive_stub(project_id, bar.clone())
+            .unwrap()
+            .types
+            .get(&id)
+            .cloned();
+        assert_ne!(db.type_decl(project_id, bar, id).unwrap(), stub_decl);
+    }
+
+    #[test]
+    fn test_module_config_directives() {
+        let (mut db, file_id) = RootDatabase::with_single_file(
+            r#"
+%% elp:generated by protoc
+-module(generated).
+% eqwalizer:config #{stale_beam => error, max_type_expansion_depth => 0, skip => true}
+%% eqwalizer:config #{enable_custom_overrides => 'a,b', max_type_expansion_depth => 7}
+f() -> ok.
+"#,
+        );
+        let project_id = db.file_project_id(file_id).unwrap();
+        let module = ModuleName::new("generated");
+        let text = db.file_text(file_id).text(&db);
+
+        let directives = db.config_directives(file_id);
+        assert_eq!(
+            directives.overrides,
+            Some(EqwalizerConfigOverrides {
+                max_type_expansion_depth: Some(7),
+                stale_beam: Some(StaleBeam::Error),
+                ..EqwalizerConfigOverrides::default()
+            })
+        );
+        let invalid: Vec<&str> = directives
+            .invalid
+            .iter()
+            .map(|invalid| &text[invalid.range])
+            .collect();
+        assert_eq!(
+            invalid,
+            vec![
+                "max_type_expansion_depth => 0",
+                "skip => true",
+                "enable_custom_overrides => 'a,b'",
+            ]
+        );
+
+        // Invalid settings are reported even if the module isn't checked
+        db.set_eqwalizer_config(Arc::new(EqwalizerConfig {
+            skip_generated: true,
+            ..EqwalizerConfig::default()
+        }));
+        let (diagnostics, _) = db.module_diagnostics(project_id, module.as_str().to_string());
+        let EqwalizerDiagnostics::Diagnostics { errors, .. } = &*diagnostics else {
+            panic!("unexpected {diagnostics:?}");
+        };
+        let ranges: Vec<TextRange> = errors["generated"].iter().map(|d| d.range).collect();
+        let expected: Vec<TextRange> = directives.invalid.iter().map(|d| d.range).collect();
+        assert_eq!(ranges, expected);
+
+        // Edits that leave the directives alone don't invalidate the
+        // module config
+        let config = db.module_eqwalizer_config(project_id, module.clone());
+        db.set_file_text(
+            file_id,
+            Arc::from(text.replace("f() -> ok.", "f() -> not_ok.")),
+        );
+        assert!(Arc::ptr_eq(
+            &config,
+            &db.module_eqwalizer_config(project_id, module.clone())
+        ));
+        db.set_file_text(file_id, Arc::from(text.replace("=> 7", "=> 8")));
+        assert_eq!(
+            db.module_eqwalizer_config(project_id, module)
+                .max_type_expansion_depth,
+            8
+        );
+    }
 }
@@ -8,6 +8,8 @@
  * above-listed licenses.
  */
 
+use std::cell::RefCell;
+use std::panic::AssertUnwindSafe;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -20,48 +22,74 @@ use elp_base_db::SourceDatabase;
 use elp_base_db::path_for_file;
 use elp_base_db::salsa;
 use elp_base_db::salsa::Database;
+use elp_eqwalizer::ast::Error;
+use elp_erlang_service::Connection;
+use elp_erlang_service::DiagnosticLocation;
 use elp_erlang_service::Format;
 use elp_erlang_service::IncludeType;
+use elp_erlang_service::KNOWN_FEATURES;
 use elp_erlang_service::ParseError;
+use elp_erlang_service::ParseErrorCode;
 use elp_erlang_service::ParseResult;
+use elp_project_model::macro_name;
+use elp_project_model::merge_macros;
+use elp_syntax::AstNode;
+use elp_syntax::TextRange;
+use elp_syntax::ast;
+use elp_syntax::ast::ClauseSeparator;
+use elp_types_db::eqwalizer::Id;
+use elp_types_db::eqwalizer::expr::Body;
+use elp_types_db::eqwalizer::form::ExternalForm;
+use elp_types_db::eqwalizer::form::FunDecl;
 
+use crate::LineIndex;
 use crate::LineIndexDatabase;
 use crate::erlang_service::CompileOption;
 use crate::erlang_service::ParseRequest;
 use crate::metadata;
+use crate::metadata::Annotation;
 use crate::metadata::Metadata;
 
 pub trait AstLoader {
+    /// `project_id` is `None` for files outside of any project. The text
+    /// parsed is `file_text`, or the one of `file_id` if it is `None`.
+    #[allow(clippy::too_many_arguments)]
     fn load_ast(
         &self,
-        project_id: ProjectId,
+        project_id: Option<ProjectId>,
         file_id: FileId,
         path: &AbsPath,
         macros: &[eetf::Term],
         parse_transforms: &[eetf::Term],
+        features: &[CompileOption],
         elp_metadata: eetf::Term,
+        file_text: Option<Arc<str>>,
     ) -> ParseResult;
 }
 
 impl AstLoader for crate::RootDatabase {
     fn load_ast(
         &self,
-        project_id: ProjectId,
+        project_id: Option<ProjectId>,
         file_id: FileId,
         path: &AbsPath,
         macros: &[eetf::Term],
         parse_transforms: &[eetf::Term],
+        features: &[CompileOption],
         elp_metadata: eetf::Term,
+        file_text: Option<Arc<str>>,
     ) -> ParseResult {
-        let mut macros = macros.to_vec();
-        macros.push(eetf::Atom::from("ELP_ERLANG_SERVICE").into());
-        let options = vec![
+        let (macros, overridden) = erlang_service_macros(macros);
+        let mut options = vec![
             CompileOption::Macros(macros),
             CompileOption::ParseTransforms(parse_transforms.to_vec()),
             CompileOption::ElpMetadata(elp_metadata),
         ];
+        options.extend(features.iter().cloned());
         let path: PathBuf = path.to_path_buf().into();
-        let file_text = SourceDatabase::file_text(self, file_id).text(self);
+        let file_text =
+            file_text.unwrap_or_else(|| SourceDatabase::file_text(self, file_id).text(self));
+        let (file_text, unsupported_encoding) = normalize_encoding(file_text);
         let req = ParseRequest {
             options,
             file_id,
@@ -69,16 +97,131 @@ impl AstLoader for crate::RootDatabase {
             format: Format::OffsetEtf,
             file_text,
         };
-        let erlang_service = self.erlang_service_for(project_id);
+        let erlang_service = match project_id {
+            Some(project_id) => self.erlang_service_for(project_id),
+            None => self.erlang_service_without_project(),
+        };
 
-        erlang_service.request_parse(
+        let resolver_panics = RefCell::new(Vec::new());
+        let mut result = erlang_service.request_parse(
             req,
             || self.unwind_if_revision_cancelled(),
-            &move |file_id, include_type, path| resolve_include(self, file_id, include_type, path),
-        )
+            &catch_resolver_panics(
+                move |file_id, include_type, path| {
+                    resolve_include(self, file_id, include_type, path)
+                },
+                &resolver_panics,
+            ),
+        );
+        for msg in resolver_panics.into_inner() {
+            result.warnings.push(ParseError::elp(
+                path.clone(),
+                ParseErrorCode::IncludeResolutionPanicked,
+                msg,
+            ));
+        }
+        if let Some(mac) = overridden {
+            result.warnings.push(ParseError::elp(
+                path.clone(),
+                ParseErrorCode::ElpMacroOverridden,
+                format!(
+                    "Macro '{ELP_ERLANG_SERVICE}' is defined by ELP, ignoring the project definition {mac}"
+                ),
+            ));
+        }
+        if let Some((range, encoding)) = unsupported_encoding {
+            result.warnings.push(ParseError {
+                path,
+                location: Some(DiagnosticLocation::Normal(range)),
+                msg: format!("Unsupported encoding '{encoding}', the file is parsed as UTF-8"),
+                code: ParseErrorCode::UnsupportedEncoding.code().to_string(),
+            });
+        }
+        result
     }
 }
 
+/// Defined when parsing with the Erlang service, so that code can tell
+/// it apart from the compiler.
+const ELP_ERLANG_SERVICE: &str = "ELP_ERLANG_SERVICE";
+
+/// `macros` with `ELP_ERLANG_SERVICE` defined. Returns the definition
+/// of `ELP_ERLANG_SERVICE` in `macros` too, if it differs from the one
+/// ELP injects and so is ignored.
+fn erlang_service_macros(macros: &[eetf::Term]) -> (Vec<eetf::Term>, Option<eetf::Term>) {
+    let injected: eetf::Term = eetf::Atom::from(ELP_ERLANG_SERVICE).into();
+    let overridden = macros
+        .iter()
+        .find(|mac| macro_name(mac) == Some(ELP_ERLANG_SERVICE) && **mac != injected)
+        .cloned();
+    (merge_macros(macros, &[injected]), overridden)
+}
+
+const UTF8_BOM: char = '\u{feff}';
+
+/// Make `text` suitable for the Erlang service, which expects UTF-8
+/// without a BOM. The result has the same length as `text`, so that the
+/// positions the service reports are positions in `text`:
+/// - a leading UTF-8 BOM is replaced by spaces
+/// - a `coding: latin-1` declaration is rewritten to `utf-8`, since the
+///   file was already decoded when loaded
+///
+/// Returns the range and name of a declared encoding the Erlang compiler
+/// doesn't support as well.
+fn normalize_encoding(text: Arc<str>) -> (Arc<str>, Option<(TextRange, String)>) {
+    let coding = encoding_declaration(&text);
+    let latin1 = coding
+        .as_ref()
+        .filter(|(_, name)| name == "latin-1" || name == "latin1")
+        .map(|(range, _)| *range);
+    let unsupported =
+        coding.filter(|(_, name)| !["utf-8", "utf8", "latin-1", "latin1"].contains(&name.as_str()));
+    if !text.starts_with(UTF8_BOM) && latin1.is_none() {
+        return (text, unsupported);
+    }
+    let mut normalized = match text.strip_prefix(UTF8_BOM) {
+        Some(rest) => format!("{:width$}{rest}", "", width = UTF8_BOM.len_utf8()),
+        None => text.to_string(),
+    };
+    if let Some(range) = latin1 {
+        let range = std::ops::Range::<usize>::from(range);
+        let utf8 = format!("{:width$}", "utf-8", width = range.len());
+        normalized.replace_range(range, &utf8);
+    }
+    (Arc::from(normalized), unsupported)
+}
+
+/// The range and lowercased name of the encoding declared by a
+/// `coding: <name>` comment in the first two lines of `text`, as
+/// recognised by `epp`.
+fn encoding_declaration(text: &str) -> Option<(TextRange, String)> {
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n').take(2) {
+        if let Some(comment) = line.find('%') {
+            let mut rest = &line[comment..];
+            while let Some(idx) = rest.find("coding") {
+                let after = rest[idx + "coding".len()..].trim_start();
+                rest = &rest[idx + "coding".len()..];
+                let Some(value) = after.strip_prefix([':', '=']) else {
+                    continue;
+                };
+                let value = value.trim_start();
+                let len = value
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                    .unwrap_or(value.len());
+                if len == 0 {
+                    continue;
+                }
+                let start = line_start + (line.len() - value.len());
+                let range = TextRange::at((start as u32).into(), (len as u32).into());
+                return Some((range, value[..len].to_ascii_lowercase()));
+            }
+        }
+        line_start += line.len();
+    }
+    None
+}
+
 fn resolve_include(
     db: &dyn RootQueryDb,
     file_id: FileId,
@@ -98,37 +241,346 @@ fn resolve_include(
     ))
 }
 
+/// Wrap an include resolver for the Erlang service, so that a panic while
+/// resolving an include fails that resolution instead of unwinding through
+/// the service. The panics are logged and recorded in `panics`. Salsa
+/// cancellation is propagated as usual.
+fn catch_resolver_panics<'a, R>(
+    resolve: impl Fn(FileId, IncludeType, &str) -> Option<R> + 'a,
+    panics: &'a RefCell<Vec<String>>,
+) -> impl Fn(FileId, IncludeType, &str) -> Option<R> + 'a {
+    move |file_id, include_type, path| match std::panic::catch_unwind(AssertUnwindSafe(|| {
+        resolve(file_id, include_type, path)
+    })) {
+        Ok(resolved) => resolved,
+        Err(payload) => {
+            if payload.is::<salsa::Cancelled>() {
+                std::panic::resume_unwind(payload);
+            }
+            let reason = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown cause");
+            let msg = format!("Resolving include \"{path}\" panicked: {reason}");
+            log::error!("{file_id:?}: {msg}");
+            panics.borrow_mut().push(msg);
+            None
+        }
+    }
+}
+
+/// How a `.erl` file that doesn't belong to any application is
+/// reported, with code `L0003`, unless `parse_files_without_app` is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum UnknownApplicationSeverity {
+    /// The file is not parsed, and reported as an error.
+    #[default]
+    Error,
+    /// The file is parsed with default settings, and reported as a warning.
+    Warning,
+    /// The file is parsed with default settings, and not reported.
+    Ignore,
+}
+
 #[ra_ap_query_group_macro::query_group(ErlAstDatabaseStorage)]
 pub trait ErlAstDatabase: RootQueryDb + AstLoader + LineIndexDatabase {
+    /// Whether `.erl` files that don't belong to any application are
+    /// parsed with default settings, rather than reported as `L0003`.
+    /// Header files always are.
+    #[salsa::input]
+    fn parse_files_without_app(&self) -> bool;
+
+    #[salsa::input]
+    fn unknown_application_severity(&self) -> UnknownApplicationSeverity;
+
     fn module_ast(&self, file_id: FileId) -> Arc<ParseResult>;
+    /// The text parsed by `reparse_function` for `function_id`, if
+    /// `file_id` defines it: the text of the function, with the other
+    /// functions replaced by stubs. This only changes with the function
+    /// and the forms it depends on, so that editing another function
+    /// doesn't reparse it.
+    fn function_source(&self, file_id: FileId, function_id: Id) -> Option<Arc<str>>;
+    /// The declaration of `function_id` in `file_id`, parsed without
+    /// the bodies of the other functions of the module. Falls back to
+    /// `module_ast` when the function can't be parsed on its own.
+    fn reparse_function(
+        &self,
+        file_id: FileId,
+        function_id: Id,
+    ) -> Result<Option<Arc<FunDecl>>, Error>;
+    /// `module_ast` and `file_line_index` for `file_id`, from the same
+    /// revision, to map the positions of parse errors.
+    fn module_ast_with_lines(&self, file_id: FileId) -> (Arc<ParseResult>, Arc<LineIndex>);
     fn elp_metadata(&self, file_id: FileId) -> Metadata;
+    /// The `% elp:ignore` annotations of `file_id`, see `Metadata::elp_ignores`.
+    fn elp_ignores(&self, file_id: FileId) -> Arc<Vec<Annotation>>;
 }
 
 fn module_ast(db: &dyn ErlAstDatabase, file_id: FileId) -> Arc<ParseResult> {
     // Context for T171541590
     let _ = stdx::panic_context::enter(format!("\nmodule_ast: {file_id:?}"));
+    Arc::new(load_module_ast(db, file_id, None))
+}
+
+/// Parse `file_id` with the settings of its application, see `AstLoader::load_ast`.
+fn load_module_ast(
+    db: &dyn ErlAstDatabase,
+    file_id: FileId,
+    file_text: Option<Arc<str>>,
+) -> ParseResult {
     let root_id = db.file_source_root(file_id).source_root_id(db);
     let root = db.source_root(root_id).source_root(db);
     let path = root.path_for_file(&file_id).unwrap().as_path().unwrap();
     let metadata = db.elp_metadata(file_id);
-    let app_data = if let Some(app_data) = db.file_app_data(file_id) {
-        app_data
-    } else {
-        return Arc::new(ParseResult::error(ParseError {
-            path: path.to_path_buf().into(),
-            location: None,
-            msg: "Unknown application".to_string(),
-            code: "L0003".to_string(),
-        }));
+    let Some(app_data) = db.file_app_data(file_id) else {
+        // Headers are often opened outside of any application, e.g. when
+        // browsing dependencies, parse them without app-specific settings.
+        let severity = if path.extension() == Some("hrl") || db.parse_files_without_app() {
+            UnknownApplicationSeverity::Ignore
+        } else {
+            db.unknown_application_severity()
+        };
+        let unknown_application = || {
+            ParseError::elp(
+                path.to_path_buf().into(),
+                ParseErrorCode::UnknownApplication,
+                "Unknown application".to_string(),
+            )
+        };
+        return match severity {
+            UnknownApplicationSeverity::Error => ParseResult::error(unknown_application()),
+            UnknownApplicationSeverity::Warning => {
+                let mut result = db.load_ast(
+                    None,
+                    file_id,
+                    path,
+                    &[],
+                    &[],
+                    &[],
+                    metadata.into(),
+                    file_text,
+                );
+                result.warnings.push(unknown_application());
+                result
+            }
+            UnknownApplicationSeverity::Ignore => db.load_ast(
+                None,
+                file_id,
+                path,
+                &[],
+                &[],
+                &[],
+                metadata.into(),
+                file_text,
+            ),
+        };
     };
-    Arc::new(db.load_ast(
-        app_data.project_id,
+    let project_data = db.project_data(app_data.project_id).project_data(db);
+    let macros = app_data.effective_macros(&project_data);
+    let parse_transforms = app_data.effective_parse_transforms(&project_data);
+    let (features, unknown_features) = feature_options(project_data.features.as_deref());
+    let mut result = db.load_ast(
+        Some(app_data.project_id),
         file_id,
         path,
-        &app_data.macros,
-        &app_data.parse_transforms,
+        &macros,
+        &parse_transforms,
+        &features,
         metadata.into(),
-    ))
+        file_text,
+    );
+    // A misconfigured feature should not prevent the module from being
+    // parsed, so report it alongside the other warnings.
+    result
+        .warnings
+        .extend(unknown_features.into_iter().map(|name| {
+            ParseError::elp(
+                path.to_path_buf().into(),
+                ParseErrorCode::UnknownFeature,
+                format!("Unknown feature '{name}' in project configuration"),
+            )
+        }));
+    result
+}
+
+/// The name and arity of the function a clause belongs to, and a stub
+/// of the clause with the same separator, e.g. `f(_,_)->a;`. `None` for
+/// clauses defined by macros, or missing their separator.
+fn clause_stub(decl: &ast::FunDecl) -> Option<((String, usize), String)> {
+    let ast::FunctionOrMacroClause::FunctionClause(clause) = decl.clause()? else {
+        return None;
+    };
+    let name = clause.name()?;
+    let arity = clause.args()?.args().count();
+    let separator = match decl.separator()? {
+        (ClauseSeparator::Semi | ClauseSeparator::Dot, token) => token,
+        (ClauseSeparator::Missing, _) => return None,
+    };
+    let stub = format!(
+        "{}({})->a{}",
+        name.syntax().text(),
+        vec!["_"; arity].join(","),
+        separator.text()
+    );
+    Some(((name.text()?, arity), stub))
+}
+
+fn function_source(db: &dyn ErlAstDatabase, file_id: FileId, function_id: Id) -> Option<Arc<str>> {
+    let text = db.file_text(file_id).text(db);
+    let forms: Vec<ast::Form> = db.parse(file_id).tree().forms().collect();
+    let stubs: Vec<Option<((String, usize), String)>> = forms
+        .iter()
+        .map(|form| match form {
+            ast::Form::FunDecl(decl) => clause_stub(decl),
+            _ => None,
+        })
+        .collect();
+    let is_function = |stub: &Option<((String, usize), String)>| {
+        stub.as_ref().is_some_and(|((name, arity), _)| {
+            name == function_id.name.as_str() && *arity as u32 == function_id.arity
+        })
+    };
+    let first = stubs.iter().position(is_function)?;
+    let last = first + stubs[first..].iter().take_while(|s| is_function(s)).count() - 1;
+    let start: usize = forms[first].syntax().text_range().start().into();
+    let end: usize = forms[last].syntax().text_range().end().into();
+
+    // The forms before the function keep their offsets, so that the
+    // positions in the AST are positions in the file.
+    let mut before = text.as_bytes()[..start].to_vec();
+    for (form, stub) in forms[..first].iter().zip(&stubs) {
+        let Some((_, stub)) = stub else { continue };
+        let range = std::ops::Range::<usize>::from(form.syntax().text_range());
+        if stub.len() > range.len() {
+            continue;
+        }
+        before[range.start..range.start + stub.len()].copy_from_slice(stub.as_bytes());
+        for byte in &mut before[range.start + stub.len()..range.end] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    }
+    let mut source = String::from_utf8(before).ok()?;
+    source.push_str(&text[start..end]);
+    // The forms after it only need to be declared, their offsets don't
+    // matter
+    for (form, stub) in forms[last + 1..].iter().zip(&stubs[last + 1..]) {
+        source.push('\n');
+        match stub {
+            Some((_, stub)) => source.push_str(stub),
+            None => source.push_str(&form.syntax().text().to_string()),
+        }
+    }
+    source.push('\n');
+    Some(Arc::from(source))
+}
+
+fn reparse_function(
+    db: &dyn ErlAstDatabase,
+    file_id: FileId,
+    function_id: Id,
+) -> Result<Option<Arc<FunDecl>>, Error> {
+    let find_decl = |ast: &Vec<u8>| -> Result<Option<Arc<FunDecl>>, Error> {
+        Ok(elp_eqwalizer::ast::from_bytes(ast, false)?
+            .forms
+            .into_iter()
+            .find_map(|form| match form {
+                ExternalForm::FunDecl(decl) if decl.id == function_id => Some(Arc::new(decl)),
+                _ => None,
+            }))
+    };
+    if let Some(text) = db.function_source(file_id, function_id.clone()) {
+        let result = load_module_ast(db, file_id, Some(text));
+        if result.is_ok() {
+            if let Ok(Some(decl)) = find_decl(&result.ast) {
+                return Ok(Some(decl));
+            }
+        }
+    }
+    find_decl(&module_ast_etf(db, file_id)?)
+}
+
+fn module_ast_with_lines(
+    db: &dyn ErlAstDatabase,
+    file_id: FileId,
+) -> (Arc<ParseResult>, Arc<LineIndex>) {
+    (db.module_ast(file_id), db.file_line_index(file_id))
+}
+
+/// The raw `OffsetEtf` bytes returned by the Erlang service for
+/// `file_id`, e.g. to dump them for inspection with `erl` when
+/// converting them fails. This shares the bytes of `module_ast` rather
+/// than being a query of its own.
+pub fn module_ast_etf(db: &dyn ErlAstDatabase, file_id: FileId) -> Result<Arc<Vec<u8>>, Error> {
+    let result = db.module_ast(file_id);
+    if result.is_ok() {
+        Ok(result.ast.clone())
+    } else {
+        Err(Error::ParseError)
+    }
+}
+
+const FRAGMENT_HEADER: &str = "-module(elp_fragment).\nfragment() ->\n";
+
+/// Where fragments start in the synthetic module they are parsed in,
+/// positions in the result of `parse_fragment` are offsets in it.
+pub const FRAGMENT_OFFSET: usize = FRAGMENT_HEADER.len();
+
+/// Parse a sequence of comma-separated expressions, without the
+/// boilerplate of a whole module, e.g. to test analyses. The fragment
+/// becomes the body of a synthetic function.
+pub fn parse_fragment(erlang_service: &Connection, text: &str) -> Result<Body, Error> {
+    let req = ParseRequest {
+        options: vec![],
+        file_id: FileId::from_raw(0),
+        path: PathBuf::from("elp_fragment.erl"),
+        format: Format::OffsetEtf,
+        file_text: Arc::from(format!("{FRAGMENT_HEADER}{text}\n.\n")),
+    };
+    let result = erlang_service.request_parse(req, || (), &|_, _, _| None);
+    if !result.is_ok() {
+        let messages: Vec<&str> = result.errors.iter().map(|err| err.msg.as_str()).collect();
+        return Err(Error::InvalidFragment(messages.join("\n")));
+    }
+    let ast = elp_eqwalizer::ast::from_bytes(&result.ast, false)?;
+    ast.forms
+        .into_iter()
+        .find_map(|form| match form {
+            ExternalForm::FunDecl(decl) if decl.id.name.as_str() == "fragment" => {
+                decl.clauses.into_iter().next().map(|clause| clause.body)
+            }
+            _ => None,
+        })
+        .ok_or_else(|| Error::InvalidFragment("no expressions".to_string()))
+}
+
+/// Turn the configured list of enabled features into compile options,
+/// explicitly disabling every other known feature, which a module can
+/// still enable with its own `-feature` attribute. Returns the
+/// configured names the erlang service does not know about as well.
+fn feature_options(enabled: Option<&[String]>) -> (Vec<CompileOption>, Vec<String>) {
+    let Some(enabled) = enabled else {
+        return (vec![], vec![]);
+    };
+    let options = KNOWN_FEATURES
+        .iter()
+        .map(|name| CompileOption::Feature {
+            name: name.to_string(),
+            enable: enabled.iter().any(|f| f == name),
+        })
+        .collect();
+    let unknown = enabled
+        .iter()
+        .filter(|f| !KNOWN_FEATURES.contains(&f.as_str()))
+        .cloned()
+        .collect();
+    (options, unknown)
+}
+
+fn elp_ignores(db: &dyn ErlAstDatabase, file_id: FileId) -> Arc<Vec<Annotation>> {
+    Arc::new(db.elp_metadata(file_id).elp_ignores())
 }
 
 fn elp_metadata(db: &dyn ErlAstDatabase, file_id: FileId) -> Metadata {
@@ -137,3 +589,371 @@ fn elp_metadata(db: &dyn ErlAstDatabase, file_id: FileId) -> Metadata {
     let source = db.parse(file_id);
     metadata::collect_metadata(&line_index, &file_text, &source)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use elp_base_db::AbsPathBuf;
+    use elp_base_db::AppDataId;
+    use elp_base_db::FileSet;
+    use elp_base_db::ProjectData;
+    use elp_base_db::SourceRoot;
+    use elp_base_db::SourceRootId;
+    use elp_base_db::VfsPath;
+    use elp_base_db::fixture::WithFixture;
+    use elp_project_model::CompileConfig;
+    use elp_project_model::MacroValue;
+    use elp_types_db::eqwalizer::expr::Expr;
+
+    use super::*;
+    use crate::RootDatabase;
+
+    /// Add `path` to `db` in a source root of its own, outside of any
+    /// application.
+    fn add_file_without_app(db: &mut RootDatabase, path: &str, text: &str) -> FileId {
+        let file_id = FileId::from_raw(100);
+        let root_id = SourceRootId(100);
+        let app_data_id = AppDataId(100);
+        let path = VfsPath::new_real_path(path.to_string());
+        let mut file_set = FileSet::default();
+        file_set.insert(file_id, path.clone());
+        db.set_source_root(root_id, Arc::new(SourceRoot::new(file_set)));
+        db.set_file_source_root(file_id, root_id, Some(path));
+        db.set_app_data_by_id(app_data_id, None);
+        db.set_app_data_id(root_id, app_data_id);
+        db.set_file_text(file_id, Arc::from(text));
+        file_id
+    }
+
+    #[test]
+    fn header_without_app() {
+        let (mut db, _) = RootDatabase::with_single_file("-module(main).");
+        let file_id =
+            add_file_without_app(&mut db, "/deps/lib/include/lib.hrl", "-define(X, 1).\n");
+
+        assert!(db.file_app_data(file_id).is_none());
+        let result = db.module_ast(file_id);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn module_without_app() {
+        let (mut db, _) = RootDatabase::with_single_file("-module(main).");
+        let file_id = add_file_without_app(&mut db, "/loose/loose.erl", "-module(loose).\n");
+
+        let result = db.module_ast(file_id);
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| e.code.as_str())
+                .collect::<Vec<_>>(),
+            vec![ParseErrorCode::UnknownApplication.code()]
+        );
+
+        db.set_parse_files_without_app(true);
+        let result = db.module_ast(file_id);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn unknown_application_severity() {
+        let (mut db, _) = RootDatabase::with_single_file("-module(main).");
+        let file_id = add_file_without_app(&mut db, "/loose/loose.erl", "-module(loose).\n");
+        let codes =
+            |errors: &[ParseError]| errors.iter().map(|e| e.code.clone()).collect::<Vec<_>>();
+        let l0003 = vec![ParseErrorCode::UnknownApplication.code().to_string()];
+
+        assert_eq!(
+            db.unknown_application_severity(),
+            UnknownApplicationSeverity::Error
+        );
+        let result = db.module_ast(file_id);
+        assert_eq!(codes(&result.errors), l0003);
+        assert!(result.ast.is_empty());
+
+        db.set_unknown_application_severity(UnknownApplicationSeverity::Warning);
+        let result = db.module_ast(file_id);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(codes(&result.warnings), l0003);
+        assert!(!result.ast.is_empty());
+
+        db.set_unknown_application_severity(UnknownApplicationSeverity::Ignore);
+        let result = db.module_ast(file_id);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+        assert!(!result.ast.is_empty());
+    }
+
+    #[test]
+    fn reparse_one_function() {
+        let (mut db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(main).
+-export([f/0, g/1]).
+-define(ONE, 1).
+f() -> g(?ONE).
+g(0) -> zero;
+g(N) -> h(N).
+h(N) -> N + 1.
+"#,
+        );
+        let id = |name: &str, arity| Id {
+            name: name.into(),
+            arity,
+        };
+        let full =
+            elp_eqwalizer::ast::from_bytes(&module_ast_etf(&db, file_id).unwrap(), false).unwrap();
+        let full_decl = |name: &str, arity| {
+            full.forms
+                .iter()
+                .find_map(|form| match form {
+                    ExternalForm::FunDecl(decl) if decl.id == id(name, arity) => Some(decl.clone()),
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        // Positions are the ones of the whole module
+        let f = db.reparse_function(file_id, id("f", 0)).unwrap().unwrap();
+        assert_eq!(*f, full_decl("f", 0));
+        let g = db.reparse_function(file_id, id("g", 1)).unwrap().unwrap();
+        assert_eq!(*g, full_decl("g", 1));
+        assert_eq!(g.clauses.len(), 2);
+        assert_eq!(db.reparse_function(file_id, id("f", 1)), Ok(None));
+
+        // Editing a later function reuses the earlier ones
+        let text = db.file_text(file_id).text(&db);
+        db.set_file_text(file_id, Arc::from(text.replace("N + 1", "N * 2 + 1")));
+        let f2 = db.reparse_function(file_id, id("f", 0)).unwrap().unwrap();
+        assert!(Arc::ptr_eq(&f, &f2));
+        let h = db.reparse_function(file_id, id("h", 1)).unwrap().unwrap();
+        let full =
+            elp_eqwalizer::ast::from_bytes(&module_ast_etf(&db, file_id).unwrap(), false).unwrap();
+        assert!(full.forms.contains(&ExternalForm::FunDecl((*h).clone())));
+    }
+
+    #[test]
+    fn module_ast_with_lines() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(main).
+-export([f/0]).
+f() -> ok.
+"#,
+        );
+
+        let (ast, line_index) = db.module_ast_with_lines(file_id);
+        assert_eq!(ast, db.module_ast(file_id));
+        assert_eq!(line_index, db.file_line_index(file_id));
+    }
+
+    #[test]
+    fn module_ast_etf_decodes() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(main).
+-export([f/0]).
+f() -> ok.
+"#,
+        );
+
+        let bytes = module_ast_etf(&db, file_id).unwrap();
+        let term = eetf::Term::decode(std::io::Cursor::new(&*bytes)).unwrap();
+        assert!(matches!(term, eetf::Term::Tuple(_)), "{term}");
+
+        let (db, file_id) = RootDatabase::with_single_file("-module(main).\nf( -> ok.\n");
+        assert_eq!(module_ast_etf(&db, file_id), Err(Error::ParseError));
+    }
+
+    #[test]
+    fn normalize_bom_and_latin1() {
+        let (text, unsupported) = normalize_encoding(Arc::from("\u{feff}-module(main).\n"));
+        assert_eq!(&*text, "   -module(main).\n");
+        assert!(unsupported.is_none());
+
+        let source = "%% -*- coding: latin-1 -*-\n-module(main).\n";
+        let (text, unsupported) = normalize_encoding(Arc::from(source));
+        assert_eq!(&*text, "%% -*- coding: utf-8   -*-\n-module(main).\n");
+        assert_eq!(text.len(), source.len());
+        assert!(unsupported.is_none());
+
+        let (text, unsupported) = normalize_encoding(Arc::from("%% coding: ebcdic\n"));
+        assert_eq!(&*text, "%% coding: ebcdic\n");
+        assert_eq!(
+            unsupported,
+            Some((TextRange::new(11.into(), 17.into()), "ebcdic".to_string()))
+        );
+    }
+
+    #[test]
+    fn module_with_bom() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            "\u{feff}-module(main).\n-export([f/0]).\nf() -> \"héllo\".\n",
+        );
+
+        let result = db.module_ast(file_id);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+    }
+
+    #[test]
+    fn module_in_latin1() {
+        // Latin-1 files are decoded when loaded, so the text is Unicode
+        // despite the declaration.
+        let (db, file_id) = RootDatabase::with_single_file(
+            "%% -*- coding: latin-1 -*-\n-module(main).\n-export([f/0]).\nf() -> \"café\".\n",
+        );
+
+        let result = db.module_ast(file_id);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+    }
+
+    #[test]
+    fn module_in_unsupported_encoding() {
+        let (db, file_id) = RootDatabase::with_single_file("%% coding: ebcdic\n-module(main).\n");
+
+        let result = db.module_ast(file_id);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let warning = result
+            .warnings
+            .iter()
+            .find(|w| w.code == ParseErrorCode::UnsupportedEncoding.code())
+            .unwrap();
+        assert_eq!(
+            warning.location,
+            Some(DiagnosticLocation::Normal(TextRange::new(
+                11.into(),
+                17.into()
+            )))
+        );
+    }
+
+    #[test]
+    fn conflicting_elp_erlang_service_macro() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            "-module(main).\n-if(?ELP_ERLANG_SERVICE).\nfoo() -> ok.\n-endif.\n",
+        );
+        let user_macro: eetf::Term = eetf::Tuple::from(vec![
+            eetf::Atom::from(ELP_ERLANG_SERVICE).into(),
+            eetf::Atom::from("false").into(),
+        ])
+        .into();
+        let path = AbsPathBuf::assert("/src/main.erl".into());
+
+        let result = db.load_ast(
+            None,
+            file_id,
+            &path,
+            &[user_macro],
+            &[],
+            &[],
+            db.elp_metadata(file_id).into(),
+            None,
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let warning = result
+            .warnings
+            .iter()
+            .find(|w| w.code == ParseErrorCode::ElpMacroOverridden.code())
+            .unwrap();
+        assert!(
+            warning.msg.contains("ELP_ERLANG_SERVICE"),
+            "{}",
+            warning.msg
+        );
+
+        // A definition matching the injected one is not reported
+        let (macros, overridden) =
+            erlang_service_macros(&[eetf::Atom::from(ELP_ERLANG_SERVICE).into()]);
+        assert_eq!(macros, vec![eetf::Atom::from(ELP_ERLANG_SERVICE).into()]);
+        assert_eq!(overridden, None);
+    }
+
+    #[test]
+    fn configured_macros() {
+        let (mut db, file_id) = RootDatabase::with_single_file(
+            r#"
+-module(main).
+-export([f/0]).
+-ifndef(DEBUG).
+-error("DEBUG is undefined").
+-endif.
+-if(?COUNT =/= 42).
+-error("COUNT is not 42").
+-endif.
+f() -> ?COUNT.
+"#,
+        );
+        assert!(!db.module_ast(file_id).errors.is_empty());
+
+        let compile_config = CompileConfig {
+            macros: BTreeMap::from([
+                ("COUNT".to_string(), MacroValue::Term("42".to_string())),
+                ("DEBUG".to_string(), MacroValue::Defined(true)),
+            ]),
+            ..CompileConfig::default()
+        };
+        let project_id = db.file_app_data(file_id).unwrap().project_id;
+        let project_data = db.project_data(project_id).project_data(&db);
+        db.set_project_data(
+            project_id,
+            Arc::new(ProjectData {
+                macros: compile_config.macros().unwrap(),
+                ..(*project_data).clone()
+            }),
+        );
+        let result = db.module_ast(file_id);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn parse_fragments() {
+        let erlang_service = Connection::start().unwrap();
+
+        let body = parse_fragment(&erlang_service, "1 + 2").unwrap();
+        assert!(matches!(&body.exprs[..], [Expr::BinOp(_)]));
+
+        let body = parse_fragment(
+            &erlang_service,
+            "case 1 + 2 of\n    3 -> three;\n    _ -> other\nend",
+        )
+        .unwrap();
+        assert!(matches!(&body.exprs[..], [Expr::Case(_)]));
+
+        assert!(matches!(
+            parse_fragment(&erlang_service, "1 +"),
+            Err(Error::InvalidFragment(_))
+        ));
+    }
+
+    #[test]
+    fn panicking_include_resolver() {
+        let erlang_service = Connection::start().unwrap();
+        let req = ParseRequest {
+            options: vec![],
+            file_id: FileId::from_raw(0),
+            path: PathBuf::from("/src/main.erl"),
+            format: Format::OffsetEtf,
+            file_text: Arc::from("-module(main).\n-include(\"bad.hrl\").\nfoo() -> ok.\n"),
+        };
+        let panics = RefCell::new(Vec::new());
+
+        let result = erlang_service.request_parse(
+            req,
+            || (),
+            &catch_resolver_panics(
+                |_, _, _| -> Option<(String, FileId, Arc<str>)> { panic!("malformed path") },
+                &panics,
+            ),
+        );
+
+        assert!(!result.ast.is_empty());
+        assert_eq!(
+            panics.into_inner(),
+            vec!["Resolving include \"bad.hrl\" panicked: malformed path".to_string()]
+        );
+    }
+}
@@ -13,6 +13,9 @@ use std::panic::AssertUnwindSafe;
 use std::panic::RefUnwindSafe;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
 use elp_base_db::AbsPathBuf;
 use elp_base_db::AppData;
@@ -34,12 +37,15 @@ use elp_base_db::SourceRoot;
 use elp_base_db::SourceRootId;
 use elp_base_db::SourceRootInput;
 use elp_base_db::Upcast;
-use elp_base_db::limit_logged_string;
+use elp_base_db::VfsPath;
 use elp_base_db::salsa;
 use elp_eqwalizer::EqwalizerConfig;
 use elp_eqwalizer::Mode;
+use elp_eqwalizer::db::DeadlineWaiters;
+use elp_eqwalizer::db::ELPDbApi;
 use elp_eqwalizer::db::EqwalizerDiagnosticsDatabase;
 use elp_eqwalizer::ipc::IpcHandle;
+use elp_eqwalizer::progress::EqwalizerProgress;
 use elp_syntax::AstNode;
 use elp_syntax::SyntaxKind;
 use elp_syntax::SyntaxToken;
@@ -47,6 +53,7 @@ use elp_types_db::TypedSemantic;
 use elp_types_db::eqwalizer::types::Type;
 use erlang_service::Connection;
 use fxhash::FxHashMap;
+use fxhash::FxHashSet;
 use helpers::pick_best_token;
 use hir::InFile;
 use hir::Semantic;
@@ -91,6 +98,10 @@ pub use elp_eqwalizer::EqwalizerDiagnostics;
 pub use elp_erlang_service as erlang_service;
 pub use eqwalizer::EqwalizerDatabase;
 pub use erl_ast::ErlAstDatabase;
+pub use erl_ast::FRAGMENT_OFFSET;
+pub use erl_ast::UnknownApplicationSeverity;
+pub use erl_ast::module_ast_etf;
+pub use erl_ast::parse_fragment;
 pub use line_index::LineCol;
 pub use line_index::LineIndex;
 pub use search::FindUsages;
@@ -117,9 +128,19 @@ pub struct RootDatabase {
     storage: salsa::Storage<Self>,
     files: Arc<Files>,
     erlang_services: Arc<AssertUnwindSafe<RwLock<FxHashMap<ProjectId, Connection>>>>,
+    /// Used for files outside of any project, started on first use.
+    erlang_service_without_project: Arc<AssertUnwindSafe<RwLock<Option<Connection>>>>,
     eqwalizer: Eqwalizer,
     eqwalizer_progress_reporter: EqwalizerProgressReporterBox,
+    eqwalizing_progress: Arc<Mutex<Option<Arc<EqwalizerProgress>>>>,
+    /// Modules for which `eqwalizing_start` was reported, but not yet
+    /// `eqwalizing_done`, used to avoid announcing them twice.
+    eqwalizing_in_flight: Arc<Mutex<FxHashSet<String>>>,
+    /// Wakes the thread saving `eqwalizing_in_flight`, see
+    /// `persist_eqwalizing_in_flight`.
+    eqwalizing_in_flight_saver: Arc<Mutex<Option<mpsc::Sender<()>>>>,
     ipc_handles: Arc<AssertUnwindSafe<RwLock<FxHashMap<String, Arc<Mutex<IpcHandle>>>>>>,
+    deadline_waiters: Arc<DeadlineWaiters>,
 }
 impl Default for RootDatabase {
     fn default() -> Self {
@@ -127,11 +148,19 @@ impl Default for RootDatabase {
             storage: salsa::Storage::default(),
             files: Arc::default(),
             erlang_services: Arc::default(),
+            erlang_service_without_project: Arc::default(),
             eqwalizer: Eqwalizer::default(),
             eqwalizer_progress_reporter: EqwalizerProgressReporterBox::default(),
+            eqwalizing_progress: Arc::default(),
+            eqwalizing_in_flight: Arc::default(),
+            eqwalizing_in_flight_saver: Arc::default(),
             ipc_handles: Arc::default(),
+            deadline_waiters: Arc::default(),
         };
         db.set_eqwalizer_config(Arc::new(EqwalizerConfig::default()));
+        db.set_ebin_beam_files(Arc::default());
+        db.set_parse_files_without_app(false);
+        db.set_unknown_application_severity(UnknownApplicationSeverity::default());
         db
     }
 }
@@ -144,9 +173,14 @@ impl Clone for RootDatabase {
             storage: self.storage.clone(),
             files: self.files.clone(),
             erlang_services: self.erlang_services.clone(),
+            erlang_service_without_project: self.erlang_service_without_project.clone(),
             eqwalizer: self.eqwalizer.clone(),
             eqwalizer_progress_reporter: self.eqwalizer_progress_reporter.clone(),
+            eqwalizing_progress: self.eqwalizing_progress.clone(),
+            eqwalizing_in_flight: self.eqwalizing_in_flight.clone(),
+            eqwalizing_in_flight_saver: self.eqwalizing_in_flight_saver.clone(),
             ipc_handles: self.ipc_handles.clone(),
+            deadline_waiters: self.deadline_waiters.clone(),
         }
     }
 }
@@ -200,13 +234,22 @@ impl SourceDatabase for RootDatabase {
         files.set_source_root(self, source_root_id, source_root);
     }
 
+    fn has_source_root(&self, source_root_id: SourceRootId) -> bool {
+        self.files.has_source_root(source_root_id)
+    }
+
     fn file_source_root(&self, id: FileId) -> FileSourceRootInput {
         self.files.file_source_root(id)
     }
 
-    fn set_file_source_root(&mut self, id: FileId, source_root_id: SourceRootId) {
+    fn set_file_source_root(
+        &mut self,
+        id: FileId,
+        source_root_id: SourceRootId,
+        path: Option<VfsPath>,
+    ) {
         let files = self.files.clone();
-        files.set_file_source_root(self, id, source_root_id);
+        files.set_file_source_root(self, id, source_root_id, path);
     }
 
     fn app_data_by_id(&self, id: AppDataId) -> AppDataInput {
@@ -243,9 +286,14 @@ impl RootDatabase {
             storage: self.storage.clone(),
             files: self.files.clone(),
             erlang_services: self.erlang_services.clone(),
+            erlang_service_without_project: self.erlang_service_without_project.clone(),
             eqwalizer: self.eqwalizer.clone(),
             eqwalizer_progress_reporter: self.eqwalizer_progress_reporter.clone(),
+            eqwalizing_progress: self.eqwalizing_progress.clone(),
+            eqwalizing_in_flight: self.eqwalizing_in_flight.clone(),
+            eqwalizing_in_flight_saver: self.eqwalizing_in_flight_saver.clone(),
             ipc_handles: self.ipc_handles.clone(),
+            deadline_waiters: self.deadline_waiters.clone(),
         }
     }
 
@@ -256,6 +304,7 @@ impl RootDatabase {
 
     pub fn clear_erlang_services(&mut self) {
         self.erlang_services.write().clear();
+        *self.erlang_service_without_project.write() = None;
     }
 
     pub fn erlang_service_for(&self, project_id: ProjectId) -> Connection {
@@ -283,6 +332,17 @@ impl RootDatabase {
             .clone()
     }
 
+    pub fn erlang_service_without_project(&self) -> Connection {
+        let read = self.erlang_service_without_project.upgradable_read();
+        if let Some(conn) = read.as_ref() {
+            return conn.clone();
+        }
+        let mut write = RwLockUpgradableReadGuard::upgrade(read);
+        write
+            .get_or_insert_with(|| Connection::start().expect("failed to establish connection"))
+            .clone()
+    }
+
     pub fn update_erlang_service_paths(&self) {
         for (&project_id, connection) in self.erlang_services.read().iter() {
             let project_data = self.project_data(project_id).project_data(self);
@@ -302,6 +362,50 @@ impl RootDatabase {
         *self.eqwalizer_progress_reporter.lock() = report
     }
 
+    /// Save the modules being eqWAlized to `path` as they start and
+    /// finish, so that a restart can tell which ones a crash left
+    /// unfinished. Those listed in `path` already are reported done
+    /// first, so set the progress reporter before calling this. `None`
+    /// stops saving. This is best-effort: I/O errors are only logged.
+    pub fn persist_eqwalizing_in_flight(&self, path: Option<PathBuf>) {
+        let saver = path.map(|path| {
+            let dangling = match std::fs::read_to_string(&path) {
+                Ok(text) => text.lines().map(|line| line.to_string()).collect(),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+                Err(err) => {
+                    log::warn!(
+                        "Could not read eqWAlizer modules from {}: {err}",
+                        path.display()
+                    );
+                    vec![]
+                }
+            };
+            for module in dangling {
+                self.eqwalizing_in_flight.lock().insert(module.clone());
+                self.eqwalizing_done(module);
+            }
+            let (sender, receiver) = mpsc::channel();
+            let in_flight = self.eqwalizing_in_flight.clone();
+            thread::Builder::new()
+                .name("EqwalizingInFlight".to_string())
+                .spawn(move || save_eqwalizing_in_flight(path, in_flight, receiver))
+                .expect("failed to spawn thread");
+            sender
+        });
+        // Dropping the previous sender stops its thread.
+        *self.eqwalizing_in_flight_saver.lock() = saver;
+        self.save_eqwalizing_in_flight();
+    }
+
+    /// Ask for `eqwalizing_in_flight` to be saved, if
+    /// `persist_eqwalizing_in_flight` set a file.
+    fn save_eqwalizing_in_flight(&self) {
+        if let Some(saver) = self.eqwalizing_in_flight_saver.lock().as_ref() {
+            // The thread only stops once the sender is dropped
+            let _ = saver.send(());
+        }
+    }
+
     pub fn eqwalizer(&self) -> &Eqwalizer {
         &self.eqwalizer
     }
@@ -347,6 +451,35 @@ impl RootDatabase {
     }
 }
 
+/// How long to wait for more changes before saving
+/// `eqwalizing_in_flight`, as modules often start and finish in bursts.
+const SAVE_EQWALIZING_IN_FLIGHT_DELAY: Duration = Duration::from_millis(100);
+
+/// Write `in_flight` to `path` each time `receiver` is woken, until its
+/// sender is dropped.
+fn save_eqwalizing_in_flight(
+    path: PathBuf,
+    in_flight: Arc<Mutex<FxHashSet<String>>>,
+    receiver: mpsc::Receiver<()>,
+) {
+    while receiver.recv().is_ok() {
+        thread::sleep(SAVE_EQWALIZING_IN_FLIGHT_DELAY);
+        while receiver.try_recv().is_ok() {}
+        let text: String = {
+            let in_flight = in_flight.lock();
+            let mut modules: Vec<&String> = in_flight.iter().collect();
+            modules.sort();
+            modules.iter().map(|module| format!("{module}\n")).collect()
+        };
+        if let Err(err) = std::fs::write(&path, text) {
+            log::warn!(
+                "Could not save eqWAlizer modules to {}: {err}",
+                path.display()
+            );
+        }
+    }
+}
+
 #[ra_ap_query_group_macro::query_group(LineIndexDatabaseStorage)]
 pub trait LineIndexDatabase: RootQueryDb {
     fn file_line_index(&self, file_id: FileId) -> Arc<LineIndex>;
@@ -457,23 +590,7 @@ impl TypedSemantic for RootDatabase {
         }
 
         let diags = eqwalizer::eqwalizer_diagnostics_by_project(self, project_id, vec![file_id]);
-        match &*diags {
-            EqwalizerDiagnostics::Diagnostics { errors, .. } => Some(
-                errors
-                    .iter()
-                    .flat_map(|(_, diags)| diags.iter().cloned())
-                    .collect(),
-            ),
-            EqwalizerDiagnostics::NoAst { .. } => Some(vec![]),
-            EqwalizerDiagnostics::Error(err) => {
-                log::error!(
-                    "EqWAlizer failed for {}: {}",
-                    module.as_str(),
-                    limit_logged_string(err)
-                );
-                Some(vec![])
-            }
-        }
+        Some(eqwalizer::eqwalizer_errors(&module, &diags))
     }
 
     fn eqwalizer_type_at_position(&self, range: FileRange) -> Option<Arc<(Type, FileRange)>> {
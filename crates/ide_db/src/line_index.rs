@@ -25,6 +25,8 @@ pub struct LineIndex {
     pub(crate) newlines: Vec<TextSize>,
     /// List of offsets of utf16 surrogates on each line
     pub(crate) utf16_lines: FxHashMap<u32, Vec<Utf16Char>>,
+    /// Length of the text, the end offset of its last line
+    pub(crate) len: TextSize,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -104,9 +106,16 @@ impl LineIndex {
         LineIndex {
             newlines,
             utf16_lines,
+            len: TextSize::of(text),
         }
     }
 
+    /// `range` limited to the end of the text, for ranges computed
+    /// against another version of it.
+    pub fn clamp(&self, range: TextRange) -> TextRange {
+        TextRange::new(range.start().min(self.len), range.end().min(self.len))
+    }
+
     pub fn line_col(&self, offset: TextSize) -> LineCol {
         let line = partition_point(&self.newlines, |&it| it <= offset) - 1;
         let line_start_offset = self.newlines[line];
@@ -32,6 +32,25 @@ impl Metadata {
             .iter()
             .filter(move |ann| ann.source == source)
     }
+
+    pub fn by_kind(&self, source: Source, kind: Kind) -> impl Iterator<Item = &Annotation> + '_ {
+        self.annotations
+            .iter()
+            .filter(move |ann| ann.source == source && ann.kind == kind)
+    }
+
+    /// The `% elp:ignore` annotations, in the order they appear in the file.
+    pub fn elp_ignores(&self) -> Vec<Annotation> {
+        let mut ignores: Vec<_> = self.by_kind(Source::Elp, Kind::Ignore).cloned().collect();
+        ignores.sort_by_key(|ann| ann.comment_range.start());
+        ignores
+    }
+
+    /// Whether the file is marked as emitted by a code generator, with a
+    /// `% elp:generated` comment.
+    pub fn is_generated(&self) -> bool {
+        self.by_kind(Source::Elp, Kind::Generated).next().is_some()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -65,6 +84,8 @@ impl fmt::Display for Source {
 pub enum Kind {
     Ignore,
     Fixme,
+    /// The whole file is generated, see `Metadata::is_generated`.
+    Generated,
 }
 
 impl fmt::Display for Kind {
@@ -72,6 +93,7 @@ impl fmt::Display for Kind {
         match self {
             Kind::Ignore => write!(f, "ignore"),
             Kind::Fixme => write!(f, "fixme"),
+            Kind::Generated => write!(f, "generated"),
         }
     }
 }
@@ -86,6 +108,20 @@ pub struct Annotation {
     pub codes: FxHashSet<DiagnosticCode>,
 }
 
+impl Annotation {
+    /// Whether the annotation names no diagnostic code, as in a bare
+    /// `% elp:ignore`, in which case it suppresses nothing.
+    pub fn names_no_code(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Whether the annotation suppresses `code` when reported at `offset`,
+    /// i.e. on the line after the annotation.
+    pub fn suppresses(&self, code: &DiagnosticCode, offset: TextSize) -> bool {
+        self.codes.contains(code) && self.suppression_range.contains(offset)
+    }
+}
+
 impl From<Metadata> for eetf::Term {
     fn from(val: Metadata) -> Self {
         let eqwalizer_annotations: Vec<eetf::Term> =
@@ -150,6 +186,10 @@ pub fn collect_metadata(
             source: Source::Elp,
             kind: Kind::Fixme,
         },
+        Pattern {
+            source: Source::Elp,
+            kind: Kind::Generated,
+        },
     ];
     let mut annotations = Vec::new();
     for pattern in patterns {
@@ -168,10 +208,14 @@ pub fn collect_metadata(
                     let suppression_range = get_suppression_range(line_index, line_num, file_text);
                     let comment = token.to_string();
                     let comment_range = TextRange::new(pattern_start, pattern_end);
-                    let codes = comment
-                        .split_whitespace()
-                        .filter_map(DiagnosticCode::maybe_from_string)
-                        .collect();
+                    // A generated file suppresses no diagnostic in particular
+                    let codes = match pattern.kind {
+                        Kind::Generated => FxHashSet::default(),
+                        Kind::Ignore | Kind::Fixme => comment
+                            .split_whitespace()
+                            .filter_map(DiagnosticCode::maybe_from_string)
+                            .collect(),
+                    };
 
                     annotations.push(Annotation {
                         comment,
@@ -200,3 +244,48 @@ fn get_suppression_range(line_index: &LineIndex, line_num: u32, text: &str) -> T
     let end = line_start(line_index, line_num + 2, text);
     TextRange::new(start, end)
 }
+
+#[cfg(test)]
+mod tests {
+    use elp_base_db::fixture::WithFixture;
+
+    use super::*;
+    use crate::RootDatabase;
+    use crate::erl_ast::ErlAstDatabase;
+
+    #[test]
+    fn elp_ignore_annotations() {
+        let text = r#"-module(main).
+% elp:ignore W0007
+foo() -> ok.
+% elp:ignore
+bar() -> ok.
+% elp:fixme W0007
+baz() -> ok.
+"#;
+        let (db, file_id) = RootDatabase::with_single_file(text);
+
+        let ignores = db.elp_ignores(file_id);
+        let summary: Vec<_> = ignores
+            .iter()
+            .map(|ann| {
+                let line = &text[ann.suppression_range];
+                (ann.comment.as_str(), ann.names_no_code(), line)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("% elp:ignore W0007", false, "foo() -> ok.\n"),
+                ("% elp:ignore", true, "bar() -> ok.\n"),
+            ]
+        );
+
+        let code = DiagnosticCode::maybe_from_string("W0007").unwrap();
+        let foo = TextSize::from(text.find("foo").unwrap() as u32);
+        let bar = TextSize::from(text.find("bar").unwrap() as u32);
+        assert!(ignores[0].suppresses(&code, foo));
+        assert!(!ignores[0].suppresses(&code, bar));
+        assert!(!ignores[1].suppresses(&code, bar));
+    }
+}
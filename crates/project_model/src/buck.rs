@@ -169,6 +169,10 @@ impl IncludeMapping {
     pub fn insert(&mut self, path: SmolStr, abs_path: AbsPathBuf) -> Option<AbsPathBuf> {
         self.includes.insert(path, abs_path)
     }
+
+    pub fn files(&self) -> impl Iterator<Item = &AbsPathBuf> {
+        self.includes.values()
+    }
 }
 
 impl BuckProject {
@@ -0,0 +1,197 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Parsing of Erlang terms written in `.elp.toml`, e.g. macro values.
+//! Only literals are supported: integers, atoms, strings, binary
+//! strings, and tuples and lists of those.
+
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+
+pub fn parse(text: &str) -> Result<eetf::Term> {
+    let mut parser = Parser { text, pos: 0 };
+    let term = parser.term()?;
+    parser.skip_whitespace();
+    if !parser.rest().is_empty() {
+        bail!("unexpected `{}` after term in `{text}`", parser.rest());
+    }
+    Ok(term)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.pos = self.text.len() - self.rest().trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn term(&mut self) -> Result<eetf::Term> {
+        self.skip_whitespace();
+        if self.eat("{") {
+            Ok(eetf::Tuple::from(self.elements("}")?).into())
+        } else if self.eat("[") {
+            Ok(eetf::List::from(self.elements("]")?).into())
+        } else if self.eat("<<") {
+            let bytes = self.quoted('"')?.into_bytes();
+            if !self.eat(">>") {
+                bail!("expected `>>` in `{}`", self.text);
+            }
+            Ok(eetf::Binary { bytes }.into())
+        } else if self.rest().starts_with('"') {
+            let chars = self
+                .quoted('"')?
+                .chars()
+                .map(|c| eetf::FixInteger::from(c as i32).into())
+                .collect::<Vec<_>>();
+            Ok(eetf::List::from(chars).into())
+        } else if self.rest().starts_with('\'') {
+            Ok(eetf::Atom::from(self.quoted('\'')?).into())
+        } else if self
+            .rest()
+            .starts_with(|c: char| c == '-' || c.is_ascii_digit())
+        {
+            let start = self.pos;
+            self.eat("-");
+            self.take_while(|c| c.is_ascii_digit());
+            let digits = &self.text[start..self.pos];
+            let value: i32 = digits
+                .parse()
+                .map_err(|_| anyhow!("invalid integer `{digits}` in `{}`", self.text))?;
+            Ok(eetf::FixInteger::from(value).into())
+        } else if self.rest().starts_with(|c: char| c.is_ascii_lowercase()) {
+            let name = self.take_while(|c| c.is_alphanumeric() || c == '_' || c == '@');
+            Ok(eetf::Atom::from(name).into())
+        } else {
+            bail!(
+                "expected an Erlang term at `{}` in `{}`",
+                self.rest(),
+                self.text
+            )
+        }
+    }
+
+    fn elements(&mut self, close: &str) -> Result<Vec<eetf::Term>> {
+        let mut elements = Vec::new();
+        if self.eat(close) {
+            return Ok(elements);
+        }
+        loop {
+            elements.push(self.term()?);
+            if self.eat(close) {
+                return Ok(elements);
+            }
+            if !self.eat(",") {
+                bail!("expected `,` or `{close}` in `{}`", self.text);
+            }
+        }
+    }
+
+    /// A `quote`-delimited string at the current position, with `\`
+    /// escaping the next character.
+    fn quoted(&mut self, quote: char) -> Result<String> {
+        self.skip_whitespace();
+        let mut chars = self.rest().char_indices();
+        if chars.next().map(|(_, c)| c) != Some(quote) {
+            bail!("expected `{quote}` in `{}`", self.text);
+        }
+        let mut result = String::new();
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => result.push('\n'),
+                    Some((_, 't')) => result.push('\t'),
+                    Some((_, c)) => result.push(c),
+                    None => break,
+                },
+                c if c == quote => {
+                    self.pos += idx + 1;
+                    return Ok(result);
+                }
+                c => result.push(c),
+            }
+        }
+        bail!("unterminated `{quote}` in `{}`", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(name: &str) -> eetf::Term {
+        eetf::Atom::from(name).into()
+    }
+
+    fn int(value: i32) -> eetf::Term {
+        eetf::FixInteger::from(value).into()
+    }
+
+    #[test]
+    fn parse_literals() {
+        assert_eq!(parse("42").unwrap(), int(42));
+        assert_eq!(parse(" -7 ").unwrap(), int(-7));
+        assert_eq!(parse("debug").unwrap(), atom("debug"));
+        assert_eq!(parse("'Quoted atom'").unwrap(), atom("Quoted atom"));
+        assert_eq!(
+            parse(r#""ab""#).unwrap(),
+            eetf::List::from(vec![int(97), int(98)]).into()
+        );
+        assert_eq!(
+            parse(r#"<<"ab">>"#).unwrap(),
+            eetf::Binary {
+                bytes: b"ab".to_vec()
+            }
+            .into()
+        );
+        assert_eq!(
+            parse("{level, [1, 2], {}}").unwrap(),
+            eetf::Tuple::from(vec![
+                atom("level"),
+                eetf::List::from(vec![int(1), int(2)]).into(),
+                eetf::Tuple::from(vec![]).into(),
+            ])
+            .into()
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("Var").is_err());
+        assert!(parse("{a, b").is_err());
+        assert!(parse("'open").is_err());
+        assert!(parse("1 2").is_err());
+        assert!(parse("99999999999").is_err());
+    }
+}
@@ -10,6 +10,7 @@
 
 use std::borrow::Borrow;
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
@@ -59,6 +60,7 @@ use crate::rebar::RebarProject;
 
 pub mod buck;
 pub mod eqwalizer_support;
+pub mod erl_term;
 pub mod json;
 pub mod no_manifest;
 pub mod otp;
@@ -384,6 +386,8 @@ pub struct ElpConfig {
     pub eqwalizer: EqwalizerConfig,
     #[serde(default)]
     pub rebar: ElpRebarConfig,
+    #[serde(default, skip_serializing_if = "CompileConfig::is_empty")]
+    pub compile: CompileConfig,
 }
 
 #[derive(
@@ -402,6 +406,10 @@ pub struct EqwalizerConfig {
     pub enable_all: bool,
     #[serde(default = "eqwalizer_max_tasks_default")]
     pub max_tasks: usize,
+    /// The most eqWAlizer checks run at the same time, the number of
+    /// available cores if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_check_parallelism: Option<usize>,
 }
 
 fn eqwalizer_enable_all_default() -> bool {
@@ -417,10 +425,19 @@ impl Default for EqwalizerConfig {
         Self {
             enable_all: eqwalizer_enable_all_default(),
             max_tasks: eqwalizer_max_tasks_default(),
+            max_check_parallelism: None,
         }
     }
 }
 
+impl EqwalizerConfig {
+    /// The number of eqWAlizer checks to run at the same time.
+    pub fn check_parallelism(&self) -> usize {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        self.max_check_parallelism.unwrap_or(cores).max(1)
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct BuildInfoConfig {
     pub file: Option<PathBuf>,
@@ -479,6 +496,164 @@ impl Default for ElpRebarConfig {
     }
 }
 
+/// Compile options applied to every app in the project, e.g.
+/// ```toml
+/// [compile]
+/// macros = { FEATURE_X = "42", DEBUG = true }
+/// features = ["maybe_expr"]
+/// parse_transforms = ["lint_support"]
+/// otp_root = "/usr/local/lib/erlang"
+/// ```
+/// App-level definitions (from the build system) take precedence.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct CompileConfig {
+    #[serde(default)]
+    pub macros: BTreeMap<String, MacroValue>,
+    /// Language features (`-feature(Name, enable)`) to enable. When
+    /// unset, the erlang service defaults apply and every feature it
+    /// knows about is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<Vec<String>>,
+    /// Parse transforms applied to every app, before the app-level
+    /// ones, see `merge_parse_transforms`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parse_transforms: Vec<String>,
+    /// Root of the OTP installation, as returned by `code:root_dir()`,
+    /// whose applications are used, e.g. for `-include_lib`. When unset,
+    /// the one of the `erl` found on the path is used. A relative path
+    /// is relative to the directory of the `.elp.toml` file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otp_root: Option<String>,
+}
+
+impl CompileConfig {
+    pub fn is_empty(&self) -> bool {
+        self.macros.is_empty()
+            && self.features.is_none()
+            && self.parse_transforms.is_empty()
+            && self.otp_root.is_none()
+    }
+
+    /// The directory holding the applications of the configured OTP
+    /// installation, if any, like the one `Otp::find_otp` returns.
+    pub fn otp_lib_dir(&self) -> Result<Option<Utf8PathBuf>> {
+        let Some(root) = &self.otp_root else {
+            return Ok(None);
+        };
+        let root = Utf8Path::new(root);
+        if !root.is_absolute() {
+            bail!("otp_root must be an absolute path, got '{root}'");
+        }
+        Ok(Some(root.join("lib")))
+    }
+
+    fn find_otp(&self) -> Result<Utf8PathBuf> {
+        match self.otp_lib_dir()? {
+            Some(lib_dir) => Ok(lib_dir),
+            None => Otp::find_otp(),
+        }
+    }
+
+    /// Make a relative `otp_root` relative to the directory of the
+    /// `.elp.toml` file at `config_path` instead.
+    fn resolve_otp_root(&mut self, config_path: &AbsPath) {
+        if let (Some(root), Some(config_dir)) = (&mut self.otp_root, config_path.parent()) {
+            *root = config_dir.join(root.as_str()).to_string();
+        }
+    }
+
+    /// The project-level parse transforms, in the form expected by the
+    /// erlang service `parse_transforms` compile option.
+    pub fn parse_transforms(&self) -> Vec<eetf::Term> {
+        self.parse_transforms
+            .iter()
+            .map(|name| eetf::Atom::from(name.clone()).into())
+            .collect()
+    }
+
+    /// The project-level macros, in the form expected by the erlang
+    /// service `macros` compile option. Fails if a value is not a valid
+    /// Erlang term.
+    pub fn macros(&self) -> Result<Vec<eetf::Term>> {
+        self.macros
+            .iter()
+            .filter_map(|(key, value)| {
+                let name: eetf::Term = eetf::Atom::from(key.clone()).into();
+                match value {
+                    MacroValue::Defined(true) => Some(Ok(name)),
+                    MacroValue::Defined(false) => None,
+                    MacroValue::Term(term) => Some(
+                        erl_term::parse(term)
+                            .map(|value| eetf::Tuple::from(vec![name, value]).into())
+                            .with_context(|| format!("invalid value for macro {key}")),
+                    ),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The value of a macro in the `[compile]` section of `.elp.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum MacroValue {
+    /// `true` defines the macro without a value, like `-DNAME`; `false`
+    /// leaves it undefined.
+    Defined(bool),
+    /// An Erlang term, e.g. `"42"`, `"debug"` or `"{1, \"text\"}"`.
+    Term(String),
+}
+
+/// The name of a macro definition, as given in a `macros` compile
+/// option. It is either a bare atom `'NAME'` or a tuple `{'NAME', Value}`.
+pub fn macro_name(mac: &eetf::Term) -> Option<&str> {
+    match mac {
+        eetf::Term::Atom(atom) => Some(atom.name.as_str()),
+        eetf::Term::Tuple(tuple) => match tuple.elements.first() {
+            Some(eetf::Term::Atom(atom)) => Some(atom.name.as_str()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Combine two sets of macro definitions. Where both define the same
+/// macro, the one from `overrides` wins, so pass the more specific
+/// definitions (e.g. app-level) as `overrides`.
+pub fn merge_macros(defaults: &[eetf::Term], overrides: &[eetf::Term]) -> Vec<eetf::Term> {
+    let mut result: Vec<eetf::Term> = Vec::with_capacity(defaults.len() + overrides.len());
+    for mac in defaults.iter().chain(overrides) {
+        match macro_name(mac) {
+            Some(name) => {
+                if let Some(pos) = result.iter().position(|m| macro_name(m) == Some(name)) {
+                    result[pos] = mac.clone();
+                } else {
+                    result.push(mac.clone());
+                }
+            }
+            None => {
+                if !result.contains(mac) {
+                    result.push(mac.clone());
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Combine the project-level parse transforms with the app-level ones,
+/// which are applied after them. A transform given by both is applied
+/// once, in its project-level position.
+pub fn merge_parse_transforms(global: &[eetf::Term], app: &[eetf::Term]) -> Vec<eetf::Term> {
+    let mut result: Vec<eetf::Term> = Vec::with_capacity(global.len() + app.len());
+    for transform in global.iter().chain(app) {
+        if !result.contains(transform) {
+            result.push(transform.clone());
+        }
+    }
+    result
+}
+
 impl ElpConfig {
     pub fn new(
         config_path: AbsPathBuf,
@@ -486,6 +661,7 @@ impl ElpConfig {
         build_info: Option<BuildInfoConfig>,
         eqwalizer: EqwalizerConfig,
         rebar: ElpRebarConfig,
+        compile: CompileConfig,
     ) -> Self {
         Self {
             config_path: Some(config_path),
@@ -493,6 +669,7 @@ impl ElpConfig {
             build_info,
             eqwalizer,
             rebar,
+            compile,
         }
     }
     pub fn try_parse(path: &AbsPath) -> Result<ElpConfig> {
@@ -506,6 +683,13 @@ impl ElpConfig {
         match toml::from_str(config_content.as_str()) {
             Ok(mut config) => {
                 BuckConfig::make_config(&path, &mut config)?;
+                config.compile.resolve_otp_root(&path);
+                config.compile.macros().with_context(|| {
+                    format!(
+                        "unable to read {}",
+                        path.as_path().as_os_str().to_string_lossy()
+                    )
+                })?;
                 config.config_path = Some(path);
 
                 Ok(config)
@@ -657,6 +841,7 @@ pub struct Project {
     pub project_build_data: ProjectBuildData,
     pub project_apps: Vec<ProjectAppData>,
     pub eqwalizer_config: EqwalizerConfig,
+    pub compile_config: CompileConfig,
     pub include_mapping: Arc<IncludeMapping>,
 }
 
@@ -692,6 +877,7 @@ impl Project {
             project_build_data: ProjectBuildData::Otp,
             project_apps,
             eqwalizer_config: EqwalizerConfig::default(),
+            compile_config: CompileConfig::default(),
             include_mapping: Arc::new(IncludeMapping::default()),
         }
     }
@@ -702,6 +888,7 @@ impl Project {
             project_build_data: ProjectBuildData::Rebar(Default::default()),
             project_apps: Vec::default(),
             eqwalizer_config: EqwalizerConfig::default(),
+            compile_config: CompileConfig::default(),
             include_mapping: Arc::new(IncludeMapping::default()),
         }
     }
@@ -970,6 +1157,7 @@ impl Project {
     pub fn load(
         manifest: &ProjectManifest,
         eqwalizer_config: EqwalizerConfig,
+        compile_config: CompileConfig,
         query_config: &BuckQueryConfig,
         report_progress: &impl Fn(&str),
     ) -> Result<Project> {
@@ -1017,7 +1205,7 @@ impl Project {
                 )
             }
             ProjectManifest::Json(config) => {
-                let otp_root = Otp::find_otp()?;
+                let otp_root = compile_config.find_otp()?;
                 let config_path = config.config_path().to_path_buf();
                 let (mut apps, deps) = json::gen_app_data(config, AbsPath::assert(&otp_root));
                 let project = StaticProject { config_path };
@@ -1030,7 +1218,7 @@ impl Project {
                 )
             }
             ProjectManifest::NoManifest(config) => {
-                let otp_root = Otp::find_otp()?;
+                let otp_root = compile_config.find_otp()?;
                 let abs_otp_root = AbsPath::assert(&otp_root);
                 let config_path = config.config_path().to_path_buf();
                 let mut apps = config.to_project_app_data(abs_otp_root);
@@ -1047,6 +1235,9 @@ impl Project {
             }
         };
 
+        // Rebar and buck report the OTP they build with, the configured
+        // one wins.
+        let otp_root = compile_config.otp_lib_dir()?.unwrap_or(otp_root);
         let (otp, otp_project_apps) = Otp::discover(otp_root);
         project_apps.extend(otp_project_apps);
         report_progress("Project info loaded");
@@ -1055,6 +1246,7 @@ impl Project {
             project_build_data: project_build_info,
             project_apps,
             eqwalizer_config,
+            compile_config,
             include_mapping,
         })
     }
@@ -1152,10 +1344,17 @@ mod tests {
                         eqwalizer: EqwalizerConfig {
                             enable_all: true,
                             max_tasks: 4,
+                            max_check_parallelism: None,
                         },
                         rebar: ElpRebarConfig {
                             profile: "test",
                         },
+                        compile: CompileConfig {
+                            macros: {},
+                            features: None,
+                            parse_transforms: [],
+                            otp_root: None,
+                        },
                     },
                     Rebar(
                         RebarConfig {
@@ -1215,10 +1414,17 @@ mod tests {
                         eqwalizer: EqwalizerConfig {
                             enable_all: true,
                             max_tasks: 4,
+                            max_check_parallelism: None,
                         },
                         rebar: ElpRebarConfig {
                             profile: "test",
                         },
+                        compile: CompileConfig {
+                            macros: {},
+                            features: None,
+                            parse_transforms: [],
+                            otp_root: None,
+                        },
                     },
                     Json(
                         JsonConfig {
@@ -1323,10 +1529,17 @@ mod tests {
                         eqwalizer: EqwalizerConfig {
                             enable_all: true,
                             max_tasks: 4,
+                            max_check_parallelism: None,
                         },
                         rebar: ElpRebarConfig {
                             profile: "test",
                         },
+                        compile: CompileConfig {
+                            macros: {},
+                            features: None,
+                            parse_transforms: [],
+                            otp_root: None,
+                        },
                     },
                     JsonConfig {
                         apps: [
@@ -1476,10 +1689,17 @@ mod tests {
                         eqwalizer: EqwalizerConfig {
                             enable_all: true,
                             max_tasks: 4,
+                            max_check_parallelism: None,
                         },
                         rebar: ElpRebarConfig {
                             profile: "test",
                         },
+                        compile: CompileConfig {
+                            macros: {},
+                            features: None,
+                            parse_transforms: [],
+                            otp_root: None,
+                        },
                     },
                     NoManifest(
                         NoManifestConfig {
@@ -1543,10 +1763,17 @@ mod tests {
                             eqwalizer: EqwalizerConfig {
                                 enable_all: true,
                                 max_tasks: 4,
+                                max_check_parallelism: None,
                             },
                             rebar: ElpRebarConfig {
                                 profile: "test",
                             },
+                            compile: CompileConfig {
+                                macros: {},
+                                features: None,
+                                parse_transforms: [],
+                                otp_root: None,
+                            },
                         },
                         NoManifest(
                             NoManifestConfig {
@@ -1734,10 +1961,17 @@ mod tests {
                     eqwalizer: EqwalizerConfig {
                         enable_all: true,
                         max_tasks: 4,
+                        max_check_parallelism: None,
                     },
                     rebar: ElpRebarConfig {
                         profile: "other",
                     },
+                    compile: CompileConfig {
+                        macros: {},
+                        features: None,
+                        parse_transforms: [],
+                        otp_root: None,
+                    },
                 }
             "#]]
             .assert_eq(&debug_normalise_temp_dir(dir, &elp_config));
@@ -1775,10 +2009,12 @@ mod tests {
             eqwalizer: EqwalizerConfig {
                 enable_all: true,
                 max_tasks: 34,
+                max_check_parallelism: None,
             },
             rebar: ElpRebarConfig {
                 profile: "my_profile".to_string(),
             },
+            compile: CompileConfig::default(),
         })
         .unwrap();
         expect![[r#"
@@ -1867,10 +2103,17 @@ mod tests {
                 eqwalizer: EqwalizerConfig {
                     enable_all: true,
                     max_tasks: 34,
+                    max_check_parallelism: None,
                 },
                 rebar: ElpRebarConfig {
                     profile: "my_profile",
                 },
+                compile: CompileConfig {
+                    macros: {},
+                    features: None,
+                    parse_transforms: [],
+                    otp_root: None,
+                },
             }
         "#]]
         .assert_debug_eq(&lints);
@@ -1986,4 +2229,170 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn merge_macros_app_overrides_project() {
+        let spec = r#"
+        //- /.elp.toml
+        [compile]
+        macros = { FEATURE_X = "false", FEATURE_Y = "true" }
+        //- /app_a/src/app.erl
+        -module(app).
+        "#;
+        let dir = FixtureWithProjectMeta::gen_project(spec);
+        let (elp_config, _manifest) = ProjectManifest::discover(
+            &to_abs_path_buf(&dir.path().join("app_a/src/app.erl")).unwrap(),
+        )
+        .unwrap();
+        let project_macros = elp_config.compile.macros().unwrap();
+        let macro_def = |name: &str, value: &str| -> eetf::Term {
+            eetf::Tuple::from(vec![
+                eetf::Atom::from(name).into(),
+                eetf::Atom::from(value).into(),
+            ])
+            .into()
+        };
+        let app_macros = vec![
+            macro_def("FEATURE_X", "true"),
+            eetf::Atom::from("TEST").into(),
+        ];
+        let merged = merge_macros(&project_macros, &app_macros);
+        assert_eq!(
+            merged,
+            vec![
+                macro_def("FEATURE_X", "true"),
+                macro_def("FEATURE_Y", "true"),
+                eetf::Atom::from("TEST").into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn compile_macro_values() {
+        let spec = r#"
+        //- /.elp.toml
+        [compile]
+        macros = { COUNT = "42", NAME = "<<\"elp\">>", DEBUG = true, TRACE = false }
+        //- /app_a/src/app.erl
+        -module(app).
+        "#;
+        let dir = FixtureWithProjectMeta::gen_project(spec);
+        let (elp_config, _manifest) = ProjectManifest::discover(
+            &to_abs_path_buf(&dir.path().join("app_a/src/app.erl")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            elp_config.compile.macros().unwrap(),
+            vec![
+                eetf::Tuple::from(vec![
+                    eetf::Atom::from("COUNT").into(),
+                    eetf::FixInteger::from(42).into(),
+                ])
+                .into(),
+                eetf::Atom::from("DEBUG").into(),
+                eetf::Tuple::from(vec![
+                    eetf::Atom::from("NAME").into(),
+                    eetf::Binary {
+                        bytes: b"elp".to_vec()
+                    }
+                    .into(),
+                ])
+                .into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn compile_invalid_macro_value() {
+        let spec = r#"
+        //- /.elp.toml
+        [compile]
+        macros = { COUNT = "{42" }
+        //- /app_a/src/app.erl
+        -module(app).
+        "#;
+        let dir = FixtureWithProjectMeta::gen_project(spec);
+        let err = ElpConfig::try_parse(&to_abs_path_buf(dir.path()).unwrap()).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("invalid value for macro COUNT"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn merge_parse_transforms_global_first() {
+        let spec = r#"
+        //- /.elp.toml
+        [compile]
+        parse_transforms = ["lint_support", "shared"]
+        //- /app_a/src/app.erl
+        -module(app).
+        "#;
+        let dir = FixtureWithProjectMeta::gen_project(spec);
+        let (elp_config, _manifest) = ProjectManifest::discover(
+            &to_abs_path_buf(&dir.path().join("app_a/src/app.erl")).unwrap(),
+        )
+        .unwrap();
+        let global = elp_config.compile.parse_transforms();
+        let atom = |name: &str| -> eetf::Term { eetf::Atom::from(name).into() };
+
+        // An app declaring no transform gets the global ones
+        assert_eq!(
+            merge_parse_transforms(&global, &[]),
+            vec![atom("lint_support"), atom("shared")]
+        );
+        assert_eq!(
+            merge_parse_transforms(&global, &[atom("app_pt"), atom("shared")]),
+            vec![atom("lint_support"), atom("shared"), atom("app_pt")]
+        );
+    }
+
+    #[test]
+    fn compile_otp_root() {
+        let spec = r#"
+        //- /.elp.toml
+        [compile]
+        otp_root = "/opt/otp-27"
+        //- /app_a/src/app.erl
+        -module(app).
+        "#;
+        let dir = FixtureWithProjectMeta::gen_project(spec);
+        let (elp_config, _manifest) = ProjectManifest::discover(
+            &to_abs_path_buf(&dir.path().join("app_a/src/app.erl")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            elp_config.compile.otp_lib_dir().unwrap(),
+            Some(Utf8PathBuf::from("/opt/otp-27/lib"))
+        );
+        assert_eq!(CompileConfig::default().otp_lib_dir().unwrap(), None);
+    }
+
+    #[test]
+    fn compile_relative_otp_root() {
+        let spec = r#"
+        //- /.elp.toml
+        [compile]
+        otp_root = "otp"
+        //- /app_a/src/app.erl
+        -module(app).
+        "#;
+        let dir = FixtureWithProjectMeta::gen_project(spec);
+        let (elp_config, _manifest) = ProjectManifest::discover(
+            &to_abs_path_buf(&dir.path().join("app_a/src/app.erl")).unwrap(),
+        )
+        .unwrap();
+        let root = to_abs_path_buf(dir.path()).unwrap();
+        assert_eq!(
+            elp_config.compile.otp_lib_dir().unwrap(),
+            Some(root.join("otp").join("lib").into())
+        );
+
+        // Only the config file tells what a relative path is relative to
+        let unresolved = CompileConfig {
+            otp_root: Some("otp".to_string()),
+            ..CompileConfig::default()
+        };
+        assert!(unresolved.otp_lib_dir().is_err());
+    }
 }
@@ -53,7 +53,9 @@ pub use crate::ast::SourceFile;
 pub use crate::ptr::AstPtr;
 pub use crate::ptr::SyntaxNodePtr;
 pub use crate::syntax_error::SyntaxError;
+pub use crate::token_text::TokenPattern;
 pub use crate::token_text::TokenText;
+pub use crate::token_text::cmp_ignore_ascii_case;
 
 /// `Parse` is the result of the parsing: a syntax tree and a collection of
 /// errors.
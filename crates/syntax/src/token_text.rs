@@ -10,9 +10,11 @@
 
 //! Yet another version of owned string, backed by a syntax tree token.
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::fmt;
 use std::ops;
+use std::sync::Arc;
 
 use rowan::GreenToken;
 
@@ -21,6 +23,7 @@ pub struct TokenText<'a>(pub(crate) Repr<'a>);
 pub(crate) enum Repr<'a> {
     Borrowed(&'a str),
     Owned(GreenToken),
+    OwnedString(Arc<str>),
 }
 
 impl<'a> TokenText<'a> {
@@ -32,12 +35,144 @@ impl<'a> TokenText<'a> {
         TokenText(Repr::Owned(green))
     }
 
+    /// Text computed rather than taken from a syntax tree, e.g. a
+    /// normalized identifier.
+    pub fn from_string(text: impl Into<Arc<str>>) -> TokenText<'static> {
+        TokenText(Repr::OwnedString(text.into()))
+    }
+
     pub fn as_str(&self) -> &str {
         match &self.0 {
             Repr::Borrowed(it) => it,
             Repr::Owned(green) => green.text(),
+            Repr::OwnedString(text) => text,
         }
     }
+
+    /// The green token backing the text, if it was taken from one, for
+    /// reuse when building a new tree.
+    pub fn as_green_token(&self) -> Option<&GreenToken> {
+        match &self.0 {
+            Repr::Owned(green) => Some(green),
+            Repr::Borrowed(_) | Repr::OwnedString(_) => None,
+        }
+    }
+
+    /// `self` without leading and trailing whitespace, borrowing from it.
+    pub fn trim(&self) -> TokenText<'_> {
+        TokenText::borrowed(self.as_str().trim())
+    }
+
+    pub fn trim_start(&self) -> TokenText<'_> {
+        TokenText::borrowed(self.as_str().trim_start())
+    }
+
+    pub fn trim_end(&self) -> TokenText<'_> {
+        TokenText::borrowed(self.as_str().trim_end())
+    }
+
+    /// The text with ASCII letters lowercased, borrowed when it has no
+    /// uppercase ASCII letters to begin with.
+    pub fn to_ascii_lowercase_cow(&self) -> Cow<'_, str> {
+        let text = self.as_str();
+        if text.bytes().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(text.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    /// Compare the texts as if their ASCII letters were lowercased,
+    /// without allocating, e.g. to sort identifiers in an outline.
+    pub fn cmp_ignore_ascii_case(&self, other: &TokenText) -> Ordering {
+        let lowercase = |c: char| c.to_ascii_lowercase();
+        self.as_str()
+            .chars()
+            .map(lowercase)
+            .cmp(other.as_str().chars().map(lowercase))
+    }
+
+    pub fn starts_with(&self, pat: impl TokenPattern) -> bool {
+        pat.is_prefix_of(self.as_str())
+    }
+
+    pub fn ends_with(&self, pat: impl TokenPattern) -> bool {
+        pat.is_suffix_of(self.as_str())
+    }
+
+    pub fn contains(&self, pat: impl TokenPattern) -> bool {
+        pat.is_contained_in(self.as_str())
+    }
+
+    /// The texts of `parts`, one after the other.
+    pub fn concat(parts: &[&TokenText]) -> String {
+        TokenText::join(parts, "")
+    }
+
+    /// The texts of `parts`, separated by `sep`, e.g. to build a
+    /// qualified `module:function` name.
+    pub fn join(parts: &[&TokenText], sep: &str) -> String {
+        let len = parts.iter().map(|part| part.len()).sum::<usize>()
+            + sep.len() * parts.len().saturating_sub(1);
+        let mut result = String::with_capacity(len);
+        for (idx, part) in parts.iter().enumerate() {
+            if idx > 0 {
+                result.push_str(sep);
+            }
+            result.push_str(part.as_str());
+        }
+        result
+    }
+}
+
+/// `TokenText::cmp_ignore_ascii_case` as a function, to pass to
+/// `sort_by` and the like.
+pub fn cmp_ignore_ascii_case(a: &TokenText, b: &TokenText) -> Ordering {
+    a.cmp_ignore_ascii_case(b)
+}
+
+/// The patterns `TokenText::starts_with`, `ends_with` and `contains`
+/// accept, a stable stand-in for `std::str::pattern::Pattern`.
+pub trait TokenPattern {
+    fn is_prefix_of(self, text: &str) -> bool;
+    fn is_suffix_of(self, text: &str) -> bool;
+    fn is_contained_in(self, text: &str) -> bool;
+}
+
+impl TokenPattern for &str {
+    fn is_prefix_of(self, text: &str) -> bool {
+        text.starts_with(self)
+    }
+    fn is_suffix_of(self, text: &str) -> bool {
+        text.ends_with(self)
+    }
+    fn is_contained_in(self, text: &str) -> bool {
+        text.contains(self)
+    }
+}
+
+impl TokenPattern for &String {
+    fn is_prefix_of(self, text: &str) -> bool {
+        self.as_str().is_prefix_of(text)
+    }
+    fn is_suffix_of(self, text: &str) -> bool {
+        self.as_str().is_suffix_of(text)
+    }
+    fn is_contained_in(self, text: &str) -> bool {
+        self.as_str().is_contained_in(text)
+    }
+}
+
+impl TokenPattern for char {
+    fn is_prefix_of(self, text: &str) -> bool {
+        text.starts_with(self)
+    }
+    fn is_suffix_of(self, text: &str) -> bool {
+        text.ends_with(self)
+    }
+    fn is_contained_in(self, text: &str) -> bool {
+        text.contains(self)
+    }
 }
 
 impl ops::Deref for TokenText<'_> {
@@ -79,6 +214,48 @@ impl PartialEq<TokenText<'_>> for String {
         other == self
     }
 }
+impl PartialEq<Cow<'_, str>> for TokenText<'_> {
+    fn eq(&self, other: &Cow<'_, str>) -> bool {
+        self.as_str() == other.as_ref()
+    }
+}
+impl PartialEq<TokenText<'_>> for Cow<'_, str> {
+    fn eq(&self, other: &TokenText) -> bool {
+        other == self
+    }
+}
+impl PartialEq<Box<str>> for TokenText<'_> {
+    fn eq(&self, other: &Box<str>) -> bool {
+        self.as_str() == &**other
+    }
+}
+impl PartialEq<TokenText<'_>> for Box<str> {
+    fn eq(&self, other: &TokenText) -> bool {
+        other == self
+    }
+}
+impl PartialEq<[u8]> for TokenText<'_> {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_bytes() == other
+    }
+}
+impl PartialEq<&'_ [u8]> for TokenText<'_> {
+    fn eq(&self, other: &&[u8]) -> bool {
+        self.as_bytes() == *other
+    }
+}
+/// Whether the text is exactly the one `char`.
+impl PartialEq<char> for TokenText<'_> {
+    fn eq(&self, other: &char) -> bool {
+        let mut chars = self.as_str().chars();
+        chars.next() == Some(*other) && chars.next().is_none()
+    }
+}
+impl PartialEq<TokenText<'_>> for char {
+    fn eq(&self, other: &TokenText) -> bool {
+        other == self
+    }
+}
 impl PartialEq for TokenText<'_> {
     fn eq(&self, other: &TokenText) -> bool {
         self.as_str() == other.as_str()
@@ -105,3 +282,153 @@ impl fmt::Debug for TokenText<'_> {
         fmt::Debug::fmt(self.as_str(), f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rowan::SyntaxKind;
+
+    use super::*;
+
+    #[test]
+    fn join_tokens() {
+        let module = TokenText::borrowed("lists");
+        let function = TokenText::borrowed("map");
+        let arity = TokenText::borrowed("2");
+        let parts = [&module, &function, &arity];
+
+        let joined = TokenText::join(&parts, ":");
+        assert_eq!(joined, "lists:map:2");
+        assert_eq!(joined.capacity(), joined.len());
+        assert_eq!(TokenText::concat(&parts), "listsmap2");
+        assert_eq!(TokenText::join(&[], ":"), "");
+    }
+
+    #[test]
+    fn trim_tokens() {
+        let borrowed = TokenText::borrowed("  padded ");
+        let owned = TokenText::owned(GreenToken::new(SyntaxKind(0), "  padded "));
+        for text in [borrowed, owned] {
+            assert_eq!(text.trim(), "padded");
+            assert_eq!(text.trim_start(), "padded ");
+            assert_eq!(text.trim_end(), "  padded");
+            assert!(matches!(text.trim().0, Repr::Borrowed(_)));
+        }
+    }
+
+    #[test]
+    fn starts_with() {
+        let text = TokenText::borrowed("is_valid_test");
+        assert!(text.starts_with("is_"));
+        assert!(text.starts_with('i'));
+        assert!(!text.starts_with("_test"));
+    }
+
+    #[test]
+    fn ends_with() {
+        let text = TokenText::borrowed("is_valid_test");
+        assert!(text.ends_with("_test"));
+        assert!(text.ends_with(&"test".to_string()));
+        assert!(text.ends_with('t'));
+        assert!(!text.ends_with("is_"));
+    }
+
+    #[test]
+    fn contains() {
+        let text = TokenText::borrowed("is_valid_test");
+        assert!(text.contains("valid"));
+        assert!(text.contains('_'));
+        assert!(!text.contains('@'));
+    }
+
+    #[test]
+    fn from_string() {
+        let text = TokenText::from_string(format!("{}_{}", "my", "var"));
+        assert_eq!(text, "my_var");
+        assert_eq!(text, TokenText::borrowed("my_var"));
+        assert_eq!(text.trim(), "my_var");
+    }
+
+    #[test]
+    fn eq_char() {
+        let plus = TokenText::borrowed("+");
+        assert_eq!(plus, '+');
+        assert_eq!('+', plus);
+        assert_ne!(plus, '-');
+
+        let bsl = TokenText::borrowed("bsl");
+        assert_ne!(bsl, 'b');
+        assert_ne!(TokenText::borrowed(""), '+');
+
+        let multibyte = TokenText::owned(GreenToken::new(SyntaxKind(0), "é"));
+        assert_eq!(multibyte, 'é');
+        assert_ne!(TokenText::borrowed("é+"), 'é');
+    }
+
+    #[test]
+    fn to_ascii_lowercase_cow() {
+        let lower = TokenText::borrowed("behaviour");
+        assert!(matches!(
+            lower.to_ascii_lowercase_cow(),
+            Cow::Borrowed("behaviour")
+        ));
+
+        let mixed = TokenText::owned(GreenToken::new(SyntaxKind(0), "Behaviour"));
+        let lowered = mixed.to_ascii_lowercase_cow();
+        assert!(matches!(lowered, Cow::Owned(_)));
+        assert_eq!(lowered, "behaviour");
+    }
+
+    #[test]
+    fn sort_ignoring_ascii_case() {
+        let mut texts = vec![
+            TokenText::borrowed("Beta"),
+            TokenText::owned(GreenToken::new(SyntaxKind(0), "alpha")),
+            TokenText::from_string("Gamma"),
+        ];
+        texts.sort_by(cmp_ignore_ascii_case);
+        assert_eq!(texts, vec!["alpha", "Beta", "Gamma"]);
+
+        let upper = TokenText::borrowed("ALPHA");
+        assert_eq!(upper.cmp_ignore_ascii_case(&texts[0]), Ordering::Equal);
+        assert_eq!(
+            upper.cmp_ignore_ascii_case(&TokenText::borrowed("alphabet")),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn eq_bytes() {
+        let text = TokenText::borrowed("utf8");
+        assert!(text == b"utf8"[..]);
+        assert!(text == &b"utf8"[..]);
+        assert!(text != &b"utf16"[..]);
+    }
+
+    #[test]
+    fn eq_cow() {
+        let text = TokenText::borrowed("gen_server");
+        let borrowed: Cow<str> = Cow::Borrowed("gen_server");
+        let owned: Cow<str> = Cow::Owned("gen_server".to_string());
+        assert_eq!(text, borrowed);
+        assert_eq!(owned, text);
+        assert_ne!(text, Cow::Borrowed("gen_statem"));
+    }
+
+    #[test]
+    fn eq_box_str() {
+        let text = TokenText::owned(GreenToken::new(SyntaxKind(0), "gen_server"));
+        let boxed: Box<str> = "gen_server".into();
+        assert_eq!(text, boxed);
+        assert_eq!(boxed, text);
+        assert_ne!(text, Box::<str>::from("gen_statem"));
+    }
+
+    #[test]
+    fn as_green_token() {
+        let green = GreenToken::new(SyntaxKind(0), "foo");
+        let owned = TokenText::owned(green.clone());
+        assert_eq!(owned.as_green_token(), Some(&green));
+        assert_eq!(TokenText::borrowed("foo").as_green_token(), None);
+        assert_eq!(TokenText::from_string("foo").as_green_token(), None);
+    }
+}
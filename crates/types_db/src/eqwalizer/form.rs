@@ -168,6 +168,9 @@ pub struct TypeDecl {
     #[serde(default)]
     pub params: Vec<VarType>,
     pub body: Type,
+    /// Declared with `-opaque`. Not sent to eqWAlizer.
+    #[serde(default, skip_serializing)]
+    pub opaque: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -189,6 +192,9 @@ pub struct ExternalTypeDecl {
     #[serde(default)]
     pub params: Vec<StringId>,
     pub body: ext_types::ExtType,
+    /// Declared with `-opaque`.
+    #[serde(default)]
+    pub opaque: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -66,6 +66,22 @@ impl EqwalizerDiagnostic {
             None => "".to_string(),
         }
     }
+
+    /// The message shown to users, with the expression, the explanation
+    /// and a link to the docs.
+    pub fn full_message(&self) -> String {
+        let explanation = match &self.explanation {
+            Some(s) => format!("\n\n{s}"),
+            None => "".to_string(),
+        };
+        format!(
+            "{}{}{}\n        See {}",
+            self.expr_string(),
+            self.message,
+            explanation,
+            self.uri
+        )
+    }
 }
 
 fn deserialize_text_range<'de, D>(deserializer: D) -> Result<elp_syntax::TextRange, D::Error>
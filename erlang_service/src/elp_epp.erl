@@ -368,6 +368,7 @@ when
         | {'source_name', SourceName :: file:name()}
         | {'macros', PredefMacros :: macros()}
         | {'default_encoding', DefEncoding :: source_encoding()}
+        | {'feature', Feature :: atom(), 'enable' | 'disable'}
         | 'extra'
     ],
     Form ::
@@ -704,7 +705,7 @@ server(Pid, Id, Name, FileId, Options) ->
 
 init_server(Pid, FileName, Options0, St0) ->
     {ScanOpts0, Options} = proplists:split(Options0, [structured_comments]),
-    ScanOpts = lists:append(ScanOpts0),
+    ScanOpts = lists:append(ScanOpts0) ++ feature_scan_opts(Options),
     SourceName = proplists:get_value(source_name, Options, FileName),
     Pdm = proplists:get_value(macros, Options, []),
     Offset = proplists:get_value(offset, Options, 0),
@@ -1234,8 +1235,34 @@ scan_feature(Toks, {atom, _, Tag} = Token, From, St) ->
     epp_reply(From, {error, {loc(T), elp_epp, {bad, Tag}}}),
     wait_req_scan(St).
 
-%% This is just a stub. We don't update any feature in the ELP fork.
-update_features(St0, _Ind, _Ftr, _Loc) -> {ok, St0}.
+%% A `-feature` attribute of the module overrides the `{feature, ...}`
+%% option for the forms that follow it, like in the compiler. Features
+%% other than `maybe_expr` are always enabled in the ELP fork.
+update_features(St0, enable, maybe_expr, _Loc) ->
+    ScanOpts = lists:keydelete(reserved_word_fun, 1, St0#epp.scan_opts),
+    {ok, St0#epp{scan_opts = ScanOpts}};
+update_features(St0, disable, maybe_expr, _Loc) ->
+    ScanOpts = lists:keydelete(reserved_word_fun, 1, St0#epp.scan_opts),
+    NoMaybe = {reserved_word_fun, fun reserved_word_no_maybe/1},
+    {ok, St0#epp{scan_opts = [NoMaybe | ScanOpts]}};
+update_features(St0, _Ind, _Ftr, _Loc) ->
+    {ok, St0}.
+
+%% All features are enabled by default in the ELP fork. A feature can
+%% be switched off for the whole module with the
+%% `{feature, Name, disable}` option, unless the module enables it
+%% itself, see `update_features/4`.
+feature_scan_opts(Options) ->
+    case lists:member({feature, maybe_expr, disable}, Options) of
+        true ->
+            [{reserved_word_fun, fun reserved_word_no_maybe/1}];
+        false ->
+            []
+    end.
+
+reserved_word_no_maybe('maybe') -> false;
+reserved_word_no_maybe('else') -> false;
+reserved_word_no_maybe(Word) -> elp_scan:reserved_word(Word).
 
 %% scan_define(Tokens, DefineToken, From, EppState)
 